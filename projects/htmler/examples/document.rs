@@ -20,6 +20,6 @@ fn main() {
     println!("{:#?}", document);
 
     for node in document.select(&selector) {
-        println!("{:?}", node.value());
+        println!("{:?}", node.as_kind());
     }
 }
@@ -21,6 +21,6 @@ fn main() {
     println!("{:#?}", fragment);
 
     for node in fragment.select(&selector) {
-        println!("{:?}", node.value());
+        println!("{:?}", node.as_kind());
     }
 }
@@ -0,0 +1,122 @@
+/// Builds an [`Element`](crate::builder::Element) from markup-like syntax.
+///
+/// - `tag`, `tag(attributes)`, `tag { children }` and `tag(attributes) { children }` build elements.
+/// - Attributes are `name = value`, separated by commas. Names which are not identifiers are written as strings.
+/// - A string literal is text, and so is any expression in parentheses, converted with `ToString`.
+/// - An expression in brackets is an iterator of children, see [`Element::children`](crate::builder::Element::children).
+/// - Parentheses right after a tag are its attributes, put a `;` in between to add text instead.
+///
+/// Void elements like `<br>` and `<img>` cannot have children, which is checked at compile time. Other nesting rules,
+/// such as no `<div>` inside a `<p>`, are not checked.
+///
+/// Each child takes a level of macro recursion, so an element with more than about a hundred children needs a higher
+/// `recursion_limit`. Children in brackets take one level however many there are.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::html;
+///
+/// let name = "<world>";
+/// let items = ["one", "two"];
+/// let element = html! {
+///     div(class = "greeting", "data-id" = 1.to_string()) {
+///         h1 { "Hello, " (name) }
+///         br
+///         ul { [items.iter().map(|item| html! { li { (item) } })] }
+///     }
+/// };
+/// assert_eq!(
+///     element.into_fragment().root_node().inner_html(),
+///     r#"<div class="greeting" data-id="1"><h1>Hello, &lt;world&gt;</h1><br><ul><li>one</li><li>two</li></ul></div>"#
+/// );
+/// ```
+///
+/// ```compile_fail
+/// htmler::html! { img(src = "a.png") { "caption" } };
+/// ```
+#[macro_export]
+macro_rules! html {
+    ($tag:ident $(($($attrs:tt)*))? $({ $($children:tt)* })?) => {
+        $crate::__html_element!($tag $(($($attrs)*))? $({ $($children)* })?)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __html_element {
+    ($tag:ident $(($($attrs:tt)*))? $({ $($children:tt)* })?) => {{
+        $($crate::__html_void!($tag { $($children)* });)?
+        let element = $crate::builder::Element::new(stringify!($tag));
+        $(let element = $crate::__html_attrs!(element; $($attrs)*);)?
+        $(let element = $crate::__html_children!(element; $($children)*);)?
+        element
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __html_void {
+    ($tag:ident { $($children:tt)* }) => {
+        $crate::__html_void!(@check $tag; area base br col embed hr img input link meta source track wbr)
+    };
+    (@check $tag:ident; $($void:ident)*) => {
+        $($crate::__html_void!(@is $tag $void);)*
+    };
+    (@is area area) => { compile_error!("<area> cannot have children") };
+    (@is base base) => { compile_error!("<base> cannot have children") };
+    (@is br br) => { compile_error!("<br> cannot have children") };
+    (@is col col) => { compile_error!("<col> cannot have children") };
+    (@is embed embed) => { compile_error!("<embed> cannot have children") };
+    (@is hr hr) => { compile_error!("<hr> cannot have children") };
+    (@is img img) => { compile_error!("<img> cannot have children") };
+    (@is input input) => { compile_error!("<input> cannot have children") };
+    (@is link link) => { compile_error!("<link> cannot have children") };
+    (@is meta meta) => { compile_error!("<meta> cannot have children") };
+    (@is source source) => { compile_error!("<source> cannot have children") };
+    (@is track track) => { compile_error!("<track> cannot have children") };
+    (@is wbr wbr) => { compile_error!("<wbr> cannot have children") };
+    (@is $tag:ident $void:ident) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __html_attrs {
+    ($element:expr;) => { $element };
+    ($element:expr; $name:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__html_attrs!($element.attr(stringify!($name), $value); $($($rest)*)?)
+    };
+    ($element:expr; $name:literal = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__html_attrs!($element.attr($name, $value); $($($rest)*)?)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __html_children {
+    ($element:expr;) => { $element };
+    ($element:expr; ; $($rest:tt)*) => {
+        $crate::__html_children!($element; $($rest)*)
+    };
+    ($element:expr; $text:literal $($rest:tt)*) => {
+        $crate::__html_children!($element.child($crate::builder::text($text)); $($rest)*)
+    };
+    ($element:expr; ($text:expr) $($rest:tt)*) => {
+        $crate::__html_children!($element.child(::std::string::ToString::to_string(&$text)); $($rest)*)
+    };
+    ($element:expr; [$children:expr] $($rest:tt)*) => {
+        $crate::__html_children!($element.children($children); $($rest)*)
+    };
+    ($element:expr; $tag:ident ($($attrs:tt)*) { $($children:tt)* } $($rest:tt)*) => {
+        $crate::__html_children!($element.child($crate::__html_element!($tag ($($attrs)*) { $($children)* })); $($rest)*)
+    };
+    ($element:expr; $tag:ident ($($attrs:tt)*) $($rest:tt)*) => {
+        $crate::__html_children!($element.child($crate::__html_element!($tag ($($attrs)*))); $($rest)*)
+    };
+    ($element:expr; $tag:ident { $($children:tt)* } $($rest:tt)*) => {
+        $crate::__html_children!($element.child($crate::__html_element!($tag { $($children)* })); $($rest)*)
+    };
+    ($element:expr; $tag:ident $($rest:tt)*) => {
+        $crate::__html_children!($element.child($crate::__html_element!($tag)); $($rest)*)
+    };
+}
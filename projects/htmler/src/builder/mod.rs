@@ -0,0 +1,272 @@
+//! Building trees in code.
+//!
+//! Elements are built with [`Element`], or with the [`html!`](crate::html!) macro which rejects children of void
+//! elements at compile time. Other nesting rules are not checked. Text is stored as is and escaped when the tree is serialized.
+//!
+//! # Examples
+//!
+//! ```
+//! use htmler::builder::{text, Element};
+//!
+//! let link = Element::new("a").attr("href", "/?a=1&b=2").child(text("<next>"));
+//! let list =
+//!     Element::new("ul").children(["one", "two"].map(|item| Element::new("li").child(item)));
+//! let html = Element::new("nav").child(link).child(list).into_fragment();
+//! assert_eq!(
+//!     html.root_node().inner_html(),
+//!     r#"<nav><a href="/?a=1&amp;b=2">&lt;next&gt;</a><ul><li>one</li><li>two</li></ul></nav>"#
+//! );
+//! ```
+
+use ego_tree::{NodeId, Tree};
+use html5ever::{tree_builder::QuirksMode, LocalName, Namespace, QualName};
+use indexmap::IndexMap;
+
+use crate::{node::NodeData, Html, HtmlStr, NodeKind};
+
+mod macros;
+
+/// An element under construction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    name: QualName,
+    attrs: IndexMap<QualName, HtmlStr>,
+    children: Vec<Child>,
+}
+
+/// A child of an [`Element`] under construction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Child {
+    /// An element.
+    Element(Element),
+    /// Text, which is escaped when serialized.
+    Text(HtmlStr),
+    /// A comment.
+    Comment(HtmlStr),
+}
+
+/// Creates a text child.
+pub fn text(text: &str) -> Child {
+    Child::Text(text.into())
+}
+
+/// Creates a comment child.
+pub fn comment(comment: &str) -> Child {
+    Child::Comment(comment.into())
+}
+
+impl From<Element> for Child {
+    fn from(element: Element) -> Self {
+        Child::Element(element)
+    }
+}
+
+impl From<&str> for Child {
+    fn from(text: &str) -> Self {
+        Child::Text(text.into())
+    }
+}
+
+impl From<String> for Child {
+    fn from(text: String) -> Self {
+        Child::Text(text)
+    }
+}
+
+impl Element {
+    /// Creates an HTML element. The name is lowercased, as the parser does.
+    pub fn new(name: &str) -> Self {
+        Self::new_ns(ns!(html), name)
+    }
+
+    /// Creates an element in another namespace, e.g. `ns!(svg)`.
+    ///
+    /// Names in the HTML namespace are lowercased, others are kept as is.
+    pub fn new_ns(ns: Namespace, name: &str) -> Self {
+        let name = adjust_case(&ns, name);
+        Element { name: QualName::new(None, ns, name), attrs: IndexMap::new(), children: Vec::new() }
+    }
+
+    /// Sets an attribute, replacing an earlier value but keeping its position.
+    ///
+    /// Names are lowercased on HTML elements, as the parser does.
+    pub fn attr(mut self, name: &str, value: impl Into<HtmlStr>) -> Self {
+        let name = adjust_case(&self.name.ns, name);
+        self.attrs.insert(QualName::new(None, ns!(), name), value.into());
+        self
+    }
+
+    /// Adds a class to the `class` attribute.
+    pub fn class(mut self, class: &str) -> Self {
+        let classes = self.attrs.entry(QualName::new(None, ns!(), local_name!("class"))).or_default();
+        if !classes.is_empty() {
+            classes.push(' ');
+        }
+        classes.push_str(class);
+        self
+    }
+
+    /// Appends a child.
+    pub fn child(mut self, child: impl Into<Child>) -> Self {
+        self.children.push(child.into());
+        self
+    }
+
+    /// Appends children.
+    pub fn children<I>(mut self, children: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Child>,
+    {
+        self.children.extend(children.into_iter().map(Into::into));
+        self
+    }
+
+    /// Builds a tree with this element as its root.
+    ///
+    /// It can be inserted into an [`Html`] with its structural editing methods, e.g. [`Html::append_child`].
+    pub fn into_tree(self) -> Tree<NodeKind> {
+        let (data, children) = self.into_data();
+        let mut tree = Tree::new(NodeKind::Element(data));
+        let root = tree.root().id();
+        build_children(&mut tree, root, children);
+        tree
+    }
+
+    /// Builds a fragment holding this element, as if it had been parsed with [`Html::parse_fragment`].
+    pub fn into_fragment(self) -> Html {
+        Html::fragment_of(self.into_tree().root(), QuirksMode::NoQuirks)
+    }
+
+    fn into_data(self) -> (NodeData, Vec<Child>) {
+        let mut data = NodeData::new(self.name, Vec::new());
+        data.attrs = self.attrs;
+        (data, self.children)
+    }
+}
+
+/// Appends the children to `parent`, merging adjacent text like the parser does.
+fn build_children(tree: &mut Tree<NodeKind>, parent: NodeId, children: Vec<Child>) {
+    for child in children {
+        let mut parent = tree.get_mut(parent).unwrap();
+        match child {
+            Child::Element(element) => {
+                let (data, children) = element.into_data();
+                let id = parent.append(NodeKind::Element(data)).id();
+                build_children(tree, id, children);
+            }
+            Child::Text(text) => {
+                let mut last = parent.last_child();
+                match last.as_mut().map(|last| last.value()) {
+                    Some(NodeKind::Text(t)) => t.text.push_str(&text),
+                    _ => {
+                        parent.append(NodeKind::Text(text.into()));
+                    }
+                }
+            }
+            Child::Comment(comment) => {
+                parent.append(NodeKind::Comment(comment.into()));
+            }
+        }
+    }
+}
+
+/// Lowercases a name in the HTML namespace.
+fn adjust_case(ns: &Namespace, name: &str) -> LocalName {
+    if *ns == ns!(html) { LocalName::from(name.to_ascii_lowercase()) } else { LocalName::from(name) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{comment, text, Child, Element};
+    use crate::{Html, Selector};
+
+    #[test]
+    fn escapes_and_merges_text() {
+        let p = Element::new("p").child("a < b").child(text(" & c")).child(comment("-")).child(String::from("\"d\""));
+        let tree = p.clone().into_tree();
+        assert_eq!(tree.root().children().count(), 3);
+        assert_eq!(p.into_fragment().root_node().inner_html(), "<p>a &lt; b &amp; c<!----->\"d\"</p>");
+    }
+
+    #[test]
+    fn attributes_keep_order() {
+        let a = Element::new("a").attr("href", "x").class("b").attr("title", "t").class("c").attr("href", String::from("y"));
+        let html = a.into_fragment();
+        assert_eq!(html.root_node().inner_html(), r#"<a href="y" class="b c" title="t"></a>"#);
+        assert_eq!(html.select(&Selector::new("a.b.c")).count(), 1);
+    }
+
+    #[test]
+    fn namespaces() {
+        let svg = Element::new_ns(ns!(svg), "svg").child(Element::new_ns(ns!(svg), "circle").attr("r", "1"));
+        let html = svg.into_fragment();
+        let circle = html.select(&Selector::new("circle")).next().unwrap();
+        assert_eq!(circle.as_data().unwrap().name.ns, ns!(svg));
+        assert_eq!(html.root_node().inner_html(), r#"<svg><circle r="1"></circle></svg>"#);
+    }
+
+    #[test]
+    fn lowercases_html_names() {
+        let html = Element::new("DIV").child(Element::new_ns(ns!(svg), "foreignObject")).into_fragment();
+        assert_eq!(html.select(&Selector::new("div")).count(), 1);
+        assert_eq!(html.root_node().inner_html(), "<div><foreignObject></foreignObject></div>");
+
+        let html =
+            Element::new("a").attr("HREF", "x").child(Element::new_ns(ns!(svg), "svg").attr("viewBox", "0")).into_fragment();
+        assert_eq!(html.select(&Selector::new("a[href]")).count(), 1);
+        assert_eq!(html.root_node().inner_html(), r#"<a href="x"><svg viewBox="0"></svg></a>"#);
+    }
+
+    #[test]
+    fn inserts_into_html() {
+        let mut html = Html::parse_fragment("<ul><li>a</li></ul>");
+        let ul = html.select(&Selector::new("ul")).next().unwrap().id();
+        let li = html.append_child(ul, Element::new("li").child(Element::new("b").child("b")));
+        assert_eq!(html.root_node().inner_html(), "<ul><li>a</li><li><b>b</b></li></ul>");
+        assert_eq!(html.tree.get(li).unwrap().parent().unwrap().id(), ul);
+    }
+
+    #[test]
+    fn macro_matches_builder() {
+        let title = "T";
+        let built = crate::html! {
+            section(id = "s", "aria-label" = title) {
+                h2 { (title) }
+                img(src = "a.png", alt = "")
+                p { "one" br; (2) hr "three" }
+                [vec![Child::from("x"), comment("y")]]
+            }
+        };
+        let expected = Element::new("section")
+            .attr("id", "s")
+            .attr("aria-label", "T")
+            .child(Element::new("h2").child("T"))
+            .child(Element::new("img").attr("src", "a.png").attr("alt", ""))
+            .child(Element::new("p").child("one").child(Element::new("br")).child("2").child(Element::new("hr")).child("three"))
+            .child("x")
+            .child(comment("y"));
+        assert_eq!(built, expected);
+    }
+
+    #[test]
+    fn macro_with_many_children() {
+        // A hundred children, ten per line.
+        let built = crate::html! {
+            p {
+                "0" br "1" br "2" br "3" br "4" br
+                "5" br "6" br "7" br "8" br "9" br
+                "0" br "1" br "2" br "3" br "4" br
+                "5" br "6" br "7" br "8" br "9" br
+                "0" br "1" br "2" br "3" br "4" br
+                "5" br "6" br "7" br "8" br "9" br
+                "0" br "1" br "2" br "3" br "4" br
+                "5" br "6" br "7" br "8" br "9" br
+                "0" br "1" br "2" br "3" br "4" br
+                "5" br "6" br "7" br "8" br "9" br
+            }
+        };
+        let expected = (0..50).fold(Element::new("p"), |p, i| p.child((i % 10).to_string()).child(Element::new("br")));
+        assert_eq!(built, expected);
+    }
+}
@@ -0,0 +1,682 @@
+//! Structural differences between trees.
+//!
+//! Children are compared in order: identical subtrees are kept, other nodes of the same kind and name are paired up
+//! and compared in depth, and whatever is left over was inserted or removed. A removed subtree which is inserted
+//! elsewhere unchanged was moved. The contents of paired `<template>` elements are compared in the same way.
+//!
+//! # Examples
+//!
+//! ```
+//! use htmler::{diff::Edit, Html};
+//!
+//! let old = Html::parse_fragment(r#"<p>a</p><p class="x">b</p><hr>"#);
+//! let new = Html::parse_fragment(r#"<hr><p>a</p><p class="y">c</p>"#);
+//! let diff = htmler::diff(&old, &new);
+//! assert!(matches!(diff.edits(), [Edit::Attributes { .. }, Edit::Change { .. }, Edit::Move { .. }]));
+//! assert_eq!(
+//!     diff.render().root_node().inner_html(),
+//!     r#"<ins class="moved"><hr></ins><p>a</p><p class="y" data-diff-attributes="class"><del>b</del><ins>c</ins></p><del class="moved"><hr></del>"#
+//! );
+//! ```
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::Range,
+};
+
+use ego_tree::{NodeId, NodeRef, Tree};
+use html5ever::QualName;
+
+use crate::{node::NodeData, Html, HtmlStr, NodeKind};
+
+mod patch;
+mod render;
+
+pub use self::patch::{HtmlPatch, NodePath, PatchContent, PatchOp, PatchRef, PathStep};
+
+/// Options for comparing trees.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    whitespace: bool,
+    attribute_order: bool,
+}
+
+/// A change from the old tree to the new one.
+///
+/// Nodes are identified by their IDs in the old and new tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    /// A subtree of the new tree which is not in the old one.
+    Insert {
+        /// The root of the subtree in the new tree.
+        new: NodeId,
+    },
+    /// A subtree of the old tree which is not in the new one.
+    Remove {
+        /// The root of the subtree in the old tree.
+        old: NodeId,
+    },
+    /// A subtree which is unchanged but somewhere else.
+    Move {
+        /// Where the subtree was in the old tree.
+        old: NodeId,
+        /// Where the subtree is in the new tree.
+        new: NodeId,
+    },
+    /// The content of a text node, comment or processing instruction changed.
+    Change {
+        /// The node in the old tree.
+        old: NodeId,
+        /// The node in the new tree.
+        new: NodeId,
+    },
+    /// The attributes of an element changed.
+    Attributes {
+        /// The element in the old tree.
+        old: NodeId,
+        /// The element in the new tree.
+        new: NodeId,
+        /// The changes, in the order of the new attributes, then the removed ones.
+        changes: Vec<AttributeChange>,
+    },
+}
+
+/// A change of an attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeChange {
+    /// An attribute was added.
+    Added {
+        /// The attribute name.
+        name: HtmlStr,
+        /// The new value.
+        value: HtmlStr,
+    },
+    /// An attribute was removed.
+    Removed {
+        /// The attribute name.
+        name: HtmlStr,
+        /// The old value.
+        value: HtmlStr,
+    },
+    /// The value of an attribute changed.
+    Changed {
+        /// The attribute name.
+        name: HtmlStr,
+        /// The old value.
+        old: HtmlStr,
+        /// The new value.
+        new: HtmlStr,
+    },
+    /// The attributes are in another order, unless [`DiffOptions::ignore_attribute_order`] is set.
+    Reordered,
+}
+
+/// The differences between two trees, see [`diff`](crate::diff()).
+#[derive(Debug, Clone)]
+pub struct Diff<'a> {
+    old: &'a Html,
+    new: &'a Html,
+    edits: Vec<Edit>,
+    /// Nodes of the old tree kept in place, mapped to the new tree.
+    pairs: HashMap<NodeId, NodeId>,
+}
+
+/// Compares two trees with the default options.
+pub fn diff<'a>(old: &'a Html, new: &'a Html) -> Diff<'a> {
+    DiffOptions::new().diff(old, new)
+}
+
+impl DiffOptions {
+    /// Creates the default options, which compare everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to skip text nodes which only contain whitespace. Default: `false`.
+    pub fn ignore_whitespace(mut self, ignore: bool) -> Self {
+        self.whitespace = ignore;
+        self
+    }
+
+    /// Whether attributes in another order are equal. Default: `false`.
+    pub fn ignore_attribute_order(mut self, ignore: bool) -> Self {
+        self.attribute_order = ignore;
+        self
+    }
+
+    /// Compares two trees.
+    pub fn diff<'a>(&self, old: &'a Html, new: &'a Html) -> Diff<'a> {
+        let mut classes = Classes::default();
+        let mut differ = Differ {
+            options: *self,
+            old: &old.tree,
+            new: &new.tree,
+            old_classes: self.classes(&old.tree, &mut classes),
+            new_classes: self.classes(&new.tree, &mut classes),
+            edits: Vec::new(),
+            pairs: HashMap::new(),
+        };
+        differ.pair(old.tree.root().id(), new.tree.root().id());
+        differ.find_moves();
+        Diff { old, new, edits: differ.edits, pairs: differ.pairs }
+    }
+
+    /// Returns the children which are compared.
+    fn children<'t>(&self, node: NodeRef<'t, NodeKind>) -> impl Iterator<Item = NodeRef<'t, NodeKind>> {
+        let whitespace = self.whitespace;
+        node.children().filter(move |child| match child.value() {
+            NodeKind::Text(t) => !whitespace || !t.trim_matches(|c: char| c.is_ascii_whitespace()).is_empty(),
+            _ => true,
+        })
+    }
+
+    /// Numbers the subtrees reachable from the root and counts their nodes.
+    ///
+    /// The contents of a `<template>` element count as part of its subtree.
+    fn classes<'t>(&self, tree: &'t Tree<NodeKind>, classes: &mut Classes<'t>) -> HashMap<NodeId, (usize, usize)> {
+        let mut result: HashMap<NodeId, (usize, usize)> = HashMap::new();
+        // Each node is pushed again once its children are pending, and numbered when it is popped the second time.
+        let mut pending = vec![(tree.root(), false)];
+        while let Some((node, ready)) = pending.pop() {
+            if !ready {
+                pending.push((node, true));
+                pending.extend(self.children(node).chain(template_contents(node)).map(|child| (child, false)));
+                continue;
+            }
+            let mut size = 1;
+            let children = self
+                .children(node)
+                .chain(template_contents(node))
+                .map(|child| {
+                    let (class, count) = result[&child.id()];
+                    size += count;
+                    class
+                })
+                .collect();
+            result.insert(node.id(), (classes.class(self, node, children), size));
+        }
+        result
+    }
+
+    fn hash_value(&self, kind: &NodeKind, hasher: &mut DefaultHasher) {
+        std::mem::discriminant(kind).hash(hasher);
+        match kind {
+            NodeKind::Document | NodeKind::Fragment => {}
+            NodeKind::Doctype(d) => (&d.name, &d.public_id, &d.system_id).hash(hasher),
+            NodeKind::Comment(c) => c.comment.hash(hasher),
+            NodeKind::Text(t) => t.text.hash(hasher),
+            NodeKind::ProcessingInstruction(pi) => (&pi.target, &pi.data).hash(hasher),
+            NodeKind::Element(e) => (&e.name, self.attributes(e)).hash(hasher),
+        }
+    }
+
+    /// Returns true if two nodes are equal, not counting their children.
+    fn value_eq(&self, a: &NodeKind, b: &NodeKind) -> bool {
+        match (a, b) {
+            (NodeKind::Document, NodeKind::Document) | (NodeKind::Fragment, NodeKind::Fragment) => true,
+            (NodeKind::Doctype(a), NodeKind::Doctype(b)) => {
+                (&a.name, &a.public_id, &a.system_id) == (&b.name, &b.public_id, &b.system_id)
+            }
+            (NodeKind::Comment(a), NodeKind::Comment(b)) => a.comment == b.comment,
+            (NodeKind::Text(a), NodeKind::Text(b)) => a.text == b.text,
+            (NodeKind::ProcessingInstruction(a), NodeKind::ProcessingInstruction(b)) => {
+                (&a.target, &a.data) == (&b.target, &b.data)
+            }
+            (NodeKind::Element(a), NodeKind::Element(b)) => a.name == b.name && self.attributes(a) == self.attributes(b),
+            _ => false,
+        }
+    }
+
+    /// Returns the attributes which are compared, sorted if their order is ignored.
+    fn attributes<'e>(&self, element: &'e NodeData) -> Vec<(&'e QualName, &'e HtmlStr)> {
+        let mut attrs: Vec<_> = element.attrs.iter().collect();
+        if self.attribute_order {
+            attrs.sort_unstable();
+        }
+        attrs
+    }
+}
+
+/// The subtrees seen so far, which are numbered so that two subtrees are in the same class exactly if they are equal.
+///
+/// A subtree is looked up by a hash of its root and the classes of its children, then compared to the subtrees with
+/// that hash, so that a hash collision cannot make different subtrees equal.
+#[derive(Default)]
+struct Classes<'t> {
+    subtrees: HashMap<u64, Vec<Subtree<'t>>>,
+    count: usize,
+}
+
+/// The first subtree of a class.
+struct Subtree<'t> {
+    root: NodeRef<'t, NodeKind>,
+    children: Vec<usize>,
+    class: usize,
+}
+
+impl<'t> Classes<'t> {
+    /// Returns the class of the subtree of `node`, whose children are in the classes `children`.
+    fn class(&mut self, options: &DiffOptions, node: NodeRef<'t, NodeKind>, children: Vec<usize>) -> usize {
+        let subtrees = self.subtrees.entry(subtree_hash(options, node, &children)).or_default();
+        let equal = |subtree: &&Subtree| subtree.children == children && options.value_eq(subtree.root.value(), node.value());
+        if let Some(subtree) = subtrees.iter().find(equal) {
+            return subtree.class;
+        }
+        let class = self.count;
+        self.count += 1;
+        subtrees.push(Subtree { root: node, children, class });
+        class
+    }
+}
+
+impl<'a> Diff<'a> {
+    /// Returns the edits which turn the old tree into the new one.
+    pub fn edits(&self) -> &[Edit] {
+        &self.edits
+    }
+
+    /// Returns true if the trees are equal.
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Returns the node of the new tree which took the place of a node of the old tree, if it was kept in place.
+    pub fn paired(&self, old: NodeId) -> Option<NodeId> {
+        self.pairs.get(&old).copied()
+    }
+}
+
+/// Walks both trees to collect the edits.
+struct Differ<'a> {
+    options: DiffOptions,
+    old: &'a Tree<NodeKind>,
+    new: &'a Tree<NodeKind>,
+    /// The class and size of each subtree, subtrees of the same class are equal.
+    old_classes: HashMap<NodeId, (usize, usize)>,
+    new_classes: HashMap<NodeId, (usize, usize)>,
+    edits: Vec<Edit>,
+    pairs: HashMap<NodeId, NodeId>,
+}
+
+impl<'a> Differ<'a> {
+    /// Compares two nodes of the same kind and name.
+    fn pair(&mut self, old: NodeId, new: NodeId) {
+        if self.old_classes[&old] == self.new_classes[&new] {
+            return self.pair_equal(old, new);
+        }
+        self.pairs.insert(old, new);
+        match (self.old.get(old).unwrap().value(), self.new.get(new).unwrap().value()) {
+            (NodeKind::Element(a), NodeKind::Element(b)) => {
+                let mut changes = Vec::new();
+                for (name, value) in &b.attrs {
+                    match a.attrs.get(name) {
+                        Some(old) if old == value => {}
+                        Some(old) => changes.push(AttributeChange::Changed {
+                            name: name.local.to_string(),
+                            old: old.clone(),
+                            new: value.clone(),
+                        }),
+                        None => changes.push(AttributeChange::Added { name: name.local.to_string(), value: value.clone() }),
+                    }
+                }
+                for (name, value) in a.attrs.iter().filter(|(name, _)| !b.attrs.contains_key(*name)) {
+                    changes.push(AttributeChange::Removed { name: name.local.to_string(), value: value.clone() });
+                }
+                if changes.is_empty() && !self.options.attribute_order && !a.attrs.keys().eq(b.attrs.keys()) {
+                    changes.push(AttributeChange::Reordered);
+                }
+                if !changes.is_empty() {
+                    self.edits.push(Edit::Attributes { old, new, changes });
+                }
+                self.pair_children(old, new);
+                if let (Some(old), Some(new)) = (a.template_contents, b.template_contents) {
+                    self.pair(old, new);
+                }
+            }
+            (NodeKind::Document | NodeKind::Fragment, _) => self.pair_children(old, new),
+            _ => self.edits.push(Edit::Change { old, new }),
+        }
+    }
+
+    /// Pairs up the nodes of subtrees of the same class, whose children are of the same classes in turn.
+    fn pair_equal(&mut self, old: NodeId, new: NodeId) {
+        let mut pending = vec![(old, new)];
+        while let Some((old, new)) = pending.pop() {
+            self.pairs.insert(old, new);
+            let (old, new) = (self.old.get(old).unwrap(), self.new.get(new).unwrap());
+            let old = self.options.children(old).chain(template_contents(old)).map(|n| n.id());
+            let new = self.options.children(new).chain(template_contents(new)).map(|n| n.id());
+            pending.extend(old.zip(new));
+        }
+    }
+
+    /// Keeps the largest common sequence of equal children, then pairs up the rest by kind and name.
+    fn pair_children(&mut self, old: NodeId, new: NodeId) {
+        let a: Vec<_> = self.options.children(self.old.get(old).unwrap()).map(|n| n.id()).collect();
+        let b: Vec<_> = self.options.children(self.new.get(new).unwrap()).map(|n| n.id()).collect();
+        let (mut i, mut j) = (0, 0);
+        for (x, y) in self.common(&a, &b).into_iter().chain([(a.len(), b.len())]) {
+            for &old in &a[i..x] {
+                match (j..y).find(|&k| self.same_kind(old, b[k])) {
+                    Some(k) => {
+                        b[j..k].iter().for_each(|&new| self.edits.push(Edit::Insert { new }));
+                        self.pair(old, b[k]);
+                        j = k + 1;
+                    }
+                    None => self.edits.push(Edit::Remove { old }),
+                }
+            }
+            b[j..y].iter().for_each(|&new| self.edits.push(Edit::Insert { new }));
+            if x < a.len() {
+                self.pair_equal(a[x], b[y]);
+            }
+            (i, j) = (x + 1, y + 1);
+        }
+    }
+
+    /// Returns the indices of a common subsequence of equal subtrees with many nodes.
+    ///
+    /// Equal children at the start and the end are kept. The rest is compared in full if it is small enough, otherwise
+    /// only the subtrees which occur once on each side are matched up, and the gaps between them are compared in full if
+    /// they are small enough.
+    fn common(&self, a: &[NodeId], b: &[NodeId]) -> Vec<(usize, usize)> {
+        let a: Vec<_> = a.iter().map(|id| self.old_classes[id]).collect();
+        let b: Vec<_> = b.iter().map(|id| self.new_classes[id]).collect();
+        let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+        let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+        let (end_a, end_b) = (a.len() - suffix, b.len() - suffix);
+
+        let mut common: Vec<_> = (0..prefix).map(|i| (i, i)).collect();
+        let anchors = if fits_table(end_a - prefix, end_b - prefix) {
+            Vec::new()
+        }
+        else {
+            unique_matches(&a, &b, prefix..end_a, prefix..end_b)
+        };
+        let (mut i, mut j) = (prefix, prefix);
+        for (x, y) in anchors.into_iter().chain([(end_a, end_b)]) {
+            if fits_table(x - i, y - j) {
+                common.extend(longest_common(&a[i..x], &b[j..y]).into_iter().map(|(k, l)| (i + k, j + l)));
+            }
+            if x < end_a {
+                common.push((x, y));
+            }
+            (i, j) = (x + 1, y + 1);
+        }
+        common.extend((0..suffix).map(|k| (end_a + k, end_b + k)));
+        common
+    }
+
+    /// Returns true if two nodes can be compared in depth.
+    fn same_kind(&self, old: NodeId, new: NodeId) -> bool {
+        match (self.old.get(old).unwrap().value(), self.new.get(new).unwrap().value()) {
+            // A template without contents is only built in code, and holds its content as children.
+            (NodeKind::Element(a), NodeKind::Element(b)) => {
+                a.name == b.name && a.template_contents.is_some() == b.template_contents.is_some()
+            }
+            (NodeKind::ProcessingInstruction(a), NodeKind::ProcessingInstruction(b)) => a.target == b.target,
+            (NodeKind::Text(_), NodeKind::Text(_)) | (NodeKind::Comment(_), NodeKind::Comment(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Turns a removal and an insertion of equal subtrees into a move.
+    fn find_moves(&mut self) {
+        let mut inserted: HashMap<(usize, usize), Vec<NodeId>> = HashMap::new();
+        for edit in self.edits.iter().rev() {
+            if let Edit::Insert { new } = *edit {
+                inserted.entry(self.new_classes[&new]).or_default().push(new);
+            }
+        }
+        let mut moved = HashSet::new();
+        for edit in &mut self.edits {
+            if let Edit::Remove { old } = *edit {
+                if let Some(new) = inserted.get_mut(&self.old_classes[&old]).and_then(Vec::pop) {
+                    moved.insert(new);
+                    *edit = Edit::Move { old, new };
+                }
+            }
+        }
+        self.edits.retain(|edit| !matches!(edit, Edit::Insert { new } if moved.contains(new)));
+    }
+}
+
+/// The largest number of entries in a table comparing two lists of children in full.
+const MAX_TABLE: usize = 1 << 20;
+
+fn fits_table(a: usize, b: usize) -> bool {
+    (a + 1).saturating_mul(b + 1) <= MAX_TABLE
+}
+
+/// Returns the indices of the common subsequence of subtrees with the most nodes, given their classes and sizes.
+fn longest_common(a: &[(usize, usize)], b: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let width = b.len() + 1;
+    // lengths[i * width + j] is the number of nodes in the common subsequence of a[i..] and b[j..].
+    let mut lengths = vec![0usize; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + a[i].1
+            }
+            else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut common) = (0, 0, Vec::new());
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            common.push((i, j));
+            (i, j) = (i + 1, j + 1);
+        }
+        else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        }
+        else {
+            j += 1;
+        }
+    }
+    common
+}
+
+/// Returns the most pairs of indices in order whose subtrees occur once in `a[range_a]` and once in `b[range_b]`.
+fn unique_matches(
+    a: &[(usize, usize)],
+    b: &[(usize, usize)],
+    range_a: Range<usize>,
+    range_b: Range<usize>,
+) -> Vec<(usize, usize)> {
+    // The number of occurrences in `a` and `b` of each subtree, and where it last occurs.
+    let mut seen: HashMap<(usize, usize), (usize, usize, usize, usize)> = HashMap::new();
+    for i in range_a {
+        let entry = seen.entry(a[i]).or_default();
+        (entry.0, entry.2) = (entry.0 + 1, i);
+    }
+    for j in range_b {
+        let entry = seen.entry(b[j]).or_default();
+        (entry.1, entry.3) = (entry.1 + 1, j);
+    }
+    let mut matches: Vec<_> = seen.into_values().filter(|m| m.0 == 1 && m.1 == 1).map(|m| (m.2, m.3)).collect();
+    matches.sort_unstable();
+
+    // The longest increasing run of indices into `b`: ends[k] is the match which ends the best run of length k + 1
+    // found so far, and previous holds the match before each one.
+    let mut ends: Vec<usize> = Vec::new();
+    let mut previous = vec![None; matches.len()];
+    for (k, &(_, j)) in matches.iter().enumerate() {
+        let length = ends.partition_point(|&end| matches[end].1 < j);
+        previous[k] = length.checked_sub(1).map(|length| ends[length]);
+        if length == ends.len() {
+            ends.push(k);
+        }
+        else {
+            ends[length] = k;
+        }
+    }
+    let mut run = Vec::with_capacity(ends.len());
+    let mut next = ends.last().copied();
+    while let Some(k) = next {
+        run.push(matches[k]);
+        next = previous[k];
+    }
+    run.reverse();
+    run
+}
+
+/// Hashes a node and the classes of its children.
+fn subtree_hash(options: &DiffOptions, node: NodeRef<NodeKind>, children: &[usize]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    options.hash_value(node.value(), &mut hasher);
+    children.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the contents of a `<template>` element.
+fn template_contents<'t>(node: NodeRef<'t, NodeKind>) -> Option<NodeRef<'t, NodeKind>> {
+    match node.value() {
+        NodeKind::Element(e) => e.template_contents.and_then(|id| node.tree().get(id)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, AttributeChange, DiffOptions, Edit};
+    use crate::{Html, Node, Selector};
+
+    fn id(html: &Html, selector: &str) -> ego_tree::NodeId {
+        html.select(&Selector::new(selector)).next().unwrap().id()
+    }
+
+    #[test]
+    fn equal_trees() {
+        let source = "<!DOCTYPE html><p class=a>text<!-- c --></p>";
+        let (old, new) = (Html::parse_document(source), Html::parse_document(source));
+        let diff = diff(&old, &new);
+        assert!(diff.is_empty());
+        assert_eq!(diff.paired(id(&old, "p")), Some(id(&new, "p")));
+        assert_eq!(diff.render(), new);
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let old = Html::parse_fragment("<ul><li>1</li><li>2</li><li>3</li></ul>");
+        let new = Html::parse_fragment("<ul><li>1</li><li>3</li><li>4</li></ul>");
+        let diff = diff(&old, &new);
+        let removed = old.select(&Selector::new("li")).nth(1).unwrap().id();
+        let inserted = new.select(&Selector::new("li")).nth(2).unwrap().id();
+        assert_eq!(diff.edits(), [Edit::Remove { old: removed }, Edit::Insert { new: inserted }]);
+        assert_eq!(
+            diff.render().root_node().inner_html(),
+            "<ul><li>1</li><del><li>2</li></del><li>3</li><ins><li>4</li></ins></ul>"
+        );
+    }
+
+    #[test]
+    fn changes_in_depth() {
+        let old = Html::parse_fragment(r#"<div><p a="1" b="2" c="3">one <b>two</b></p></div>"#);
+        let new = Html::parse_fragment(r#"<div><p d="4" b="5" a="1">one <b>three</b></p></div>"#);
+        let diff = diff(&old, &new);
+        let changes = vec![
+            AttributeChange::Added { name: "d".into(), value: "4".into() },
+            AttributeChange::Changed { name: "b".into(), old: "2".into(), new: "5".into() },
+            AttributeChange::Removed { name: "c".into(), value: "3".into() },
+        ];
+        let text = |html: &Html| html.tree.get(id(html, "b")).unwrap().first_child().unwrap().id();
+        assert_eq!(
+            diff.edits(),
+            [
+                Edit::Attributes { old: id(&old, "p"), new: id(&new, "p"), changes },
+                Edit::Change { old: text(&old), new: text(&new) }
+            ]
+        );
+        assert_eq!(
+            diff.render().root_node().inner_html(),
+            r#"<div><p d="4" b="5" a="1" data-diff-attributes="d b c">one <b><del>two</del><ins>three</ins></b></p></div>"#
+        );
+    }
+
+    #[test]
+    fn long_lists() {
+        let items: Vec<_> = (0..20000).map(|i| format!("<li>{i}</li>")).collect();
+        let old = Html::parse_fragment(&format!("<ul>{}</ul>", items.concat()));
+        let mut changed = items.clone();
+        changed.remove(5000);
+        changed.insert(15000, "<li>x</li>".to_string());
+        let new = Html::parse_fragment(&format!("<ul>{}</ul>", changed.concat()));
+        let diff = diff(&old, &new);
+        let li = |html: &Html, n| html.select(&Selector::new("li")).nth(n).unwrap().id();
+        assert_eq!(diff.edits(), [Edit::Remove { old: li(&old, 5000) }, Edit::Insert { new: li(&new, 15000) }]);
+
+        // Without subtrees which occur once, only the equal start and end are kept.
+        let old = Html::parse_fragment(&format!("<ul>{}</ul>", "<li>a</li>".repeat(20000)));
+        let new =
+            Html::parse_fragment(&format!("<ul>{}<li>b</li>{}</ul>", "<li>a</li>".repeat(100), "<li>a</li>".repeat(19900)));
+        let diff = super::diff(&old, &new);
+        assert_eq!(diff.edits(), [Edit::Insert { new: li(&new, 100) }]);
+    }
+
+    #[test]
+    fn moves() {
+        let old = Html::parse_fragment("<div><h1>title</h1><p>a</p></div><aside></aside>");
+        let new = Html::parse_fragment("<div><p>a</p></div><aside><h1>title</h1></aside>");
+        let diff = diff(&old, &new);
+        assert_eq!(diff.edits(), [Edit::Move { old: id(&old, "h1"), new: id(&new, "h1") }]);
+        assert_eq!(
+            diff.render().root_node().inner_html(),
+            r#"<div><del class="moved"><h1>title</h1></del><p>a</p></div><aside><ins class="moved"><h1>title</h1></ins></aside>"#
+        );
+    }
+
+    #[test]
+    fn template_contents() {
+        fn contents(html: &Html) -> Node<'_> {
+            html.select_first("template").unwrap().unwrap().template_contents().unwrap()
+        }
+        let old = Html::parse_fragment("<template><b>a</b></template>");
+        let new = Html::parse_fragment("<template><b>b</b><i>c</i></template>");
+        let diff = diff(&old, &new);
+        let (b, new_b) = (contents(&old).first_child().unwrap(), contents(&new).first_child().unwrap());
+        assert_eq!(diff.paired(contents(&old).id()), Some(contents(&new).id()));
+        assert_eq!(
+            diff.edits(),
+            [
+                Edit::Change { old: b.first_child().unwrap().id(), new: new_b.first_child().unwrap().id() },
+                Edit::Insert { new: new_b.next_sibling().unwrap().id() }
+            ]
+        );
+        assert_eq!(
+            diff.render().root_node().inner_html(),
+            "<template><b><del>a</del><ins>b</ins></b><ins><i>c</i></ins></template>"
+        );
+        assert!(super::diff(&old, &Html::parse_fragment("<template><b>a</b></template>")).is_empty());
+    }
+
+    #[test]
+    fn hash_collisions() {
+        let html = Html::parse_fragment("<p>a</p><p>b</p>");
+        let mut texts = html.tree.root().descendants().filter(|n| n.value().is_text());
+        let (a, b) = (texts.next().unwrap(), texts.next().unwrap());
+        let options = DiffOptions::new();
+        let mut classes = super::Classes::default();
+        // Pretend that the text `a` hashes like the text `b`.
+        classes
+            .subtrees
+            .insert(super::subtree_hash(&options, b, &[]), vec![super::Subtree { root: a, children: Vec::new(), class: 0 }]);
+        classes.count = 1;
+        assert_eq!(classes.class(&options, b, Vec::new()), 1);
+        assert_eq!(classes.class(&options, b, Vec::new()), 1);
+    }
+
+    #[test]
+    fn options() {
+        let old = Html::parse_fragment("<div>\n  <p a=1 b=2>x</p>\n</div>");
+        let new = Html::parse_fragment("<div><p b=2 a=1>x</p></div>");
+        assert!(!diff(&old, &new).is_empty());
+        let options = DiffOptions::new().ignore_whitespace(true);
+        let edits = [Edit::Attributes { old: id(&old, "p"), new: id(&new, "p"), changes: vec![AttributeChange::Reordered] }];
+        assert_eq!(options.diff(&old, &new).edits(), edits);
+        assert!(options.ignore_attribute_order(true).diff(&old, &new).is_empty());
+    }
+}
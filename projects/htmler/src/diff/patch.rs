@@ -0,0 +1,653 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter, Write},
+    str::FromStr,
+};
+
+use ego_tree::{NodeId, Tree};
+use html5ever::{LocalName, Namespace, Prefix, QualName};
+use indexmap::IndexMap;
+
+use super::{Diff, Edit};
+use crate::{error::PatchError, Html, HtmlStr, NodeKind};
+
+/// The way from the root to a node, as the index of each node among its siblings.
+///
+/// Written as `/0/1`, with `/template` for the step into the contents of a `<template>` element.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct NodePath(pub Vec<PathStep>);
+
+/// A step of a [`NodePath`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathStep {
+    /// The child at this index.
+    Child(usize),
+    /// The contents of a `<template>` element.
+    TemplateContents,
+}
+
+/// A node which a patch inserts next to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchRef {
+    /// A node of the base document.
+    Base(NodePath),
+    /// A node inserted by an earlier operation, counting only insertions.
+    Inserted(usize),
+}
+
+/// New content inserted by a patch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchContent {
+    /// HTML, parsed in the context of the parent.
+    Html(HtmlStr),
+    /// A text node.
+    Text(HtmlStr),
+}
+
+/// An operation of a patch.
+///
+/// Paths refer to the base document as it was before the patch, even after earlier operations changed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchOp {
+    /// Replaces the attributes of an element, in this order.
+    SetAttributes {
+        /// The element.
+        node: NodePath,
+        /// The names and values.
+        attributes: Vec<(HtmlStr, HtmlStr)>,
+    },
+    /// Replaces the content of a text node, comment or processing instruction.
+    SetText {
+        /// The node.
+        node: NodePath,
+        /// The new content.
+        text: HtmlStr,
+    },
+    /// Detaches a node.
+    Remove {
+        /// The node.
+        node: NodePath,
+    },
+    /// Inserts new content after a sibling, or as the first child.
+    Insert {
+        /// The parent.
+        parent: PatchRef,
+        /// The sibling to insert after.
+        after: Option<PatchRef>,
+        /// The content.
+        content: PatchContent,
+    },
+    /// Moves a node after a sibling, or to be the first child.
+    Move {
+        /// The node.
+        node: NodePath,
+        /// The new parent.
+        parent: PatchRef,
+        /// The sibling to insert after.
+        after: Option<PatchRef>,
+    },
+}
+
+/// Changes which turn one document into another, made by [`Diff::to_patch`].
+///
+/// A patch is written as text with [`Display`] and read back with [`FromStr`], one operation per line. It records a
+/// checksum of the document it was made from, so that it is only applied to that document.
+///
+/// Inserted HTML is parsed in the context of its parent, so `<head>` and `<body>` elements cannot be inserted.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{diff::HtmlPatch, Html};
+///
+/// let old = Html::parse_document("<ul><li>a</li><li>b</li></ul>");
+/// let new = Html::parse_document("<ul><li>b</li><li class=c>c</li></ul>");
+/// let patch = htmler::diff(&old, &new).to_patch().to_string();
+///
+/// let mut html = Html::parse_document("<ul><li>a</li><li>b</li></ul>");
+/// html.apply_patch(&patch.parse::<HtmlPatch>().unwrap()).unwrap();
+/// assert_eq!(html.as_html(), new.as_html());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlPatch {
+    base: u64,
+    ops: Vec<PatchOp>,
+}
+
+impl NodePath {
+    /// Returns the path of a node.
+    ///
+    /// # Panics
+    ///
+    /// If the node is detached, as there is no way to it from the root.
+    pub fn of(tree: &Tree<NodeKind>, id: NodeId) -> Self {
+        let mut node = tree.get(id).unwrap();
+        let mut path = Vec::new();
+        while node.id() != tree.root().id() {
+            match node.parent() {
+                Some(parent) => {
+                    path.push(PathStep::Child(node.prev_siblings().count()));
+                    node = parent;
+                }
+                None => {
+                    path.push(PathStep::TemplateContents);
+                    node = tree.get(template_owner(tree, node.id()).expect("node is attached")).unwrap();
+                }
+            }
+        }
+        path.reverse();
+        NodePath(path)
+    }
+
+    /// Returns the node at this path.
+    pub fn resolve(&self, tree: &Tree<NodeKind>) -> Option<NodeId> {
+        let mut node = tree.root();
+        for step in &self.0 {
+            node = match *step {
+                PathStep::Child(index) => node.children().nth(index)?,
+                PathStep::TemplateContents => match node.value() {
+                    NodeKind::Element(e) => tree.get(e.template_contents?)?,
+                    _ => return None,
+                },
+            };
+        }
+        Some(node.id())
+    }
+}
+
+impl HtmlPatch {
+    /// Returns the operations.
+    pub fn ops(&self) -> &[PatchOp] {
+        &self.ops
+    }
+}
+
+impl<'a> Diff<'a> {
+    /// Turns the edits into a patch which can be applied to a copy of the old tree.
+    pub fn to_patch(&self) -> HtmlPatch {
+        let path = |id| NodePath::of(&self.old.tree, id);
+        let mut ops = Vec::new();
+        let mut placed = Vec::new();
+        // Nodes of the new tree which are in the old one.
+        let mut kept: HashMap<NodeId, NodeId> = self.pairs.iter().map(|(old, new)| (*new, *old)).collect();
+        for edit in &self.edits {
+            match *edit {
+                Edit::Attributes { old, new, .. } => {
+                    let attributes = match self.new.tree.get(new).unwrap().value() {
+                        NodeKind::Element(e) => e.attrs.iter().map(|(name, value)| (attribute_name(name), value.clone())),
+                        _ => unreachable!(),
+                    };
+                    ops.push(PatchOp::SetAttributes { node: path(old), attributes: attributes.collect() });
+                }
+                Edit::Change { old, new } => {
+                    let text = match self.new.tree.get(new).unwrap().value() {
+                        NodeKind::Text(t) => t.text.clone(),
+                        NodeKind::Comment(c) => c.comment.clone(),
+                        NodeKind::ProcessingInstruction(pi) => pi.data.clone(),
+                        _ => unreachable!(),
+                    };
+                    ops.push(PatchOp::SetText { node: path(old), text });
+                }
+                Edit::Remove { old } => ops.push(PatchOp::Remove { node: path(old) }),
+                Edit::Insert { new } => placed.push((new, None)),
+                Edit::Move { old, new } => {
+                    kept.insert(new, old);
+                    placed.push((new, Some(old)));
+                }
+            }
+        }
+
+        // In tree order, so that the sibling to insert after is in place.
+        let order = tree_order(&self.new.tree);
+        placed.sort_by_key(|(new, _)| order[new]);
+        let mut inserted = HashMap::new();
+        for (new, moved) in placed {
+            let to_ref = |id: NodeId| match inserted.get(&id) {
+                Some(index) => Some(PatchRef::Inserted(*index)),
+                None => kept.get(&id).map(|old| PatchRef::Base(path(*old))),
+            };
+            let node = self.new.tree.get(new).unwrap();
+            let parent = to_ref(node.parent().unwrap().id()).expect("edits are attached to kept nodes");
+            let after = node.prev_siblings().find_map(|sibling| to_ref(sibling.id()));
+            match moved {
+                Some(old) => ops.push(PatchOp::Move { node: path(old), parent, after }),
+                None => {
+                    let content = match node.value() {
+                        NodeKind::Text(t) => PatchContent::Text(t.text.clone()),
+                        _ => PatchContent::Html(self.new.to_node(node).html()),
+                    };
+                    ops.push(PatchOp::Insert { parent, after, content });
+                    inserted.insert(new, inserted.len());
+                }
+            }
+        }
+        HtmlPatch { base: checksum(self.old), ops }
+    }
+}
+
+impl Html {
+    /// Applies a patch made from a copy of this document.
+    ///
+    /// The document is left unchanged if the patch does not apply.
+    pub fn apply_patch(&mut self, patch: &HtmlPatch) -> Result<(), PatchError> {
+        if checksum(self) != patch.base {
+            return Err(PatchError::BaseMismatch);
+        }
+        let mut html = self.clone();
+        // Paths refer to the document before the patch.
+        let mut base = HashMap::new();
+        for (index, op) in patch.ops.iter().enumerate() {
+            let (node, parent, after) = match op {
+                PatchOp::SetAttributes { node, .. } | PatchOp::SetText { node, .. } | PatchOp::Remove { node } => {
+                    (Some(node), None, None)
+                }
+                PatchOp::Insert { parent, after, .. } => (None, Some(parent), after.as_ref()),
+                PatchOp::Move { node, parent, after } => (Some(node), Some(parent), after.as_ref()),
+            };
+            let paths = node.into_iter().chain([parent, after].into_iter().flatten().filter_map(|r| match r {
+                PatchRef::Base(path) => Some(path),
+                PatchRef::Inserted(_) => None,
+            }));
+            for path in paths {
+                let id = path.resolve(&html.tree).ok_or(PatchError::MissingNode { op: index })?;
+                base.insert(path.clone(), id);
+            }
+        }
+
+        let mut inserted = Vec::new();
+        for (index, op) in patch.ops.iter().enumerate() {
+            let resolve = |r: &PatchRef, inserted: &Vec<NodeId>| match r {
+                PatchRef::Base(path) => Ok(base[path]),
+                PatchRef::Inserted(i) => inserted.get(*i).copied().ok_or(PatchError::MissingNode { op: index }),
+            };
+            match op {
+                PatchOp::SetAttributes { node, attributes } => {
+                    match html.tree.get_mut(base[node]).unwrap().value() {
+                        NodeKind::Element(e) => {
+                            let attrs: IndexMap<_, _> =
+                                attributes.iter().map(|(name, value)| (parse_attribute_name(name), value.clone())).collect();
+                            e.replace_attributes(attrs);
+                        }
+                        _ => return Err(PatchError::WrongNode { op: index }),
+                    }
+                    html.refresh_index(base[node]);
+                }
+                PatchOp::SetText { node, text } => match html.tree.get_mut(base[node]).unwrap().value() {
+                    NodeKind::Text(t) => t.text = text.clone(),
+                    NodeKind::Comment(c) => c.comment = text.clone(),
+                    NodeKind::ProcessingInstruction(pi) => pi.data = text.clone(),
+                    _ => return Err(PatchError::WrongNode { op: index }),
+                },
+                PatchOp::Remove { node } => html.tree.get_mut(base[node]).unwrap().detach(),
+                PatchOp::Insert { parent, after, content } => {
+                    let parent = resolve(parent, &inserted)?;
+                    let after = after.as_ref().map(|after| resolve(after, &inserted)).transpose()?;
+                    let nodes = match content {
+                        PatchContent::Text(text) => vec![html.tree.orphan(NodeKind::text(text)).id()],
+                        PatchContent::Html(source) => {
+                            // Template contents are parsed in the context of their template.
+                            let context = template_owner(&html.tree, parent).unwrap_or(parent);
+                            let fragment = html.parse_for_parent(context, source);
+                            let nodes: Vec<_> = fragment.root_node().ptr.children().collect();
+                            nodes.into_iter().map(|node| html.copy_node(node)).collect()
+                        }
+                    };
+                    let last = *nodes.last().ok_or(PatchError::InvalidPayload { op: index })?;
+                    for id in nodes.into_iter().rev() {
+                        place(&mut html, id, parent, after).map_err(|_| PatchError::WrongNode { op: index })?;
+                    }
+                    inserted.push(last);
+                }
+                PatchOp::Move { node, parent, after } => {
+                    let parent = resolve(parent, &inserted)?;
+                    let after = after.as_ref().map(|after| resolve(after, &inserted)).transpose()?;
+                    place(&mut html, base[node], parent, after).map_err(|_| PatchError::WrongNode { op: index })?;
+                }
+            }
+        }
+        *self = html;
+        Ok(())
+    }
+}
+
+/// Attaches a node after `after`, or as the first child of `parent`.
+fn place(html: &mut Html, id: NodeId, parent: NodeId, after: Option<NodeId>) -> Result<(), ()> {
+    let tree = &mut html.tree;
+    let parent_node = tree.get(parent).unwrap();
+    if id == parent || parent_node.ancestors().any(|a| a.id() == id) {
+        return Err(());
+    }
+    match parent_node.value() {
+        NodeKind::Document | NodeKind::Fragment | NodeKind::Element(_) => {}
+        _ => return Err(()),
+    }
+    tree.get_mut(id).unwrap().detach();
+    match after {
+        Some(after) if tree.get(after).unwrap().parent().map(|p| p.id()) == Some(parent) => {
+            tree.get_mut(after).unwrap().insert_id_after(id);
+        }
+        Some(_) => return Err(()),
+        None => {
+            tree.get_mut(parent).unwrap().prepend_id(id);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the `<template>` element whose contents are `contents`.
+fn template_owner(tree: &Tree<NodeKind>, contents: NodeId) -> Option<NodeId> {
+    tree.nodes().find_map(|node| match node.value() {
+        NodeKind::Element(e) if e.template_contents == Some(contents) => Some(node.id()),
+        _ => None,
+    })
+}
+
+/// Numbers the nodes reachable from the root in tree order, with the contents of a template right after it.
+fn tree_order(tree: &Tree<NodeKind>) -> HashMap<NodeId, usize> {
+    let mut order = HashMap::new();
+    let mut pending = vec![tree.root()];
+    while let Some(node) = pending.pop() {
+        order.insert(node.id(), order.len());
+        pending.extend(node.children().rev());
+        if let NodeKind::Element(e) = node.value() {
+            pending.extend(e.template_contents.and_then(|id| tree.get(id)));
+        }
+    }
+    order
+}
+
+/// Returns the FNV-1a hash of the serialized document.
+fn checksum(html: &Html) -> u64 {
+    html.as_html().bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn attribute_name(name: &QualName) -> HtmlStr {
+    match name.prefix {
+        Some(ref prefix) => format!("{}:{}", prefix, name.local),
+        None => name.local.to_string(),
+    }
+}
+
+/// Reads a name written by [`attribute_name`], the prefixes of foreign attributes stand for their namespace.
+fn parse_attribute_name(name: &str) -> QualName {
+    let namespace = |prefix: &str| -> Option<Namespace> {
+        match prefix {
+            "xlink" => Some(ns!(xlink)),
+            "xml" => Some(ns!(xml)),
+            "xmlns" => Some(ns!(xmlns)),
+            _ => None,
+        }
+    };
+    match name.split_once(':').and_then(|(prefix, local)| Some((prefix, local, namespace(prefix)?))) {
+        Some((prefix, local, ns)) => QualName::new(Some(Prefix::from(prefix)), ns, LocalName::from(local)),
+        None => QualName::new(None, ns!(), LocalName::from(name)),
+    }
+}
+
+impl Display for NodePath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_char('/');
+        }
+        self.0.iter().try_for_each(|step| match step {
+            PathStep::Child(index) => write!(f, "/{}", index),
+            PathStep::TemplateContents => f.write_str("/template"),
+        })
+    }
+}
+
+impl FromStr for NodePath {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "/" => Ok(NodePath(Vec::new())),
+            _ => {
+                let steps = s.strip_prefix('/').ok_or(())?.split('/').map(|step| match step {
+                    "template" => Ok(PathStep::TemplateContents),
+                    index => index.parse().map(PathStep::Child).map_err(|_| ()),
+                });
+                Ok(NodePath(steps.collect::<Result<_, _>>()?))
+            }
+        }
+    }
+}
+
+impl Display for PatchRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchRef::Base(path) => path.fmt(f),
+            PatchRef::Inserted(index) => write!(f, "+{}", index),
+        }
+    }
+}
+
+impl FromStr for PatchRef {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.strip_prefix('+') {
+            Some(index) => index.parse().map(PatchRef::Inserted).map_err(|_| ()),
+            None => s.parse().map(PatchRef::Base),
+        }
+    }
+}
+
+/// Writes the sibling to insert after, `^` for none.
+fn write_after(f: &mut Formatter<'_>, after: &Option<PatchRef>) -> fmt::Result {
+    match after {
+        Some(after) => write!(f, " {}", after),
+        None => f.write_str(" ^"),
+    }
+}
+
+fn read_after(token: &str) -> Result<Option<PatchRef>, ()> {
+    match token {
+        "^" => Ok(None),
+        _ => token.parse().map(Some),
+    }
+}
+
+/// Writes a string in quotes, escaping quotes, backslashes and line breaks.
+fn write_quoted(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str(" \"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Splits a line into words and quoted strings.
+fn tokens(line: &str) -> Result<Vec<String>, ()> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => {}
+            '"' => {
+                let mut token = String::new();
+                loop {
+                    match chars.next().ok_or(())? {
+                        '"' => break,
+                        '\\' => token.push(match chars.next().ok_or(())? {
+                            'n' => '\n',
+                            'r' => '\r',
+                            c @ ('"' | '\\') => c,
+                            _ => return Err(()),
+                        }),
+                        c => token.push(c),
+                    }
+                }
+                tokens.push(token);
+            }
+            c => {
+                let mut token = String::from(c);
+                while let Some(c) = chars.next_if(|c| *c != ' ') {
+                    token.push(c);
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+impl Display for HtmlPatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "htmler-patch 1 {:016x}", self.base)?;
+        for op in &self.ops {
+            match op {
+                PatchOp::SetAttributes { node, attributes } => {
+                    write!(f, "attributes {}", node)?;
+                    for (name, value) in attributes {
+                        write_quoted(f, name)?;
+                        write_quoted(f, value)?;
+                    }
+                }
+                PatchOp::SetText { node, text } => {
+                    write!(f, "text {}", node)?;
+                    write_quoted(f, text)?;
+                }
+                PatchOp::Remove { node } => write!(f, "remove {}", node)?,
+                PatchOp::Insert { parent, after, content } => {
+                    write!(f, "insert {}", parent)?;
+                    write_after(f, after)?;
+                    match content {
+                        PatchContent::Html(html) => write!(f, " html").and_then(|_| write_quoted(f, html))?,
+                        PatchContent::Text(text) => write!(f, " text").and_then(|_| write_quoted(f, text))?,
+                    }
+                }
+                PatchOp::Move { node, parent, after } => {
+                    write!(f, "move {} {}", node, parent)?;
+                    write_after(f, after)?;
+                }
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for HtmlPatch {
+    type Err = PatchError;
+
+    fn from_str(s: &str) -> Result<Self, PatchError> {
+        let mut lines = s.lines().enumerate().map(|(index, line)| (index + 1, line));
+        let base = match lines.next().map(|(_, line)| tokens(line)) {
+            Some(Ok(header)) if header.len() == 3 && header[0] == "htmler-patch" && header[1] == "1" => {
+                u64::from_str_radix(&header[2], 16).map_err(|_| PatchError::Syntax { line: 1 })?
+            }
+            _ => return Err(PatchError::Syntax { line: 1 }),
+        };
+        let mut ops = Vec::new();
+        for (line, text) in lines.filter(|(_, text)| !text.is_empty()) {
+            let op = tokens(text).and_then(|tokens| read_op(&tokens)).map_err(|_| PatchError::Syntax { line })?;
+            ops.push(op);
+        }
+        Ok(HtmlPatch { base, ops })
+    }
+}
+
+fn read_op(tokens: &[String]) -> Result<PatchOp, ()> {
+    let op = match tokens {
+        [op, node, rest @ ..] if op == "attributes" && rest.len() % 2 == 0 => PatchOp::SetAttributes {
+            node: node.parse()?,
+            attributes: rest.chunks(2).map(|pair| (pair[0].clone(), pair[1].clone())).collect(),
+        },
+        [op, node, text] if op == "text" => PatchOp::SetText { node: node.parse()?, text: text.clone() },
+        [op, node] if op == "remove" => PatchOp::Remove { node: node.parse()? },
+        [op, parent, after, kind, content] if op == "insert" => PatchOp::Insert {
+            parent: parent.parse()?,
+            after: read_after(after)?,
+            content: match kind.as_str() {
+                "html" => PatchContent::Html(content.clone()),
+                "text" => PatchContent::Text(content.clone()),
+                _ => return Err(()),
+            },
+        },
+        [op, node, parent, after] if op == "move" => {
+            PatchOp::Move { node: node.parse()?, parent: parent.parse()?, after: read_after(after)? }
+        }
+        _ => return Err(()),
+    };
+    Ok(op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HtmlPatch, NodePath, PatchRef};
+    use crate::{diff, error::PatchError, Html};
+
+    fn round_trip(old: &str, new: &str) {
+        let (old, new) = (Html::parse_document(old), Html::parse_document(new));
+        let patch = diff(&old, &new).to_patch();
+        assert_eq!(patch.to_string().parse::<HtmlPatch>(), Ok(patch.clone()));
+        let mut html = old.clone();
+        html.apply_patch(&patch).unwrap();
+        assert_eq!(html.as_html(), new.as_html(), "{}", patch);
+    }
+
+    #[test]
+    fn test_apply() {
+        round_trip("<p>a</p>", "<p>a</p><p>b</p>");
+        round_trip("<p>a</p><p>b</p><hr>", "<hr>");
+        round_trip("<ul><li>a</li><li>b</li><li>c</li></ul>", "<ul><li>c</li><li>a</li><li>b</li></ul>");
+        round_trip("<div><p id=x class=a>\"text\"\n</p></div>", "<div><p class=b>other\ntext</p><!-- c --></div>");
+        round_trip("<div><p>a</p></div><div></div>", "<div></div><div><p>a</p><span>b</span></div>");
+        round_trip("<script>a < b</script>", "<script>a < b && c</script><script>d < e</script>");
+        round_trip("<svg><a xlink:href=x></a></svg>", "<svg><a xlink:href=y></a><rect/></svg>");
+        round_trip("<table><tr><td>a</td></tr></table>", "<table><tr><td>a</td><td>b</td></tr><tr></tr></table>");
+        round_trip(
+            "<template><b>a</b><tr><td>x</td></tr></template>",
+            "<template><tr><td>y</td><td>z</td></tr><b class=c>b</b></template>",
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let html = Html::parse_document("<p>a</p><p>b</p>");
+        let tree = &html.tree;
+        assert_eq!(NodePath::of(tree, tree.root().id()).to_string(), "/");
+        let b = tree.root().last_child().unwrap().last_child().unwrap().last_child().unwrap();
+        assert_eq!(NodePath::of(tree, b.id()).to_string(), "/0/1/1");
+        assert_eq!("/0/1/1".parse::<NodePath>().unwrap().resolve(tree), Some(b.id()));
+        assert_eq!("/0/1/5".parse::<NodePath>().unwrap().resolve(tree), None);
+        assert_eq!("/0/1/template".parse::<NodePath>().unwrap().resolve(tree), None);
+
+        let html = Html::parse_fragment("<template><p>a</p></template>");
+        let tree = &html.tree;
+        let p = html.select_first("template").unwrap().unwrap().template_contents().unwrap().first_child().unwrap();
+        assert_eq!(NodePath::of(tree, p.id()).to_string(), "/0/0/template/0");
+        assert_eq!("/0/0/template/0".parse::<NodePath>().unwrap().resolve(tree), Some(p.id()));
+        assert_eq!("+3".parse(), Ok(PatchRef::Inserted(3)));
+        assert!("0/1".parse::<PatchRef>().is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let old = Html::parse_document("<p>a</p>");
+        let patch = diff(&old, &Html::parse_document("<p>b</p>")).to_patch();
+        let mut other = Html::parse_document("<p>c</p>");
+        assert_eq!(other.apply_patch(&patch), Err(PatchError::BaseMismatch));
+
+        let header = patch.to_string().lines().next().unwrap().to_owned();
+        assert_eq!("htmler-patch 2 0".parse::<HtmlPatch>(), Err(PatchError::Syntax { line: 1 }));
+        assert_eq!(format!("{}\nremove /0\ntext /0 \"x", header).parse::<HtmlPatch>(), Err(PatchError::Syntax { line: 3 }));
+        assert_eq!(format!("{}\nremove /0 /1", header).parse::<HtmlPatch>(), Err(PatchError::Syntax { line: 2 }));
+
+        let mut html = old.clone();
+        let missing = format!("{}\nremove /0/5", header).parse().unwrap();
+        assert_eq!(html.apply_patch(&missing), Err(PatchError::MissingNode { op: 0 }));
+        let wrong = format!("{}\nremove /0/1/0\ntext /0/1/0 \"x\"", header).parse().unwrap();
+        assert_eq!(html.apply_patch(&wrong), Err(PatchError::WrongNode { op: 1 }));
+        let empty = format!("{}\ninsert /0/1 ^ html \"\"", header).parse().unwrap();
+        assert_eq!(html.apply_patch(&empty), Err(PatchError::InvalidPayload { op: 0 }));
+        assert_eq!(html.as_html(), old.as_html());
+    }
+}
@@ -0,0 +1,73 @@
+use ego_tree::NodeId;
+
+use super::{AttributeChange, Diff, Edit};
+use crate::{Html, NodeKind};
+
+impl<'a> Diff<'a> {
+    /// Renders the differences into a copy of the new tree for review.
+    ///
+    /// Inserted nodes are wrapped in `<ins>`, and removed ones are put back where they were, wrapped in `<del>`.
+    /// Both carry `class="moved"` if a node was moved. Changed text shows the old text in `<del>` followed by the new
+    /// text in `<ins>`, and elements whose attributes changed list the changed names in `data-diff-attributes`.
+    pub fn render(&self) -> Html {
+        let mut html = self.new.clone();
+        // Removed nodes go in first, while the paired nodes they are placed after are not wrapped yet.
+        for edit in self.edits.iter().rev() {
+            match *edit {
+                Edit::Remove { old } => self.restore(&mut html, old, None),
+                Edit::Move { old, .. } => self.restore(&mut html, old, Some("moved")),
+                _ => {}
+            }
+        }
+        for edit in &self.edits {
+            match edit {
+                Edit::Insert { new } => wrap(&mut html, *new, "ins", None),
+                Edit::Move { new, .. } => wrap(&mut html, *new, "ins", Some("moved")),
+                Edit::Change { old, new } => {
+                    let del = html.insert_before(*new, NodeKind::element("del"));
+                    let copy = html.import(self.old, *old);
+                    html.append_child(del, copy);
+                    wrap(&mut html, *new, "ins", None);
+                }
+                Edit::Attributes { new, changes, .. } => {
+                    let names: Vec<_> = changes
+                        .iter()
+                        .filter_map(|change| match change {
+                            AttributeChange::Added { name, .. }
+                            | AttributeChange::Removed { name, .. }
+                            | AttributeChange::Changed { name, .. } => Some(name.as_str()),
+                            AttributeChange::Reordered => None,
+                        })
+                        .collect();
+                    html.get_mut(*new).unwrap().set_attribute("data-diff-attributes", &names.join(" "));
+                }
+                Edit::Remove { .. } => {}
+            }
+        }
+        html
+    }
+
+    /// Puts a copy of a removed node, wrapped in `<del>`, after the new node paired with its nearest previous sibling.
+    fn restore(&self, html: &mut Html, old: NodeId, class: Option<&str>) {
+        let node = self.old.tree.get(old).unwrap();
+        let parent = self.pairs[&node.parent().unwrap().id()];
+        let del = match node.prev_siblings().find_map(|sibling| self.paired(sibling.id())) {
+            Some(sibling) => html.insert_after(sibling, NodeKind::element("del")),
+            None => html.prepend_child(parent, NodeKind::element("del")),
+        };
+        if let Some(class) = class {
+            html.get_mut(del).unwrap().set_attribute("class", class);
+        }
+        let copy = html.import(self.old, old);
+        html.append_child(del, copy);
+    }
+}
+
+/// Moves a node into a new element.
+fn wrap(html: &mut Html, id: NodeId, name: &str, class: Option<&str>) {
+    let wrapper = html.insert_before(id, NodeKind::element(name));
+    if let Some(class) = class {
+        html.get_mut(wrapper).unwrap().set_attribute("class", class);
+    }
+    html.append_child(wrapper, id);
+}
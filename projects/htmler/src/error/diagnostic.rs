@@ -0,0 +1,222 @@
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::{Display, Formatter, Write},
+};
+
+/// Category of a parse error.
+///
+/// `html5ever` reports errors as free-form messages, so the category is a best guess, see [`ParseErrorKind::classify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseErrorKind {
+    /// The input contains a byte sequence which is invalid in its encoding.
+    InvalidEncoding,
+    /// A character which is not allowed at this position.
+    BadCharacter,
+    /// A malformed character reference, such as `&#;` or `&amp` without a semicolon.
+    CharacterReference,
+    /// The input ended in the middle of a construct.
+    UnexpectedEof,
+    /// A malformed tag, such as a duplicate attribute or attributes on an end tag.
+    MalformedTag,
+    /// A missing, misplaced or non-conforming doctype.
+    Doctype,
+    /// A token which is not allowed in the current insertion mode.
+    UnexpectedToken,
+    /// An element which is nested or closed incorrectly.
+    MisnestedElement,
+    /// Any other error.
+    Other,
+}
+
+impl ParseErrorKind {
+    /// Categorizes an error message reported by `html5ever`.
+    ///
+    /// Exact messages quote the offending input, so they are matched by their prefix first.
+    ///
+    /// This is a heuristic over the wording of the messages, which may change between `html5ever` versions;
+    /// unrecognised messages are [`ParseErrorKind::Other`].
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_ascii_lowercase();
+        let starts = |prefixes: &[&str]| prefixes.iter().any(|p| message.starts_with(p));
+        let contains = |needles: &[&str]| needles.iter().any(|n| message.contains(n));
+        if starts(&["saw eof", "unexpected eof"]) {
+            Self::UnexpectedEof
+        }
+        else if starts(&["saw ", "bad character"]) {
+            Self::BadCharacter
+        }
+        else if starts(&["bad doctype", "doctype"]) {
+            Self::Doctype
+        }
+        else if starts(&["unexpected open element"]) {
+            Self::MisnestedElement
+        }
+        else if starts(&["unexpected", "non-space table text"]) {
+            Self::UnexpectedToken
+        }
+        else if contains(&["byte sequence"]) {
+            Self::InvalidEncoding
+        }
+        else if contains(&["character reference"]) {
+            Self::CharacterReference
+        }
+        else if contains(&["attribute", "self-closing"]) {
+            Self::MalformedTag
+        }
+        else if contains(&["nested", "close", "closing", "scope", "formatting element", "open element", "</form>"]) {
+            Self::MisnestedElement
+        }
+        else {
+            Self::Other
+        }
+    }
+}
+
+/// A parse error with its position in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+    /// The category of the error, guessed from the message.
+    pub kind: ParseErrorKind,
+    /// The message reported by the parser.
+    pub message: Cow<'static, str>,
+    /// The 1-based line of the error.
+    pub line: u64,
+    /// The 1-based column of the error, counted in characters.
+    ///
+    /// Only known when parsing with exact errors, see [`Html::parse_document_exact`](crate::Html::parse_document_exact).
+    pub column: Option<u64>,
+}
+
+impl ParseDiagnostic {
+    /// Creates a diagnostic, deriving its kind from the message.
+    pub fn new(message: Cow<'static, str>, line: u64, column: Option<u64>) -> Self {
+        ParseDiagnostic { kind: ParseErrorKind::classify(&message), message, line, column }
+    }
+
+    /// Renders the diagnostic with a snippet of `source`, marking the column with a caret.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use htmler::error::ParseDiagnostic;
+    /// let diagnostic = ParseDiagnostic::new("Duplicate attribute".into(), 2, Some(12));
+    /// let rendered = diagnostic.render("<!DOCTYPE html>\n<p id=a id=b>");
+    /// assert_eq!(
+    ///     rendered,
+    ///     "error: Duplicate attribute\n --> 2:12\n  |\n2 | <p id=a id=b>\n  |            ^\n"
+    /// );
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let mut out = String::new();
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        writeln!(out, "error: {}", self.message).unwrap();
+        match self.column {
+            Some(column) => writeln!(out, "{} --> {}:{}", &gutter[1..], self.line, column).unwrap(),
+            None => writeln!(out, "{} --> {}", &gutter[1..], self.line).unwrap(),
+        }
+        let text = match source.lines().nth(self.line.saturating_sub(1) as usize) {
+            Some(text) => text,
+            None => return out,
+        };
+        writeln!(out, "{} |", gutter).unwrap();
+        writeln!(out, "{} | {}", number, text).unwrap();
+        if let Some(column) = self.column {
+            // Keep tabs so that the caret lines up with the snippet.
+            let indent: String = text
+                .chars()
+                .take(column.saturating_sub(1) as usize)
+                .map(|c| {
+                    if c == '\t' { '\t' } else { ' ' }
+                })
+                .collect();
+            writeln!(out, "{} | {}^", gutter, indent).unwrap();
+        }
+        out
+    }
+}
+
+impl Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "{}:{}: {}", self.line, column, self.message),
+            None => write!(f, "{}: {}", self.line, self.message),
+        }
+    }
+}
+
+/// A limit of [`ParseLimits`](crate::html::ParseLimits) which was exceeded, so that the tree was truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitExceeded {
+    /// Too many nodes, the rest of the input was dropped.
+    Nodes,
+    /// Elements were nested too deeply, the deeper ones were dropped.
+    Depth,
+    /// An element had too many attributes, the rest were dropped.
+    AttributeCount,
+    /// An attribute was too long, its value was truncated or the attribute dropped.
+    AttributeLength,
+    /// A text node was too long, it was truncated.
+    TextSize,
+    /// Too many parse errors, the rest were not stored.
+    Errors,
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Nodes => "Too many nodes",
+            Self::Depth => "Elements nested too deeply",
+            Self::AttributeCount => "Too many attributes on an element",
+            Self::AttributeLength => "Attribute too long",
+            Self::TextSize => "Text too long",
+            Self::Errors => "Too many parse errors",
+        })
+    }
+}
+
+impl Error for LimitExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseDiagnostic, ParseErrorKind};
+
+    #[test]
+    fn classify_messages() {
+        let cases = [
+            ("invalid byte sequence", ParseErrorKind::InvalidEncoding),
+            ("Saw < in state TagOpen", ParseErrorKind::BadCharacter),
+            ("Bad character", ParseErrorKind::BadCharacter),
+            ("Invalid character reference &foo", ParseErrorKind::CharacterReference),
+            ("Saw EOF in state Comment", ParseErrorKind::UnexpectedEof),
+            ("Duplicate attribute", ParseErrorKind::MalformedTag),
+            ("Bad DOCTYPE", ParseErrorKind::Doctype),
+            ("Unexpected token Tag in insertion mode InBody", ParseErrorKind::UnexpectedToken),
+            ("No <p> tag to close", ParseErrorKind::MisnestedElement),
+            ("Unexpected open element while closing Atom('p')", ParseErrorKind::MisnestedElement),
+            ("Unexpected token Characters(\"nested attribute\") in insertion mode InTable", ParseErrorKind::UnexpectedToken),
+            ("Formatting element not in scope", ParseErrorKind::MisnestedElement),
+            ("not prepared to handle this!", ParseErrorKind::Other),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(ParseErrorKind::classify(message), kind, "{}", message);
+        }
+    }
+
+    #[test]
+    fn render_without_column() {
+        let diagnostic = ParseDiagnostic::new("Unexpected token".into(), 1, None);
+        assert_eq!(diagnostic.to_string(), "1: Unexpected token");
+        assert_eq!(diagnostic.render("<b></i>"), "error: Unexpected token\n --> 1\n  |\n1 | <b></i>\n");
+        assert_eq!(diagnostic.render(""), "error: Unexpected token\n --> 1\n");
+    }
+
+    #[test]
+    fn render_keeps_tabs() {
+        let diagnostic = ParseDiagnostic::new("Duplicate attribute".into(), 10, Some(4));
+        assert_eq!(diagnostic.to_string(), "10:4: Duplicate attribute");
+        let source = format!("{}\t\t<p a a>", "\n".repeat(9));
+        assert_eq!(diagnostic.render(&source), "error: Duplicate attribute\n  --> 10:4\n   |\n10 | \t\t<p a a>\n   | \t\t ^\n");
+    }
+}
@@ -1,11 +1,20 @@
 //! Custom error types for diagnostics
 //! Includes re-exported error types from dependencies
 
+mod diagnostic;
+mod patch;
 mod utils;
+mod xpath;
+
+pub use self::{
+    diagnostic::{LimitExceeded, ParseDiagnostic, ParseErrorKind},
+    patch::PatchError,
+    xpath::XPathError,
+};
 
 use std::{error::Error, fmt::Display};
 
-use cssparser::{BasicParseErrorKind, ParseErrorKind, Token};
+use cssparser::{BasicParseErrorKind, ParseErrorKind as CssParseErrorKind, Token};
 use selectors::parser::SelectorParseErrorKind;
 
 /// Error type that is returned when calling `Selector::parse`
@@ -41,8 +50,8 @@ impl<'a> From<cssparser::ParseError<'a, SelectorParseErrorKind<'a>>> for Selecto
         // NOTE: This could be improved, but I dont
         // exactly know how
         match original.kind {
-            ParseErrorKind::Basic(err) => SelectorErrorKind::from(err),
-            ParseErrorKind::Custom(err) => SelectorErrorKind::from(err),
+            CssParseErrorKind::Basic(err) => SelectorErrorKind::from(err),
+            CssParseErrorKind::Custom(err) => SelectorErrorKind::from(err),
         }
     }
 }
@@ -0,0 +1,45 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+/// Why a patch could not be read or applied, see [`Html::apply_patch`](crate::Html::apply_patch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchError {
+    /// The patch text is malformed on this line, counted from 1.
+    Syntax {
+        /// The line number.
+        line: usize,
+    },
+    /// The document is not the one the patch was made from.
+    BaseMismatch,
+    /// An operation, counted from 0, refers to a node which does not exist.
+    MissingNode {
+        /// The index of the operation.
+        op: usize,
+    },
+    /// An operation refers to a node of the wrong kind, or to a sibling under another parent.
+    WrongNode {
+        /// The index of the operation.
+        op: usize,
+    },
+    /// The HTML inserted by an operation did not parse into any node.
+    InvalidPayload {
+        /// The index of the operation.
+        op: usize,
+    },
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax { line } => write!(f, "Malformed patch on line {}", line),
+            Self::BaseMismatch => f.write_str("The patch was made for another document"),
+            Self::MissingNode { op } => write!(f, "Operation {} refers to a missing node", op),
+            Self::WrongNode { op } => write!(f, "Operation {} refers to the wrong kind of node", op),
+            Self::InvalidPayload { op } => write!(f, "Operation {} inserts no nodes", op),
+        }
+    }
+}
+
+impl Error for PatchError {}
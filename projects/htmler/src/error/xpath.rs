@@ -0,0 +1,57 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+/// Why an XPath expression could not be parsed or evaluated, see [`XPath`](crate::xpath::XPath).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XPathError {
+    /// The expression is malformed at this byte offset.
+    Syntax {
+        /// The byte offset.
+        position: usize,
+    },
+    /// The expression nests parentheses, predicates, function calls or negations deeper than the parser allows.
+    TooDeep {
+        /// The byte offset where the limit was reached.
+        position: usize,
+    },
+    /// A function which is not in the XPath 1.0 core library.
+    UnknownFunction {
+        /// The function name.
+        name: String,
+    },
+    /// A function called with the wrong number of arguments.
+    ArgumentCount {
+        /// The function name.
+        function: String,
+    },
+    /// A name test with a prefix other than `html`, `svg`, `math`, `xlink`, `xml` and `xmlns`.
+    UnknownPrefix {
+        /// The prefix.
+        prefix: String,
+    },
+    /// A variable reference, as no variables are bound.
+    UnknownVariable {
+        /// The variable name, without `$`.
+        name: String,
+    },
+    /// A string, number or boolean used where a node-set is required, as in a path or union.
+    NotANodeSet,
+}
+
+impl Display for XPathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax { position } => write!(f, "Malformed XPath at offset {}", position),
+            Self::TooDeep { position } => write!(f, "XPath nested too deeply at offset {}", position),
+            Self::UnknownFunction { name } => write!(f, "Unknown XPath function {}()", name),
+            Self::ArgumentCount { function } => write!(f, "Wrong number of arguments to {}()", function),
+            Self::UnknownPrefix { prefix } => write!(f, "Unknown namespace prefix {:?}", prefix),
+            Self::UnknownVariable { name } => write!(f, "Unknown variable ${}", name),
+            Self::NotANodeSet => f.write_str("Expected a node-set"),
+        }
+    }
+}
+
+impl Error for XPathError {}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use ego_tree::{NodeId, Tree};
+
+use super::Html;
+use crate::NodeKind;
+
+impl Html {
+    /// Rebuilds the tree with only the nodes reachable from the root, returning a map from old to new IDs.
+    ///
+    /// Detached nodes stay in the tree until then, whether they were removed by editing or left behind by the
+    /// parser, e.g. the context element of a fragment. The contents of attached `<template>` elements are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use htmler::{Html, Selector};
+    /// let mut html = Html::parse_fragment("<p>a</p><p>b</p>");
+    /// let p: Vec<_> = html.select(&Selector::new("p")).map(|p| p.id()).collect();
+    /// html.remove(p[0]);
+    /// let ids = html.compact();
+    /// assert!(!ids.contains_key(&p[0]));
+    /// assert_eq!(html.select(&Selector::new("p")).next().unwrap().id(), ids[&p[1]]);
+    /// assert_eq!(html.tree.nodes().count(), 4);
+    /// ```
+    pub fn compact(&mut self) -> HashMap<NodeId, NodeId> {
+        // Reachable nodes in tree order, each after its parent.
+        let mut order = Vec::new();
+        let mut roots = vec![self.tree.root().id()];
+        while let Some(root) = roots.pop() {
+            for node in self.tree.get(root).unwrap().descendants() {
+                if let NodeKind::Element(e) = node.value() {
+                    roots.extend(e.template_contents);
+                }
+                order.push((node.id(), node.parent().filter(|_| node.id() != root).map(|parent| parent.id())));
+            }
+        }
+
+        let mut old = std::mem::replace(&mut self.tree, Tree::with_capacity(NodeKind::Document, order.len()));
+        let mut ids = HashMap::with_capacity(order.len());
+        for (id, parent) in order {
+            let value = std::mem::replace(old.get_mut(id).unwrap().value(), NodeKind::Document);
+            let new = match parent {
+                Some(parent) => self.tree.get_mut(ids[&parent]).unwrap().append(value).id(),
+                None if id == old.root().id() => {
+                    *self.tree.root_mut().value() = value;
+                    self.tree.root().id()
+                }
+                None => self.tree.orphan(value).id(),
+            };
+            ids.insert(id, new);
+        }
+
+        let templates: Vec<_> = self
+            .tree
+            .nodes()
+            .filter_map(|node| match node.value() {
+                NodeKind::Element(e) => e.template_contents.map(|_| node.id()),
+                _ => None,
+            })
+            .collect();
+        for id in templates {
+            if let NodeKind::Element(e) = self.tree.get_mut(id).unwrap().value() {
+                e.template_contents = e.template_contents.map(|contents| ids[&contents]);
+            }
+        }
+        if self.index.0.is_some() {
+            self.build_index();
+        }
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{html::HtmlParseOptions, Html, NodeKind, Selector};
+
+    #[test]
+    fn drops_detached_nodes() {
+        let mut html = Html::parse_document("<table><tr><td>a</td></tr>b<template><i>c</i></template></table><p>d</p>");
+        let before = html.as_html();
+        let p = html.select(&Selector::new("p")).next().unwrap().id();
+        let text = html.tree.get(p).unwrap().first_child().unwrap().id();
+        let em = html.append_child(p, NodeKind::element("em"));
+        html.remove(em);
+        let nodes = html.tree.nodes().count();
+
+        let ids = html.compact();
+        assert_eq!(html.as_html(), before);
+        assert_eq!(html.tree.nodes().count(), nodes - 1);
+        assert!(html.tree.nodes().all(|n| n.parent().is_some() || n.value().is_document() || n.value().is_fragment()));
+        assert!(!ids.contains_key(&em));
+        assert_eq!(html.tree.get(ids[&p]).unwrap().first_child().unwrap().id(), ids[&text]);
+        assert_eq!(html.select(&Selector::new("i")).include_templates().count(), 1);
+        assert_eq!(html.select(&Selector::new("p")).next().unwrap().id(), ids[&p]);
+
+        // Nothing is left to drop.
+        let again = html.clone();
+        assert!(html.compact().iter().all(|(old, new)| old == new));
+        assert_eq!(html, again);
+    }
+
+    #[test]
+    fn drops_parser_leftovers() {
+        let mut html = HtmlParseOptions::new().context_element("tr").parse_fragment("<td>a</td>");
+        let nodes = html.tree.nodes().count();
+        html.compact();
+        assert_eq!(html.tree.nodes().count(), nodes - 1);
+        assert_eq!(html.root_node().inner_html(), "<td>a</td>");
+    }
+}
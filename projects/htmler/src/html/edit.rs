@@ -0,0 +1,265 @@
+use ego_tree::{NodeId, Tree};
+
+use super::{tree_sink::concat_text, Html};
+use crate::{builder::Element, NodeKind};
+
+/// A node to insert into the tree, either one already in it or a new one.
+///
+/// Nodes already in the tree are moved, like in the DOM.
+#[derive(Debug, Clone)]
+pub enum NodeOrId {
+    /// A node of the tree, attached or not.
+    Id(NodeId),
+    /// A new node.
+    Node(Box<NodeKind>),
+    /// A copy of a tree, e.g. built with [`Element`], with all of its nodes.
+    Tree(Tree<NodeKind>),
+}
+
+impl From<NodeId> for NodeOrId {
+    fn from(id: NodeId) -> Self {
+        NodeOrId::Id(id)
+    }
+}
+
+impl From<Tree<NodeKind>> for NodeOrId {
+    fn from(tree: Tree<NodeKind>) -> Self {
+        NodeOrId::Tree(tree)
+    }
+}
+
+impl From<Element> for NodeOrId {
+    fn from(element: Element) -> Self {
+        NodeOrId::Tree(element.into_tree())
+    }
+}
+
+impl From<NodeKind> for NodeOrId {
+    fn from(node: NodeKind) -> Self {
+        NodeOrId::Node(Box::new(node))
+    }
+}
+
+/// Structural editing.
+///
+/// Each insertion returns the ID of the inserted node. Text inserted next to a text node is merged into it, like the
+/// parser does, and then the ID of the merged node is returned.
+///
+/// Removed nodes stay in the tree, detached, so their IDs can be inserted again.
+///
+/// # Panics
+///
+/// If an ID is not in the tree, if a node would be inserted into itself, or if the parent cannot have children.
+///
+/// # Examples
+///
+/// ```
+/// # use htmler::{Html, NodeKind, Selector};
+/// let mut html = Html::parse_fragment("<p>one</p><p>two</p>");
+/// let p: Vec<_> = html.select(&Selector::new("p")).map(|p| p.id()).collect();
+/// html.append_child(p[0], NodeKind::text(" and"));
+/// html.insert_after(p[1], NodeKind::element("hr"));
+/// html.move_to(p[1], p[0]);
+/// assert_eq!(html.root_node().inner_html(), "<p>one and<p>two</p></p><hr>");
+/// ```
+impl Html {
+    /// Appends a node to the children of `parent`.
+    pub fn append_child(&mut self, parent: NodeId, child: impl Into<NodeOrId>) -> NodeId {
+        self.check_parent(parent);
+        self.insert_with(child.into(), parent, |tree, id| tree.get_mut(parent).unwrap().append_id(id).id())
+    }
+
+    /// Inserts a node before the first child of `parent`.
+    pub fn prepend_child(&mut self, parent: NodeId, child: impl Into<NodeOrId>) -> NodeId {
+        self.check_parent(parent);
+        self.insert_with(child.into(), parent, |tree, id| tree.get_mut(parent).unwrap().prepend_id(id).id())
+    }
+
+    /// Inserts a node before `sibling`, which must have a parent.
+    pub fn insert_before(&mut self, sibling: NodeId, node: impl Into<NodeOrId>) -> NodeId {
+        let parent = self.parent_of(sibling);
+        self.insert_with(node.into(), parent, |tree, id| tree.get_mut(sibling).unwrap().insert_id_before(id).id())
+    }
+
+    /// Inserts a node after `sibling`, which must have a parent.
+    pub fn insert_after(&mut self, sibling: NodeId, node: impl Into<NodeOrId>) -> NodeId {
+        let parent = self.parent_of(sibling);
+        self.insert_with(node.into(), parent, |tree, id| tree.get_mut(sibling).unwrap().insert_id_after(id).id())
+    }
+
+    /// Puts a node in place of `target`, which is detached.
+    pub fn replace_with(&mut self, target: NodeId, node: impl Into<NodeOrId>) -> NodeId {
+        let node = node.into();
+        if matches!(node, NodeOrId::Id(id) if id == target) {
+            return target;
+        }
+        let id = self.insert_before(target, node);
+        self.remove(target);
+        id
+    }
+
+    /// Detaches a node from its parent, merging the text nodes around it.
+    pub fn remove(&mut self, id: NodeId) {
+        let prev = self.tree.get(id).expect("node not in tree").prev_sibling().map(|node| node.id());
+        self.tree.get_mut(id).unwrap().detach();
+        if let Some(prev) = prev {
+            self.merge_text(prev);
+        }
+    }
+
+    /// Moves a node to the end of the children of `parent`.
+    pub fn move_to(&mut self, id: NodeId, parent: NodeId) -> NodeId {
+        self.append_child(parent, id)
+    }
+
+    /// Detaches a node from where it was, or creates it, then attaches it with `attach`.
+    fn insert_with(
+        &mut self,
+        node: NodeOrId,
+        parent: NodeId,
+        attach: impl FnOnce(&mut Tree<NodeKind>, NodeId) -> NodeId,
+    ) -> NodeId {
+        let (id, prev) = match node {
+            NodeOrId::Id(id) => {
+                let parent = self.tree.get(parent).unwrap();
+                assert!(parent.id() != id && !parent.ancestors().any(|a| a.id() == id), "cannot insert a node into itself");
+                let node = self.tree.get(id).expect("node not in tree");
+                (id, node.prev_sibling().map(|prev| prev.id()))
+            }
+            NodeOrId::Node(mut kind) => {
+                if let NodeKind::Element(ref mut e) = *kind {
+                    // Take the name case of the document.
+                    e.xml = self.xml;
+                    let name = e.name.local.clone();
+                    e.rename(&name);
+                }
+                (self.tree.orphan(*kind).id(), None)
+            }
+            NodeOrId::Tree(tree) => (self.copy_node(tree.root()), None),
+        };
+        self.sync_index();
+        self.tree.get_mut(id).unwrap().detach();
+        let id = attach(&mut self.tree, id);
+        let id = self.merge_text(id);
+        // The old neighbours of a moved node may be adjacent now.
+        if let Some(prev) = prev.filter(|prev| self.tree.get(*prev).unwrap().parent().is_some()) {
+            self.merge_text(prev);
+        }
+        id
+    }
+
+    /// Merges the run of text nodes around `id` into its first node, which is returned.
+    fn merge_text(&mut self, id: NodeId) -> NodeId {
+        let is_text = |html: &Html, id: NodeId| html.tree.get(id).unwrap().value().is_text();
+        if !is_text(self, id) {
+            return id;
+        }
+        let mut first = self.tree.get(id).unwrap();
+        while let Some(prev) = first.prev_sibling().filter(|prev| prev.value().is_text()) {
+            first = prev;
+        }
+        let first = first.id();
+        while let Some(next) = self.tree.get(first).unwrap().next_sibling().filter(|next| next.value().is_text()) {
+            let next = next.id();
+            let mut node = self.tree.get_mut(next).unwrap();
+            node.detach();
+            let text = match node.value() {
+                NodeKind::Text(t) => t.clone(),
+                _ => unreachable!(),
+            };
+            match self.tree.get_mut(first).unwrap().value() {
+                NodeKind::Text(t) => concat_text(t, text),
+                _ => unreachable!(),
+            }
+        }
+        first
+    }
+
+    fn check_parent(&self, parent: NodeId) {
+        match self.tree.get(parent).expect("node not in tree").value() {
+            NodeKind::Document | NodeKind::Fragment | NodeKind::Element(_) => {}
+            _ => panic!("node cannot have children"),
+        }
+    }
+
+    pub(super) fn parent_of(&self, sibling: NodeId) -> NodeId {
+        let parent = self.tree.get(sibling).expect("node not in tree").parent().expect("node has no parent");
+        parent.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ego_tree::NodeId;
+
+    use crate::{Html, NodeKind, Selector};
+
+    fn ids(html: &Html, selector: &str) -> Vec<NodeId> {
+        html.select(&Selector::new(selector)).map(|n| n.id()).collect()
+    }
+
+    fn inner(html: &Html) -> String {
+        html.root_node().inner_html()
+    }
+
+    #[test]
+    fn insert_new_nodes() {
+        let mut html = Html::parse_fragment("<ul><li>b</li></ul>");
+        let ul = ids(&html, "ul")[0];
+        let li = ids(&html, "li")[0];
+        let a = html.prepend_child(ul, NodeKind::element("LI"));
+        html.append_child(a, NodeKind::text("a"));
+        let c = html.insert_after(li, NodeKind::element("li"));
+        html.append_child(c, NodeKind::comment("c"));
+        html.insert_before(a, NodeKind::text("\n"));
+        assert_eq!(inner(&html), "<ul>\n<li>a</li><li>b</li><li><!--c--></li></ul>");
+        assert_eq!(ids(&html, "li").len(), 3);
+    }
+
+    #[test]
+    fn text_merges() {
+        let mut html = Html::parse_fragment("<p>a<b>b</b>c</p>");
+        let p = ids(&html, "p")[0];
+        let b = ids(&html, "b")[0];
+        let first = html.root_node().first_child().unwrap().first_child().unwrap().id();
+        assert_eq!(html.insert_before(b, NodeKind::text("+")), first);
+        let c = html.insert_after(b, NodeKind::text("-"));
+        assert_eq!(html.root_node().first_child().unwrap().last_child().unwrap().id(), c);
+        html.remove(b);
+        assert_eq!(html.tree.get(p).unwrap().children().count(), 1);
+        assert_eq!(html.append_child(p, NodeKind::text("!")), first);
+        assert_eq!(inner(&html), "<p>a+-c!</p>");
+        assert_eq!(
+            html.prepend_child(p, NodeKind::text("_")),
+            html.root_node().first_child().unwrap().first_child().unwrap().id()
+        );
+    }
+
+    #[test]
+    fn move_and_replace() {
+        let mut html = Html::parse_fragment("<div>x<i>i</i>y</div><p>p</p>");
+        let div = ids(&html, "div")[0];
+        let i = ids(&html, "i")[0];
+        let p = ids(&html, "p")[0];
+        html.move_to(i, p);
+        assert_eq!(inner(&html), "<div>xy</div><p>p<i>i</i></p>");
+        assert_eq!(html.tree.get(div).unwrap().children().count(), 1);
+
+        let em = html.replace_with(p, NodeKind::element("em"));
+        html.move_to(i, em);
+        assert_eq!(inner(&html), "<div>xy</div><em><i>i</i></em>");
+        assert_eq!(html.replace_with(em, em), em);
+        html.replace_with(div, p);
+        assert_eq!(inner(&html), "<p>p</p><em><i>i</i></em>");
+        assert_eq!(ids(&html, "div").len(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot insert a node into itself")]
+    fn no_cycles() {
+        let mut html = Html::parse_fragment("<div><p></p></div>");
+        let div = ids(&html, "div")[0];
+        let p = ids(&html, "p")[0];
+        html.append_child(p, div);
+    }
+}
@@ -0,0 +1,294 @@
+//! Character encoding sniffing, following the
+//! [WHATWG encoding sniffing algorithm](https://html.spec.whatwg.org/multipage/parsing.html#encoding-sniffing-algorithm).
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+use tendril::TendrilSink;
+
+use super::{Html, HtmlParseOptions};
+use crate::error::ParseDiagnostic;
+
+/// Number of bytes the `<meta>` prescan looks at.
+const PRESCAN_LIMIT: usize = 1024;
+
+impl Html {
+    /// Parses a byte string of HTML as a document, detecting its character encoding.
+    ///
+    /// The encoding is taken from the byte order mark if there is one, then from `hint` (usually the
+    /// `charset` parameter of a `Content-Type` header), then from a `<meta charset>` or
+    /// `<meta http-equiv="content-type">` declaration in the first 1024 bytes, and finally falls back to
+    /// `windows-1252`. The encoding used is recorded in [`Html::encoding`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use htmler::Html;
+    ///
+    /// let bytes = b"<meta charset=gbk><title>\xc4\xe3\xba\xc3</title>";
+    /// let html = Html::parse_bytes(bytes, None);
+    /// assert_eq!(html.encoding.name(), "GBK");
+    /// assert!(html.as_html().contains("<title>你好</title>"));
+    /// ```
+    pub fn parse_bytes(bytes: &[u8], hint: Option<&str>) -> Self {
+        let (encoding, bom_length) = sniff_encoding(bytes, hint);
+        let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        let mut html = Html::new_document();
+        html.encoding = encoding;
+        if had_errors {
+            // Point at the first replacement character, which is where decoding failed.
+            let before = &text[..text.find('\u{FFFD}').unwrap_or(0)];
+            let line = before.matches('\n').count() as u64 + 1;
+            let column = before.rsplit('\n').next().unwrap_or("").chars().count() as u64 + 1;
+            let message = format!("Invalid byte sequence for {}", encoding.name());
+            html.errors.push(ParseDiagnostic::new(message.into(), line, Some(column)));
+        }
+        HtmlParseOptions::default().document_parser_for(html).one(&*text)
+    }
+}
+
+/// Determines the encoding of `bytes`, returning it along with the length of the byte order mark.
+pub(crate) fn sniff_encoding(bytes: &[u8], hint: Option<&str>) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return (encoding, bom_length);
+    }
+    if let Some(encoding) = hint.and_then(|label| Encoding::for_label(label.trim().as_bytes())) {
+        return (encoding, 0);
+    }
+    let prefix = &bytes[..bytes.len().min(PRESCAN_LIMIT)];
+    (prescan(prefix).unwrap_or(WINDOWS_1252), 0)
+}
+
+/// Prescans a byte stream for a `<meta>` encoding declaration.
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let mut position = 0;
+    while position < bytes.len() {
+        let rest = &bytes[position..];
+        if rest.starts_with(b"<!--") {
+            position += 2 + find(&rest[2..], b"-->")? + 3;
+            continue;
+        }
+        if starts_with_ignore_case(rest, b"<meta") && rest.get(5).is_some_and(|&b| is_space(b) || b == b'/') {
+            position += 5;
+            if let Some(encoding) = prescan_meta(bytes, &mut position)? {
+                return Some(encoding);
+            }
+        }
+        else if rest.len() > 1 && is_tag_start(&rest[1..]) {
+            position += rest.iter().position(|&b| is_space(b) || b == b'>')?;
+            while get_attribute(bytes, &mut position)?.is_some() {}
+        }
+        else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            position += rest.iter().position(|&b| b == b'>')?;
+        }
+        position += 1;
+    }
+    None
+}
+
+/// Processes the attributes of a `<meta>` tag.
+///
+/// Returns `None` when the input ends, and `Some(None)` when the tag declares no usable encoding.
+fn prescan_meta(bytes: &[u8], position: &mut usize) -> Option<Option<&'static Encoding>> {
+    let mut seen: Vec<Vec<u8>> = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+    while let Some((name, value)) = get_attribute(bytes, position)? {
+        if seen.contains(&name) {
+            continue;
+        }
+        match name.as_slice() {
+            b"http-equiv" => got_pragma |= value == b"content-type",
+            b"content" if charset.is_none() => {
+                if let Some(encoding) = extract_from_content(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+            b"charset" => {
+                charset = Encoding::for_label(&value);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        seen.push(name);
+    }
+    let encoding = match need_pragma {
+        Some(true) if !got_pragma => None,
+        Some(_) => charset,
+        None => None,
+    };
+    Some(encoding.map(|encoding| match encoding {
+        e if e == UTF_16BE || e == UTF_16LE => UTF_8,
+        e if e == X_USER_DEFINED => WINDOWS_1252,
+        e => e,
+    }))
+}
+
+/// Gets the next attribute of a tag, lowercasing its name and value.
+///
+/// Returns `None` when the input ends, and `Some(None)` at the end of the tag.
+fn get_attribute(bytes: &[u8], position: &mut usize) -> Option<Option<(Vec<u8>, Vec<u8>)>> {
+    while is_space(*bytes.get(*position)?) || bytes[*position] == b'/' {
+        *position += 1;
+    }
+    if bytes[*position] == b'>' {
+        return Some(None);
+    }
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+    loop {
+        let b = *bytes.get(*position)?;
+        match b {
+            b'=' if !name.is_empty() => {
+                *position += 1;
+                break;
+            }
+            b if is_space(b) => {
+                while is_space(*bytes.get(*position)?) {
+                    *position += 1;
+                }
+                if bytes[*position] != b'=' {
+                    return Some(Some((name, value)));
+                }
+                *position += 1;
+                break;
+            }
+            b'/' | b'>' => return Some(Some((name, value))),
+            b => name.push(b.to_ascii_lowercase()),
+        }
+        *position += 1;
+    }
+    while is_space(*bytes.get(*position)?) {
+        *position += 1;
+    }
+    match bytes[*position] {
+        quote @ (b'"' | b'\'') => loop {
+            *position += 1;
+            match *bytes.get(*position)? {
+                b if b == quote => {
+                    *position += 1;
+                    return Some(Some((name, value)));
+                }
+                b => value.push(b.to_ascii_lowercase()),
+            }
+        },
+        b'>' => return Some(Some((name, value))),
+        _ => {}
+    }
+    loop {
+        match *bytes.get(*position)? {
+            b if is_space(b) || b == b'>' => return Some(Some((name, value))),
+            b => value.push(b.to_ascii_lowercase()),
+        }
+        *position += 1;
+    }
+}
+
+/// Extracts the encoding from the value of a `<meta content>` attribute, such as `text/html; charset=utf-8`.
+fn extract_from_content(content: &[u8]) -> Option<&'static Encoding> {
+    let mut position = 0;
+    loop {
+        position += find_ignore_case(&content[position..], b"charset")? + 7;
+        let rest = skip_spaces(&content[position..]);
+        if let Some(rest) = rest.strip_prefix(b"=") {
+            let rest = skip_spaces(rest);
+            let label = match rest.first()? {
+                &quote @ (b'"' | b'\'') => &rest[1..1 + find(&rest[1..], &[quote])?],
+                _ => &rest[..rest.iter().position(|&b| is_space(b) || b == b';').unwrap_or(rest.len())],
+            };
+            return Encoding::for_label(label);
+        }
+        position = content.len() - rest.len();
+    }
+}
+
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+fn is_tag_start(bytes: &[u8]) -> bool {
+    let bytes = bytes.strip_prefix(b"/").unwrap_or(bytes);
+    bytes.first().is_some_and(u8::is_ascii_alphabetic)
+}
+
+fn skip_spaces(bytes: &[u8]) -> &[u8] {
+    &bytes[bytes.iter().position(|&b| !is_space(b)).unwrap_or(bytes.len())..]
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn find(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    bytes.windows(needle.len()).position(|window| window == needle)
+}
+
+fn find_ignore_case(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    bytes.windows(needle.len()).position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sniff_encoding;
+    use crate::{error::ParseErrorKind, Html, Selector};
+    use encoding_rs::{GBK, SHIFT_JIS, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    fn sniff(bytes: &[u8]) -> &'static str {
+        sniff_encoding(bytes, None).0.name()
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        assert_eq!(sniff_encoding(b"\xEF\xBB\xBF<meta charset=gbk>", None), (UTF_8, 3));
+        assert_eq!(sniff_encoding(b"\xFF\xFE<\0p\0>\0", Some("gbk")), (UTF_16LE, 2));
+    }
+
+    #[test]
+    fn transport_hint() {
+        assert_eq!(sniff_encoding(b"<meta charset=utf-8>", Some(" Shift_JIS ")), (SHIFT_JIS, 0));
+        assert_eq!(sniff_encoding(b"<meta charset=utf-8>", Some("bogus")), (UTF_8, 0));
+    }
+
+    #[test]
+    fn meta_prescan() {
+        assert_eq!(sniff(b"<!DOCTYPE html><html><head><meta charset=\"GBK\">"), "GBK");
+        assert_eq!(sniff(b"<META CHARSET='shift_jis'/>"), "Shift_JIS");
+        assert_eq!(sniff(b"<meta http-equiv=Content-Type content=\"text/html; charset=gb2312\">"), "GBK");
+        assert_eq!(sniff(b"<meta content='text/html;charset = \"euc-jp\"' http-equiv='content-type'>"), "EUC-JP");
+        assert_eq!(sniff(b"<meta charset=utf-16le>"), "UTF-8");
+        assert_eq!(sniff(b"<meta charset=x-user-defined>"), "windows-1252");
+    }
+
+    #[test]
+    fn meta_prescan_skips() {
+        // `content` without `http-equiv` is ignored.
+        assert_eq!(sniff(b"<meta content=\"text/html; charset=gbk\">"), "windows-1252");
+        assert_eq!(sniff(b"<!-- <meta charset=gbk> --><meta charset=euc-kr>"), "EUC-KR");
+        assert_eq!(sniff(b"<div title='<meta charset=gbk>'><meta charset=big5>"), "Big5");
+        assert_eq!(sniff(b"<meta name=x charset=gbk charset=big5>"), "GBK");
+        let mut late = vec![b' '; 1024];
+        late.extend_from_slice(b"<meta charset=gbk>");
+        assert_eq!(sniff(&late), "windows-1252");
+        assert_eq!(sniff(b"<meta charset=\"gbk"), "windows-1252");
+    }
+
+    #[test]
+    fn parse_bytes() {
+        let (bytes, _, _) = SHIFT_JIS.encode("<meta charset=shift_jis><p>こんにちは</p>");
+        let html = Html::parse_bytes(&bytes, None);
+        assert_eq!(html.encoding, SHIFT_JIS);
+        let p = html.select(&Selector::new("p")).next().unwrap();
+        assert_eq!(p.inner_html(), "こんにちは");
+
+        let (bytes, _, _) = GBK.encode("<p>你好</p>");
+        assert_eq!(Html::parse_bytes(&bytes, Some("gbk")), Html { encoding: GBK, ..Html::parse_document("<p>你好</p>") });
+
+        let html = Html::parse_bytes(b"<p>caf\xe9</p>", None);
+        assert_eq!(html.encoding, WINDOWS_1252);
+        assert!(html.as_html().contains("café"));
+
+        let html = Html::parse_bytes(b"<p>\n  \xff</p>", Some("utf-8"));
+        let error = &html.errors[0];
+        assert_eq!((error.kind, error.line, error.column), (ParseErrorKind::InvalidEncoding, 2, Some(3)));
+    }
+}
@@ -0,0 +1,216 @@
+use ego_tree::NodeId;
+use html5ever::{tendril::StrTendril, QualName};
+
+use super::tree_sink::HtmlSink;
+use crate::node::SourceSpan;
+
+/// Kinds of nodes to leave out of the tree while parsing.
+///
+/// Dropped nodes are never added to the tree, and neither is anything inside them.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{
+///     html::{HtmlParseOptions, NodeFilter},
+///     Selector,
+/// };
+///
+/// let filter = NodeFilter::new().drop_scripts(true).drop_comments(true);
+/// let html = HtmlParseOptions::new()
+///     .filter(filter)
+///     .parse_fragment("<p>a<script>b()</script><!-- c --></p>");
+/// assert_eq!(html.root_node().inner_html(), "<p>a</p>");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeFilter {
+    scripts: bool,
+    styles: bool,
+    noscript: bool,
+    comments: bool,
+    whitespace_text: bool,
+}
+
+impl NodeFilter {
+    /// Creates a filter which keeps everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a filter which drops everything it can, leaving the text content.
+    pub fn all() -> Self {
+        NodeFilter { scripts: true, styles: true, noscript: true, comments: true, whitespace_text: true }
+    }
+
+    /// Whether to drop `<script>` elements. Default: `false`.
+    pub fn drop_scripts(mut self, drop: bool) -> Self {
+        self.scripts = drop;
+        self
+    }
+
+    /// Whether to drop `<style>` elements. Default: `false`.
+    pub fn drop_styles(mut self, drop: bool) -> Self {
+        self.styles = drop;
+        self
+    }
+
+    /// Whether to drop `<noscript>` elements. Default: `false`.
+    pub fn drop_noscript(mut self, drop: bool) -> Self {
+        self.noscript = drop;
+        self
+    }
+
+    /// Whether to drop comments. Default: `false`.
+    pub fn drop_comments(mut self, drop: bool) -> Self {
+        self.comments = drop;
+        self
+    }
+
+    /// Whether to drop text nodes which only contain whitespace, even inside `<pre>`. Default: `false`.
+    pub fn drop_whitespace_text(mut self, drop: bool) -> Self {
+        self.whitespace_text = drop;
+        self
+    }
+
+    fn drops_element(&self, name: &QualName) -> bool {
+        match &*name.local {
+            "script" => self.scripts,
+            "style" => self.styles,
+            "noscript" => self.noscript,
+            _ => false,
+        }
+    }
+}
+
+/// Applies the filter while the tree is being built.
+#[derive(Debug, Default)]
+pub(crate) struct Filtering {
+    pub(crate) filter: NodeFilter,
+    /// Whitespace which starts a text node, held back until it is known whether more text follows.
+    held: Option<HeldText>,
+}
+
+impl Filtering {
+    pub(crate) fn new(filter: NodeFilter) -> Self {
+        Filtering { filter, held: None }
+    }
+}
+
+/// Text which is not in the tree yet, with the place it goes.
+#[derive(Debug)]
+pub(super) struct HeldText {
+    parent: NodeId,
+    before: Option<NodeId>,
+    text: String,
+    span: Option<SourceSpan>,
+}
+
+impl HtmlSink {
+    /// Returns true if comments are dropped, so that they need not be created.
+    pub(super) fn drops_comments(&self) -> bool {
+        self.filtering.filter.comments
+    }
+
+    /// Returns true if elements may be dropped, along with everything appended to them.
+    pub(super) fn filters_elements(&self) -> bool {
+        let filter = self.filtering.filter;
+        filter.scripts || filter.styles || filter.noscript
+    }
+
+    /// Returns true if an element named `name` is dropped, so that it need not be created.
+    pub(super) fn drops_element(&self, name: &QualName) -> bool {
+        self.filtering.filter.drops_element(name)
+    }
+
+    /// Takes the held whitespace, which only joins text appended right after it.
+    pub(super) fn take_held_text(&mut self) -> Option<HeldText> {
+        self.filtering.held.take()
+    }
+
+    /// Returns the text which starts a new node at `parent`, before `before` if given, with its span.
+    ///
+    /// Held text which goes to the same place is joined with it. Returns `None` if the result is only whitespace and is
+    /// held back instead, it is dropped unless more text follows.
+    pub(super) fn hold_whitespace(
+        &mut self,
+        held: Option<HeldText>,
+        parent: NodeId,
+        before: Option<NodeId>,
+        mut text: StrTendril,
+    ) -> Option<(StrTendril, Option<SourceSpan>)> {
+        let mut span = self.spans.text();
+        if let Some(held) = held.filter(|held| held.parent == parent && held.before == before) {
+            let mut joined = StrTendril::from(held.text);
+            joined.push_tendril(&text);
+            text = joined;
+            span = match (held.span, span) {
+                (Some(start), Some(end)) => Some(start.to(end)),
+                (start, end) => start.or(end),
+            };
+        }
+        if self.filtering.filter.whitespace_text && text.trim_matches(|c: char| c.is_ascii_whitespace()).is_empty() {
+            self.filtering.held = Some(HeldText { parent, before, text: text.to_string(), span });
+            return None;
+        }
+        Some((text, span))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeFilter;
+    use crate::{html::HtmlParseOptions, Html, Selector};
+
+    const SOURCE: &str = "<!DOCTYPE html><head><style>p {}</style><script>a()</script></head>\
+        <body>\n  <p>one <!-- c --><b>two</b> </p>\n  <noscript><img src=a></noscript><svg><script>b()</script></svg></body>";
+
+    fn parse(filter: NodeFilter) -> Html {
+        HtmlParseOptions::new().filter(filter).parse_document(SOURCE)
+    }
+
+    #[test]
+    fn keeps_everything_by_default() {
+        assert_eq!(parse(NodeFilter::new()), Html::parse_document(SOURCE));
+    }
+
+    #[test]
+    fn drops_elements_with_contents() {
+        let html = parse(NodeFilter::new().drop_scripts(true).drop_styles(true));
+        assert_eq!(html.select(&Selector::new("script, style")).count(), 0);
+        assert_eq!(html.select(&Selector::new("*|script")).count(), 0);
+        assert_eq!(html.select(&Selector::new("noscript")).count(), 1);
+        assert!(!html.root_node().text().any(|t| t.contains("()")));
+
+        let html = HtmlParseOptions::new()
+            .scripting_enabled(false)
+            .filter(NodeFilter::new().drop_noscript(true))
+            .parse_document(SOURCE);
+        assert_eq!(html.select(&Selector::new("noscript, img")).count(), 0);
+    }
+
+    #[test]
+    fn drops_comments_and_whitespace() {
+        let html = parse(NodeFilter::new().drop_comments(true).drop_whitespace_text(true));
+        assert!(!html.tree.nodes().any(|n| n.parent().is_some() && n.value().is_comment()));
+        let body = html.select(&Selector::new("body")).next().unwrap();
+        assert!(body.html().starts_with("<body><p>one <b>two</b></p><noscript>"));
+
+        let html = parse(NodeFilter::all());
+        let body = html.select(&Selector::new("body")).next().unwrap();
+        assert_eq!(body.html(), "<body><p>one <b>two</b></p><svg></svg></body>");
+
+        // Whitespace which is parsed on its own is kept when more text follows.
+        let html = HtmlParseOptions::new().exact_errors(true).filter(NodeFilter::all()).parse_fragment("<p> a <i> </i></p>");
+        assert_eq!(html.root_node().inner_html(), "<p> a <i></i></p>");
+    }
+
+    #[test]
+    fn dropped_nodes_are_not_created() {
+        let html = parse(NodeFilter::all());
+        assert_eq!(html.tree.nodes().count(), html.tree.root().descendants().count());
+
+        let source = "<p>a <b>b</b></p><template><i>c</i></template>";
+        let html = HtmlParseOptions::new().filter(NodeFilter::all()).parse_fragment(source);
+        assert_eq!(html, Html::parse_fragment(source));
+    }
+}
@@ -0,0 +1,266 @@
+use ego_tree::{iter::Edge, NodeId, NodeRef};
+use html5ever::{tendril::StrTendril, tree_builder::QuirksMode, Attribute};
+
+use super::{Html, HtmlParseOptions};
+use crate::{node::NodeData, NodeKind};
+
+/// Replacing content with parsed HTML.
+///
+/// The HTML is parsed as a fragment in the context of the element which receives it, so the same rules apply as when
+/// it was part of the document, e.g. table rows need a table. Parse errors are discarded.
+///
+/// # Panics
+///
+/// If the ID is not an element of the tree.
+///
+/// # Examples
+///
+/// ```
+/// # use htmler::{Html, Selector};
+/// let mut html = Html::parse_fragment("<table><tbody><tr><td>a</td></tr></tbody></table>");
+/// let tbody = html.select(&Selector::new("tbody")).next().unwrap().id();
+/// html.set_inner_html(tbody, "<tr><td>b<tr><td>c");
+/// assert_eq!(
+///     html.root_node().inner_html(),
+///     "<table><tbody><tr><td>b</td></tr><tr><td>c</td></tr></tbody></table>"
+/// );
+/// ```
+impl Html {
+    /// Replaces the children of an element, or the contents of a `<template>` element.
+    pub fn set_inner_html(&mut self, id: NodeId, html: &str) {
+        let fragment = self.parse_in_context(id, html);
+        let parent = match self.tree.get(id).unwrap().value() {
+            NodeKind::Element(e) => e.template_contents.unwrap_or(id),
+            _ => unreachable!(),
+        };
+        let children: Vec<_> = self.tree.get(parent).unwrap().children().map(|child| child.id()).collect();
+        for child in children {
+            self.tree.get_mut(child).unwrap().detach();
+        }
+        for child in fragment.root_node().ptr.children() {
+            let child = self.copy_node(child);
+            self.tree.get_mut(parent).unwrap().append_id(child);
+        }
+    }
+
+    /// Replaces an element, which must have a parent, with the parsed nodes.
+    ///
+    /// Text at the edges merges with the text around the element.
+    pub fn set_outer_html(&mut self, id: NodeId, html: &str) {
+        let parent = self.parent_of(id);
+        assert!(self.tree.get(id).unwrap().value().is_element(), "not an element");
+        let fragment = self.parse_for_parent(parent, html);
+        for child in fragment.root_node().ptr.children() {
+            let child = self.copy_node(child);
+            self.insert_before(id, child);
+        }
+        self.remove(id);
+    }
+
+    /// Parses a fragment to be inserted under `parent`, in the `<body>` context if it is a root.
+    pub(crate) fn parse_for_parent(&self, parent: NodeId, html: &str) -> Html {
+        match self.tree.get(parent).unwrap().value() {
+            NodeKind::Element(_) => self.parse_in_context(parent, html),
+            _ => self.parse_in_context_of(None, html),
+        }
+    }
+
+    /// Parses a fragment with the element `id` as the context.
+    fn parse_in_context(&self, id: NodeId, html: &str) -> Html {
+        let context = match self.tree.get(id).expect("node not in tree").value() {
+            NodeKind::Element(e) => e,
+            _ => panic!("not an element"),
+        };
+        self.parse_in_context_of(Some(context), html)
+    }
+
+    /// Parses a fragment in the context of `context`, or of `<body>` if `None`.
+    fn parse_in_context_of(&self, context: Option<&NodeData>, html: &str) -> Html {
+        let options = HtmlParseOptions::new().quirks_mode(self.quirks_mode);
+        let options = match context {
+            Some(e) => {
+                let attrs =
+                    e.attrs.iter().map(|(name, value)| Attribute { name: name.clone(), value: StrTendril::from(&**value) });
+                options.context_element_ns(e.name.ns.clone(), &e.name.local, attrs.collect())
+            }
+            None => options,
+        };
+        options.parse_fragment(html)
+    }
+
+    /// Deep-copies a node of `other` into this tree, returning it detached so that it can be inserted.
+    ///
+    /// Attributes keep their order and namespace.
+    ///
+    /// # Panics
+    ///
+    /// If the ID is not in `other`, or is the root of `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use htmler::{Html, Selector};
+    /// let page = Html::parse_document("<article><h1>Title</h1><p>Text</p></article>");
+    /// let article = page.select(&Selector::new("article")).next().unwrap().id();
+    /// let mut html = Html::parse_fragment("<main></main>");
+    /// let main = html.select(&Selector::new("main")).next().unwrap().id();
+    /// let copy = html.import(&page, article);
+    /// html.append_child(main, copy);
+    /// assert_eq!(
+    ///     html.root_node().inner_html(),
+    ///     "<main><article><h1>Title</h1><p>Text</p></article></main>"
+    /// );
+    /// ```
+    pub fn import(&mut self, other: &Html, id: NodeId) -> NodeId {
+        let node = other.tree.get(id).expect("node not in tree");
+        assert!(
+            node.parent().is_some() || !matches!(node.value(), NodeKind::Document | NodeKind::Fragment),
+            "cannot import a root"
+        );
+        self.copy_node(node)
+    }
+
+    /// Creates a fragment holding a deep copy of `node`, or of its children if it is a root.
+    ///
+    /// Like parsed fragments, the copy is wrapped in an `<html>` element.
+    pub(crate) fn fragment_of(node: NodeRef<NodeKind>, quirks_mode: QuirksMode) -> Html {
+        let mut html = Html::new_fragment();
+        html.quirks_mode = quirks_mode;
+        if let NodeKind::Element(e) = node.value() {
+            html.xml = e.xml;
+        }
+        let root = html.tree.root().id();
+        let root = html.append_child(root, NodeKind::element("html"));
+        let copies = match node.value() {
+            NodeKind::Document | NodeKind::Fragment => node.children().map(|child| html.copy_node(child)).collect(),
+            _ => vec![html.copy_node(node)],
+        };
+        for copy in copies {
+            html.tree.get_mut(root).unwrap().append_id(copy);
+        }
+        html
+    }
+
+    /// Deep-copies a node of another tree, returning it detached.
+    ///
+    /// Elements take the flags of this document, and source spans are dropped as they point into another source.
+    pub(crate) fn copy_node(&mut self, node: NodeRef<NodeKind>) -> NodeId {
+        let mut parents: Vec<NodeId> = Vec::new();
+        let mut top = None;
+        for edge in node.traverse() {
+            match edge {
+                Edge::Open(node) => {
+                    let kind = self.copy_value(node);
+                    let id = match parents.last() {
+                        Some(parent) => self.tree.get_mut(*parent).unwrap().append(kind).id(),
+                        None => self.tree.orphan(kind).id(),
+                    };
+                    top.get_or_insert(id);
+                    parents.push(id);
+                }
+                Edge::Close(_) => {
+                    parents.pop();
+                }
+            }
+        }
+        self.sync_index();
+        top.unwrap()
+    }
+
+    fn copy_value(&mut self, node: NodeRef<NodeKind>) -> NodeKind {
+        let mut kind = node.value().clone();
+        match kind {
+            NodeKind::Element(ref mut e) => {
+                e.xml = self.xml;
+                e.span = None;
+                e.template_contents = e.template_contents.map(|contents| self.copy_node(node.tree().get(contents).unwrap()));
+            }
+            NodeKind::Text(ref mut t) => t.span = None,
+            NodeKind::Comment(ref mut c) => c.span = None,
+            _ => {}
+        }
+        kind
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{html::HtmlParseOptions, Html, Selector};
+
+    fn first(html: &Html, selector: &str) -> ego_tree::NodeId {
+        html.select(&Selector::new(selector)).next().unwrap().id()
+    }
+
+    #[test]
+    fn to_fragment() {
+        let html = Html::parse_document(r#"<div b=1 a=2><svg xlink:href="x" viewBox="0 0 1 1"><p>a</p></svg>b</div>"#);
+        let div = html.select(&Selector::new("div")).next().unwrap();
+        let fragment = div.to_fragment();
+        assert!(fragment.tree.root().value().is_fragment());
+        assert_eq!(fragment.root_node().inner_html(), div.html());
+        let copy = fragment.root_node().first_child().unwrap();
+        assert_eq!(copy.as_data().unwrap().attributes().collect::<Vec<_>>(), [("b", "1"), ("a", "2")]);
+        let svg = fragment.select(&Selector::new("svg")).next().unwrap().as_data().unwrap();
+        assert_eq!(svg.name.ns, ns!(svg));
+        assert_eq!(svg.attrs.keys().next().unwrap().ns, ns!(xlink));
+
+        let text = div.last_child().unwrap().to_fragment();
+        assert_eq!(text.root_node().inner_html(), "b");
+        assert_eq!(html.root_node().to_fragment().select(&Selector::new("p")).count(), 1);
+    }
+
+    #[test]
+    fn import() {
+        let page = HtmlParseOptions::new().source_spans(true).parse_document("<p id=a>a<template><b>b</b></template></p>");
+        let p = first(&page, "p");
+        let mut html = Html::parse_fragment("<div>x</div>");
+        let copy = html.import(&page, p);
+        assert_eq!(html.as_html(), "<html><div>x</div></html>");
+        let div = first(&html, "div");
+        html.append_child(div, copy);
+        assert_eq!(html.root_node().inner_html(), r#"<div>x<p id="a">a<template><b>b</b></template></p></div>"#);
+        assert_eq!(html.select(&Selector::new("b")).include_templates().count(), 1);
+        assert!(html.select(&Selector::new("#a")).next().unwrap().source_span().is_none());
+        // The original is untouched.
+        assert_eq!(page.select(&Selector::new("p")).count(), 1);
+    }
+
+    #[test]
+    fn inner_html_in_context() {
+        let mut html = Html::parse_fragment("<ul><li>a</li></ul><select></select><svg></svg>");
+        html.set_inner_html(first(&html, "ul"), "<li>b<li>c");
+        html.set_inner_html(first(&html, "select"), "<option>d<div>e</div>");
+        html.set_inner_html(first(&html, "svg"), "<foreignObject><p>f</p></foreignObject>");
+        assert_eq!(
+            html.root_node().inner_html(),
+            "<ul><li>b</li><li>c</li></ul><select><option>de</option></select>\
+                <svg><foreignObject><p>f</p></foreignObject></svg>"
+        );
+        let p = html.select(&Selector::new("p")).next().unwrap();
+        assert_eq!(p.as_data().unwrap().name.ns, ns!(html));
+        assert_eq!(html.select(&Selector::new("li")).count(), 2);
+    }
+
+    #[test]
+    fn inner_html_of_template() {
+        let mut html = Html::parse_fragment("<template><b>a</b></template>");
+        let template = first(&html, "template");
+        html.set_inner_html(template, "<td>cell</td><template><i>i</i></template>");
+        assert_eq!(html.root_node().inner_html(), "<template><td>cell</td><template><i>i</i></template></template>");
+        assert_eq!(html.select(&Selector::new("i")).include_templates().count(), 1);
+    }
+
+    #[test]
+    fn outer_html_merges_text() {
+        let mut html = Html::parse_fragment("<p>a<b>b</b>c</p>");
+        html.set_outer_html(first(&html, "b"), "1<i>2</i>3");
+        assert_eq!(html.root_node().inner_html(), "<p>a1<i>2</i>3c</p>");
+        assert_eq!(html.root_node().first_child().unwrap().children().count(), 3);
+
+        let mut html =
+            HtmlParseOptions::new().source_spans(true).context_element("table").parse_fragment("<tr><td>a</td></tr>");
+        html.set_outer_html(first(&html, "td"), "<th>b</th>");
+        assert_eq!(html.root_node().inner_html(), "<tbody><tr><th>b</th></tr></tbody>");
+        assert!(html.select(&Selector::new("th")).next().unwrap().source_span().is_none());
+    }
+}
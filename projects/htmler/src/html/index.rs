@@ -0,0 +1,436 @@
+use std::collections::HashMap;
+
+use ego_tree::{NodeId, NodeRef, Tree};
+use html5ever::tree_builder::QuirksMode;
+use selectors::{attr::CaseSensitivity, parser::Component};
+
+use super::{tree_position, Html};
+use crate::{node::NodeData, selector::Selector, HtmlStr, Node, NodeKind};
+
+/// Elements under one key, with their position in the tree, in the order of [`Tree::nodes`].
+type Postings = Vec<(usize, NodeId)>;
+
+/// Maps IDs, classes and local names to the elements which have them, see [`Html::build_index`].
+///
+/// Every element of the tree is indexed, attached or not, in the order [`Html::select`] visits them. Keys are
+/// case-sensitive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HtmlIndex {
+    ids: HashMap<HtmlStr, Postings>,
+    classes: HashMap<HtmlStr, Postings>,
+    tags: HashMap<HtmlStr, Postings>,
+    /// The position and keys of each indexed element.
+    entries: HashMap<NodeId, Entry>,
+    /// The number of nodes of the tree which were indexed.
+    len: usize,
+    /// Whether an element was changed in a way the index could not follow.
+    stale: bool,
+}
+
+/// The index of a document, if it was built.
+///
+/// It only speeds up lookups, so documents compare equal whether or not they have one.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IndexCache(pub(crate) Option<HtmlIndex>);
+
+impl PartialEq for IndexCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for IndexCache {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    position: usize,
+    name: HtmlStr,
+    id: Option<HtmlStr>,
+    classes: Vec<HtmlStr>,
+}
+
+/// What an element is looked up by.
+#[derive(Debug, Clone, Copy)]
+enum Key<'s> {
+    Id(&'s str),
+    Class(&'s str),
+    /// The name to match non-HTML elements by, and the lowercase name for HTML elements in HTML documents.
+    Tag(&'s str, &'s str),
+}
+
+impl HtmlIndex {
+    /// Indexes the elements of a tree.
+    pub fn new(tree: &Tree<NodeKind>) -> Self {
+        let mut index = HtmlIndex::default();
+        index.sync(tree);
+        index
+    }
+
+    /// Returns the elements with an ID.
+    pub fn by_id<'a>(&'a self, id: &str) -> impl Iterator<Item = NodeId> + 'a {
+        Self::ids_of(self.ids.get(id))
+    }
+
+    /// Returns the elements with a class.
+    pub fn by_class<'a>(&'a self, class: &str) -> impl Iterator<Item = NodeId> + 'a {
+        Self::ids_of(self.classes.get(class))
+    }
+
+    /// Returns the elements with a local name.
+    pub fn by_tag<'a>(&'a self, name: &str) -> impl Iterator<Item = NodeId> + 'a {
+        Self::ids_of(self.tags.get(name))
+    }
+
+    fn ids_of(postings: Option<&Postings>) -> impl Iterator<Item = NodeId> + '_ {
+        postings.into_iter().flatten().map(|(_, id)| *id)
+    }
+
+    /// Returns true if no node was added to the tree since it was indexed, and no element changed behind its back.
+    pub(crate) fn is_current(&self, tree: &Tree<NodeKind>) -> bool {
+        !self.stale && self.len == tree.nodes().len()
+    }
+
+    /// Marks the index out of date until it is built again, after an element was changed without refreshing it.
+    pub(crate) fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Indexes the nodes added to the tree since the last time, which come last in [`Tree::nodes`].
+    pub(crate) fn sync(&mut self, tree: &Tree<NodeKind>) {
+        for (position, node) in tree.nodes().enumerate().skip(self.len) {
+            if let NodeKind::Element(data) = node.value() {
+                self.insert(node.id(), position, data);
+            }
+        }
+        self.len = tree.nodes().len();
+    }
+
+    /// Indexes an element again after it was changed.
+    pub(crate) fn refresh(&mut self, id: NodeId, data: &NodeData) {
+        if let Some(entry) = self.entries.remove(&id) {
+            remove(&mut self.tags, &entry.name, entry.position);
+            if let Some(key) = &entry.id {
+                remove(&mut self.ids, key, entry.position);
+            }
+            for class in &entry.classes {
+                remove(&mut self.classes, class, entry.position);
+            }
+            self.insert(id, entry.position, data);
+        }
+    }
+
+    fn insert(&mut self, id: NodeId, position: usize, data: &NodeData) {
+        let entry = Entry {
+            position,
+            name: data.name().into(),
+            id: data.id().map(Into::into),
+            classes: data.classes().map(Into::into).collect(),
+        };
+        insert(&mut self.tags, &entry.name, position, id);
+        if let Some(key) = &entry.id {
+            insert(&mut self.ids, key, position, id);
+        }
+        for class in &entry.classes {
+            insert(&mut self.classes, class, position, id);
+        }
+        self.entries.insert(id, entry);
+    }
+
+    fn get(&self, key: Key) -> Postings {
+        match key {
+            Key::Id(id) => self.ids.get(id).cloned().unwrap_or_default(),
+            Key::Class(class) => self.classes.get(class).cloned().unwrap_or_default(),
+            Key::Tag(name, lower) if name == lower => self.tags.get(name).cloned().unwrap_or_default(),
+            Key::Tag(name, lower) => {
+                let mut found: Postings =
+                    [name, lower].iter().filter_map(|name| self.tags.get(*name)).flatten().copied().collect();
+                found.sort_unstable_by_key(|(position, _)| *position);
+                found
+            }
+        }
+    }
+
+    fn count(&self, key: Key) -> usize {
+        let len = |postings: Option<&Postings>| postings.map_or(0, Vec::len);
+        match key {
+            Key::Id(id) => len(self.ids.get(id)),
+            Key::Class(class) => len(self.classes.get(class)),
+            Key::Tag(name, lower) if name == lower => len(self.tags.get(name)),
+            Key::Tag(name, lower) => len(self.tags.get(name)) + len(self.tags.get(lower)),
+        }
+    }
+
+    /// Returns the elements which may match a selector, from an ID, class or name of the rightmost compound selector.
+    ///
+    /// Returns `None` if a selector of the group has none. IDs and classes are skipped in quirks mode, where they
+    /// match case-insensitively.
+    fn candidates(&self, selector: &Selector, quirks_mode: QuirksMode) -> Option<Postings> {
+        let keys_case = quirks_mode != QuirksMode::Quirks;
+        let mut found = Postings::new();
+        for selector in &selector.selectors {
+            let key = selector
+                .iter()
+                .filter_map(|component| match component {
+                    Component::ID(id) if keys_case => Some(Key::Id(&id.0)),
+                    Component::Class(class) if keys_case => Some(Key::Class(&class.0)),
+                    Component::LocalName(name) => Some(Key::Tag(&name.name.0, &name.lower_name.0)),
+                    _ => None,
+                })
+                .min_by_key(|key| self.count(*key))?;
+            found.extend(self.get(key));
+        }
+        if selector.selectors.len() > 1 {
+            found.sort_unstable_by_key(|(position, _)| *position);
+            found.dedup();
+        }
+        Some(found)
+    }
+}
+
+fn insert(map: &mut HashMap<HtmlStr, Postings>, key: &str, position: usize, id: NodeId) {
+    let postings = map.entry(key.into()).or_default();
+    let at = postings.partition_point(|(p, _)| *p < position);
+    postings.insert(at, (position, id));
+}
+
+fn remove(map: &mut HashMap<HtmlStr, Postings>, key: &str, position: usize) {
+    if let Some(postings) = map.get_mut(key) {
+        if let Ok(at) = postings.binary_search_by_key(&position, |(p, _)| *p) {
+            postings.remove(at);
+        }
+        if postings.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+/// Returns true if the node is reachable from the root.
+///
+/// The contents of a `<template>` count only if `templates` maps them to an attached owner element. `memo` keeps the
+/// answer for every node walked up from, so that each ancestor is visited once across calls.
+pub(crate) fn is_attached(
+    node: NodeRef<NodeKind>,
+    templates: Option<&HashMap<NodeId, NodeId>>,
+    memo: &mut HashMap<NodeId, bool>,
+) -> bool {
+    let mut path = Vec::new();
+    let mut current = node;
+    let attached = loop {
+        if let Some(attached) = memo.get(&current.id()) {
+            break *attached;
+        }
+        path.push(current.id());
+        match current.parent() {
+            Some(parent) => current = parent,
+            None if current == node.tree().root() => break true,
+            None => match templates.and_then(|owners| owners.get(&current.id()).copied()) {
+                Some(owner) => break is_attached(node.tree().get(owner).unwrap(), templates, memo),
+                None => break false,
+            },
+        }
+    };
+    memo.extend(path.into_iter().map(|id| (id, attached)));
+    attached
+}
+
+/// Lookups which use the index when the document has one.
+///
+/// They return the attached elements in the order of [`Html::select`], skipping the contents of `<template>`
+/// elements, and match like the equivalent selector: case-insensitively for IDs and classes in quirks mode.
+///
+/// Editing through [`Html`] and [`Html::get_mut`] keeps the index up to date. After a change through
+/// [`NodeMut::as_data_mut`](crate::NodeMut::as_data_mut), or after nodes are added to [`Html::tree`] directly, lookups
+/// fall back to scanning the tree until [`Html::build_index`] is called again. Changing the attributes or names of
+/// existing elements through [`Html::tree`] cannot be noticed, call [`Html::build_index`] afterwards.
+///
+/// # Examples
+///
+/// ```
+/// # use htmler::{Html, Selector};
+/// let mut html = Html::parse_fragment(r#"<p id="a" class="x">a</p><p class="x">b</p>"#);
+/// html.build_index();
+/// assert_eq!(html.get_element_by_id("a").unwrap().inner_html(), "a");
+/// let p = html.elements_by_tag("p")[1].id();
+/// html.get_mut(p).unwrap().set_attribute("id", "b");
+/// assert_eq!(html.get_element_by_id("b").unwrap().id(), p);
+/// assert_eq!(html.elements_by_class("x").len(), 2);
+/// assert_eq!(html.select(&Selector::new("p#b.x")).count(), 1);
+/// ```
+impl Html {
+    /// Indexes the elements by ID, class and local name, replacing any previous index.
+    pub fn build_index(&mut self) {
+        self.index.0 = Some(HtmlIndex::new(&self.tree));
+    }
+
+    /// Removes the index, returning it.
+    pub fn take_index(&mut self) -> Option<HtmlIndex> {
+        self.index.0.take()
+    }
+
+    /// Returns the index, if it was built.
+    pub fn index(&self) -> Option<&HtmlIndex> {
+        self.index.0.as_ref()
+    }
+
+    /// Returns the first element in document order with an ID.
+    pub fn get_element_by_id(&self, id: &str) -> Option<Node<'_>> {
+        self.lookup(Key::Id(id)).into_iter().min_by_key(|node| tree_position(node.ptr))
+    }
+
+    /// Returns the elements with a class.
+    pub fn elements_by_class(&self, class: &str) -> Vec<Node<'_>> {
+        self.lookup(Key::Class(class))
+    }
+
+    /// Returns the elements with a local name, which is compared in lowercase for HTML elements of HTML documents.
+    pub fn elements_by_tag(&self, name: &str) -> Vec<Node<'_>> {
+        self.lookup(Key::Tag(name, &name.to_ascii_lowercase()))
+    }
+
+    fn lookup(&self, key: Key) -> Vec<Node<'_>> {
+        let case = match self.quirks_mode {
+            QuirksMode::Quirks => CaseSensitivity::AsciiCaseInsensitive,
+            _ => CaseSensitivity::CaseSensitive,
+        };
+        let has_key = |node: &Node| {
+            let data = node.as_data().unwrap();
+            match key {
+                Key::Id(id) => data.id().is_some_and(|value| case.eq(value.as_bytes(), id.as_bytes())),
+                Key::Class(class) => data.classes().any(|value| case.eq(value.as_bytes(), class.as_bytes())),
+                Key::Tag(name, lower) => {
+                    let html = data.name.ns == html5ever::ns!(html) && !data.xml;
+                    data.name() == if html { lower } else { name }
+                }
+            }
+        };
+        let indexed = self.current_index().filter(|_| case == CaseSensitivity::CaseSensitive || matches!(key, Key::Tag(..)));
+        let nodes: Box<dyn Iterator<Item = NodeRef<NodeKind>> + '_> = match indexed {
+            Some(index) => Box::new(index.get(key).into_iter().map(|(_, id)| self.tree.get(id).unwrap())),
+            None => Box::new(self.tree.nodes()),
+        };
+        let mut attached = HashMap::new();
+        nodes
+            .filter(|node| is_attached(*node, None, &mut attached))
+            .filter_map(|node| self.to_element(node))
+            .filter(has_key)
+            .collect()
+    }
+
+    /// Returns the index if it saw every node of the tree.
+    fn current_index(&self) -> Option<&HtmlIndex> {
+        self.index.0.as_ref().filter(|index| index.is_current(&self.tree))
+    }
+
+    /// Returns the elements which may match a selector, in the order of [`Tree::nodes`], if the index can tell.
+    pub(crate) fn select_candidates(&self, selector: &Selector) -> Option<Vec<NodeRef<'_, NodeKind>>> {
+        let candidates = self.current_index()?.candidates(selector, self.quirks_mode)?;
+        Some(candidates.into_iter().map(|(_, id)| self.tree.get(id).unwrap()).collect())
+    }
+
+    /// Indexes the nodes added to the tree, if there is an index.
+    pub(crate) fn sync_index(&mut self) {
+        if let Some(index) = &mut self.index.0 {
+            index.sync(&self.tree);
+        }
+    }
+
+    /// Indexes an element again after its attributes or name changed, if there is an index.
+    pub(crate) fn refresh_index(&mut self, id: NodeId) {
+        if let (Some(index), Some(NodeKind::Element(data))) = (&mut self.index.0, self.tree.get(id).map(|node| node.value())) {
+            index.refresh(id, data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Html, NodeKind, Selector};
+
+    fn texts(html: &Html, selector: &str) -> Vec<String> {
+        html.select(&Selector::new(selector)).map(|node| node.text().collect()).collect()
+    }
+
+    #[test]
+    fn test_index_lookups() {
+        let mut html = Html::parse_document(
+            r#"<!DOCTYPE html><p id="a" class="x y">1</p><svg><linearGradient class="x"/></svg><template><p id="a">2</p></template>"#,
+        );
+        html.build_index();
+        let index = html.index().unwrap();
+        assert_eq!(index.by_id("a").count(), 2);
+        assert_eq!(index.by_tag("linearGradient").count(), 1);
+        assert_eq!(html.get_element_by_id("a").unwrap().text().collect::<String>(), "1");
+        assert!(html.get_element_by_id("A").is_none());
+        assert_eq!(html.elements_by_class("x").len(), 2);
+        assert_eq!(html.elements_by_tag("P").len(), 1);
+        assert_eq!(html.elements_by_tag("linearGradient").len(), 1);
+        assert!(html.elements_by_tag("lineargradient").is_empty());
+
+        let html = Html::parse_document(r#"<p id="a" class="x">1</p>"#);
+        assert_eq!(html.quirks_mode, html5ever::tree_builder::QuirksMode::Quirks);
+        let mut indexed = html.clone();
+        indexed.build_index();
+        assert_eq!(indexed, html);
+        for html in [&html, &indexed] {
+            assert!(html.get_element_by_id("A").is_some());
+            assert_eq!(html.elements_by_class("X").len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_index_selects_like_a_scan() {
+        let source = "<!DOCTYPE html><div id=a class=x><p class=x>1</p><span>2</span></div><p id=b>3</p>\
+            <template><p class=x>4</p></template><svg><foreignObject class=x></foreignObject></svg>";
+        let plain = Html::parse_document(source);
+        let mut html = plain.clone();
+        html.build_index();
+        for selector in ["#a", ".x", "p", "div > .x", "p, #a", "#a.x, span", "foreignObject", "foreignobject", ":not(p)"] {
+            assert_eq!(texts(&html, selector), texts(&plain, selector), "{}", selector);
+            let selector = Selector::new(selector);
+            let count = |html: &Html| html.select(&selector).include_templates().rev().count();
+            assert_eq!(count(&html), count(&plain));
+        }
+        assert!(html.select_candidates(&Selector::new("div > :not(p)")).is_none());
+        assert_eq!(html.select_candidates(&Selector::new("div #b")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_index_follows_edits() {
+        let mut html = Html::parse_fragment(r#"<p id="a">1</p>"#);
+        html.build_index();
+        let a = html.get_element_by_id("a").unwrap().id();
+        html.get_mut(a).unwrap().set_attribute("id", "b");
+        assert!(html.get_element_by_id("a").is_none());
+        assert_eq!(html.get_element_by_id("b").unwrap().id(), a);
+
+        let div = html.append_child(html.root_node().id(), NodeKind::element("div"));
+        html.get_mut(div).unwrap().add_class("x");
+        html.set_inner_html(div, r#"<i id="c">2</i>"#);
+        assert_eq!(html.elements_by_class("x").len(), 1);
+        assert_eq!(html.select(&Selector::new("#c")).count(), 1);
+        assert!(html.index().unwrap().is_current(&html.tree));
+
+        html.remove(div);
+        assert!(html.get_element_by_id("c").is_none());
+        assert_eq!(html.index().unwrap().by_id("c").count(), 1);
+        html.compact();
+        assert_eq!(html.index().unwrap().by_id("c").count(), 0);
+
+        // Nodes added behind the index's back are found by scanning.
+        let root = html.root_node().id();
+        html.tree.get_mut(root).unwrap().append(NodeKind::element("hr"));
+        assert!(!html.index().unwrap().is_current(&html.tree));
+        assert_eq!(html.elements_by_tag("hr").len(), 1);
+        assert_eq!(html.select(&Selector::new("hr")).count(), 1);
+
+        // So are elements changed without refreshing the index.
+        html.build_index();
+        let hr = html.elements_by_tag("hr")[0].id();
+        html.get_mut(hr).unwrap().as_data_mut().set_attribute("id", "d");
+        assert!(!html.index().unwrap().is_current(&html.tree));
+        assert_eq!(html.get_element_by_id("d").unwrap().id(), hr);
+        assert_eq!(html.select(&Selector::new("#d")).count(), 1);
+        html.build_index();
+        assert_eq!(html.index().unwrap().by_id("d").count(), 1);
+    }
+}
@@ -0,0 +1,261 @@
+use ego_tree::NodeId;
+use html5ever::{tendril::StrTendril, Attribute};
+
+use super::tree_sink::HtmlSink;
+use crate::{error::LimitExceeded, node::NodeKind};
+
+/// Bounds on the tree built from untrusted input.
+///
+/// Going over a limit truncates the tree instead of failing the parse, and records the first limit
+/// in [`Html::limit_exceeded`](crate::Html::limit_exceeded). All limits are off by default.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{
+///     error::LimitExceeded,
+///     html::{HtmlParseOptions, ParseLimits},
+///     Selector,
+/// };
+///
+/// let limits = ParseLimits::new().max_depth(3);
+/// let html = HtmlParseOptions::new()
+///     .limits(limits)
+///     .parse_fragment("<div><div><div><div>a</div></div></div></div>");
+/// assert_eq!(html.select(&Selector::new("div")).count(), 2);
+/// assert_eq!(html.limit_exceeded, Some(LimitExceeded::Depth));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseLimits {
+    max_nodes: Option<usize>,
+    max_depth: Option<usize>,
+    max_attributes: Option<usize>,
+    max_attribute_length: Option<usize>,
+    max_text_size: Option<usize>,
+    max_errors: Option<usize>,
+}
+
+impl ParseLimits {
+    /// Creates limits which allow anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of nodes the parser creates, the input after the last one is dropped.
+    pub fn max_nodes(mut self, max: usize) -> Self {
+        self.max_nodes = Some(max);
+        self
+    }
+
+    /// How deeply elements are nested below the root, deeper ones are dropped with their contents.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// The number of attributes an element keeps.
+    pub fn max_attributes(mut self, max: usize) -> Self {
+        self.max_attributes = Some(max);
+        self
+    }
+
+    /// The length in bytes of attribute names and values, longer values are truncated and longer names dropped.
+    pub fn max_attribute_length(mut self, max: usize) -> Self {
+        self.max_attribute_length = Some(max);
+        self
+    }
+
+    /// The length in bytes of a text node, the rest is dropped.
+    pub fn max_text_size(mut self, max: usize) -> Self {
+        self.max_text_size = Some(max);
+        self
+    }
+
+    /// The number of parse errors stored in [`Html::errors`](crate::Html::errors).
+    pub fn max_errors(mut self, max: usize) -> Self {
+        self.max_errors = Some(max);
+        self
+    }
+}
+
+/// Tracks the limits while the tree is being built.
+#[derive(Debug, Default)]
+pub(crate) struct Limiter {
+    pub(crate) limits: ParseLimits,
+    nodes: usize,
+}
+
+impl Limiter {
+    pub(crate) fn new(limits: ParseLimits) -> Self {
+        Limiter { limits, nodes: 0 }
+    }
+}
+
+impl HtmlSink {
+    fn exceed(&mut self, limit: LimitExceeded) {
+        self.html.limit_exceeded.get_or_insert(limit);
+    }
+
+    /// Creates a detached node, or returns `None` if there are too many nodes.
+    pub(super) fn create_node(&mut self, kind: NodeKind) -> Option<NodeId> {
+        self.reserve_node().then(|| self.html.tree.orphan(kind).id())
+    }
+
+    /// Counts a new node, returns false if it is over the limit.
+    pub(super) fn reserve_node(&mut self) -> bool {
+        if self.limiter.limits.max_nodes.is_some_and(|max| self.limiter.nodes >= max) {
+            self.exceed(LimitExceeded::Nodes);
+            return false;
+        }
+        self.limiter.nodes += 1;
+        true
+    }
+
+    /// Returns true if elements may be dropped for nesting too deeply.
+    pub(super) fn limits_depth(&self) -> bool {
+        self.limiter.limits.max_depth.is_some()
+    }
+
+    /// Returns true if an element can be appended to `parent` without nesting too deeply.
+    pub(super) fn can_nest(&mut self, parent: NodeId) -> bool {
+        let max = match self.limiter.limits.max_depth {
+            Some(max) => max,
+            None => return true,
+        };
+        if self.html.tree.get(parent).unwrap().ancestors().take(max).count() < max {
+            return true;
+        }
+        self.exceed(LimitExceeded::Depth);
+        false
+    }
+
+    /// Drops attributes over the count limit and truncates long values.
+    pub(super) fn limit_attributes(&mut self, mut attrs: Vec<Attribute>, existing: usize) -> Vec<Attribute> {
+        if let Some(max) = self.limiter.limits.max_attributes {
+            let keep = max.saturating_sub(existing);
+            if attrs.len() > keep {
+                attrs.truncate(keep);
+                self.exceed(LimitExceeded::AttributeCount);
+            }
+        }
+        if let Some(max) = self.limiter.limits.max_attribute_length {
+            let count = attrs.len();
+            attrs.retain(|a| a.name.local.len() <= max);
+            let mut exceeded = attrs.len() < count;
+            for attr in attrs.iter_mut().filter(|a| a.value.len() > max) {
+                attr.value.pop_back(attr.value.len32() - floor_char_boundary(&attr.value, max) as u32);
+                exceeded = true;
+            }
+            if exceeded {
+                self.exceed(LimitExceeded::AttributeLength);
+            }
+        }
+        attrs
+    }
+
+    /// Truncates text which is appended to a text node of length `existing`, or to a new node if `None`.
+    ///
+    /// Returns `None` if nothing is left to append.
+    pub(super) fn limit_text(&mut self, mut text: StrTendril, existing: Option<usize>) -> Option<StrTendril> {
+        if let Some(max) = self.limiter.limits.max_text_size {
+            let room = max.saturating_sub(existing.unwrap_or(0));
+            if text.len() > room {
+                text.pop_back(text.len32() - floor_char_boundary(&text, room) as u32);
+                self.exceed(LimitExceeded::TextSize);
+                if text.is_empty() {
+                    return None;
+                }
+            }
+        }
+        if existing.is_none() && !self.reserve_node() {
+            return None;
+        }
+        Some(text)
+    }
+
+    /// Returns true if another parse error can be stored.
+    pub(super) fn accepts_error(&mut self) -> bool {
+        if self.limiter.limits.max_errors.is_some_and(|max| self.html.errors.len() >= max) {
+            self.exceed(LimitExceeded::Errors);
+            return false;
+        }
+        true
+    }
+}
+
+/// Returns the largest char boundary of `s` not after `index`.
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    (0..=index.min(s.len())).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseLimits;
+    use crate::{error::LimitExceeded, html::HtmlParseOptions, Html, Selector};
+
+    fn parse(limits: ParseLimits, fragment: &str) -> Html {
+        HtmlParseOptions::new().limits(limits).parse_fragment(fragment)
+    }
+
+    #[test]
+    fn unlimited_by_default() {
+        let source = "<p a=1 b=2>text<!-- c --></p>".repeat(50);
+        let html = parse(ParseLimits::new(), &source);
+        assert_eq!(html, Html::parse_fragment(&source));
+        assert_eq!(html.limit_exceeded, None);
+    }
+
+    #[test]
+    fn max_nodes() {
+        let html = parse(ParseLimits::new().max_nodes(7), "<ul><li>a</li><li>b</li><li>c</li></ul>");
+        // The context element and the root `<html>` of a fragment are nodes too.
+        assert_eq!(html.root_node().html(), "<html><ul><li>a</li><li>b</li></ul></html>");
+        assert_eq!(html.limit_exceeded, Some(LimitExceeded::Nodes));
+        assert_eq!(html.select(&Selector::new("li")).count(), 2);
+    }
+
+    #[test]
+    fn max_depth() {
+        let html = parse(ParseLimits::new().max_depth(3), "<b><i><u>a</u>b</i></b>c");
+        assert_eq!(html.root_node().html(), "<html><b><i>b</i></b>c</html>");
+        assert_eq!(html.limit_exceeded, Some(LimitExceeded::Depth));
+
+        // Nothing is created for dropped elements, the context element is the only detached node.
+        let html = parse(ParseLimits::new().max_depth(2), &"<div><p>a</p>".repeat(100));
+        assert_eq!(html.root_node().html(), "<html><div></div></html>");
+        assert_eq!(html.tree.nodes().count(), html.tree.root().descendants().count() + 1);
+    }
+
+    #[test]
+    fn limits_which_are_not_hit() {
+        let source = "<table><tr><td><b>a<p>b</b>c</p></td></tr></table><template><i>d</i></template>";
+        let limits = ParseLimits::new().max_depth(20).max_nodes(100);
+        assert_eq!(parse(limits, source), Html::parse_fragment(source));
+        assert_eq!(HtmlParseOptions::new().limits(limits).parse_document(source), Html::parse_document(source));
+    }
+
+    #[test]
+    fn attribute_limits() {
+        let html = parse(ParseLimits::new().max_attributes(2), "<p a=1 b=2 c=3></p>");
+        assert_eq!(html.root_node().inner_html(), r#"<p a="1" b="2"></p>"#);
+        assert_eq!(html.limit_exceeded, Some(LimitExceeded::AttributeCount));
+
+        let html = parse(ParseLimits::new().max_attribute_length(5), "<p title=日本 long-name=a id=abcdef></p>");
+        assert_eq!(html.root_node().inner_html(), r#"<p title="日" id="abcde"></p>"#);
+        assert_eq!(html.limit_exceeded, Some(LimitExceeded::AttributeLength));
+    }
+
+    #[test]
+    fn max_text_size() {
+        let html = parse(ParseLimits::new().max_text_size(5), "<p>abc&amp;日本</p><p>ok</p>");
+        assert_eq!(html.root_node().inner_html(), "<p>abc&amp;</p><p>ok</p>");
+        assert_eq!(html.limit_exceeded, Some(LimitExceeded::TextSize));
+    }
+
+    #[test]
+    fn max_errors() {
+        let html = parse(ParseLimits::new().max_errors(1), "</a></b></c>");
+        assert_eq!(html.errors.len(), 1);
+        assert_eq!(html.limit_exceeded, Some(LimitExceeded::Errors));
+    }
+}
@@ -1,43 +1,80 @@
 //! HTML documents and fragments.
 
-use std::borrow::Cow;
+use std::collections::HashMap;
 
-use ego_tree::{iter::Nodes, Tree};
+use ego_tree::{iter::Nodes, NodeId, NodeRef, Tree};
+use encoding_rs::{Encoding, UTF_8};
 use html5ever::{
-    driver, serialize,
+    serialize,
     serialize::{SerializeOpts, TraversalScope},
     tree_builder::QuirksMode,
-    QualName,
 };
-use tendril::TendrilSink;
 
-use crate::{selector::Selector, Node, NodeKind};
+use crate::{
+    error::{LimitExceeded, ParseDiagnostic, SelectorErrorKind},
+    selector::{Selector, ToSelector},
+    Node, NodeKind,
+};
+
+pub use self::{
+    edit::NodeOrId, filter::NodeFilter, index::HtmlIndex, limits::ParseLimits, options::HtmlParseOptions, parser::HtmlParser,
+    tree_sink::HtmlSink,
+};
+pub(crate) use self::tree_sink::concat_text;
+use self::index::IndexCache;
 
 /// An HTML tree.
 ///
 /// Parsing does not fail hard. Instead, the `quirks_mode` is set and errors are added to the
-/// `errors` field. The `tree` will still be populated as best as possible.
+/// `errors` field along with the line they were found on. The `tree` will still be populated as
+/// best as possible.
+///
+/// Parsed through [`HtmlSink`], which implements the `TreeSink` trait from the `html5ever` crate.
 ///
-/// Implements the `TreeSink` trait from the `html5ever` crate, which allows HTML to be parsed.
+/// Trees compare equal whether or not they have an index.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Html {
     /// Parse errors.
-    pub errors: Vec<Cow<'static, str>>,
+    pub errors: Vec<ParseDiagnostic>,
     /// The quirks mode.
     pub quirks_mode: QuirksMode,
+    /// The character encoding the input was decoded from.
+    pub encoding: &'static Encoding,
     /// The node tree.
     pub tree: Tree<NodeKind>,
+    /// The first of the [`ParseLimits`] which was exceeded, if the tree was truncated.
+    pub limit_exceeded: Option<LimitExceeded>,
+    /// Whether the tree was parsed from XML.
+    pub(crate) xml: bool,
+    /// Speeds up lookups once built, see [`Html::build_index`].
+    pub(crate) index: IndexCache,
 }
 
 impl Html {
     /// Creates an empty HTML document.
     pub fn new_document() -> Self {
-        Html { errors: Vec::new(), quirks_mode: QuirksMode::NoQuirks, tree: Tree::new(NodeKind::Document) }
+        Html {
+            errors: Vec::new(),
+            quirks_mode: QuirksMode::NoQuirks,
+            encoding: UTF_8,
+            tree: Tree::new(NodeKind::Document),
+            limit_exceeded: None,
+            xml: false,
+            index: IndexCache::default(),
+        }
     }
 
     /// Creates an empty HTML fragment.
     pub fn new_fragment() -> Self {
-        Html { errors: Vec::new(), quirks_mode: QuirksMode::NoQuirks, tree: Tree::new(NodeKind::Fragment) }
+        Html {
+            errors: Vec::new(),
+            quirks_mode: QuirksMode::NoQuirks,
+            encoding: UTF_8,
+            tree: Tree::new(NodeKind::Fragment),
+            limit_exceeded: None,
+            xml: false,
+            index: IndexCache::default(),
+        }
     }
 
     /// Parses a string of HTML as a document.
@@ -48,38 +85,82 @@ impl Html {
     /// # fn main() {
     /// # let document = "";
     /// use html5ever::driver::{self, ParseOpts};
-    /// use htmler::Html;
+    /// use htmler::{html::HtmlSink, Html};
     /// use tendril::TendrilSink;
     ///
-    /// let parser = driver::parse_document(Html::new_document(), ParseOpts::default());
+    /// let parser = driver::parse_document(HtmlSink::new(Html::new_document()), ParseOpts::default());
     /// let html = parser.one(document);
     /// # }
     /// ```
     pub fn parse_document(document: &str) -> Self {
-        let parser = driver::parse_document(Self::new_document(), Default::default());
-        parser.one(document)
+        HtmlParseOptions::default().parse_document(document)
     }
 
-    /// Parses a string of HTML as a fragment.
+    /// Parses a string of HTML as a fragment in the `<body>` context.
+    ///
+    /// Use [`HtmlParseOptions::context_element`] to parse in another context.
     pub fn parse_fragment(fragment: &str) -> Self {
-        let parser = driver::parse_fragment(
-            Self::new_fragment(),
-            Default::default(),
-            QualName::new(None, ns!(html), local_name!("body")),
-            Vec::new(),
-        );
-        parser.one(fragment)
+        HtmlParseOptions::default().parse_fragment(fragment)
     }
 
     /// Returns an iterator over elements matching a selector.
+    ///
+    /// Classes and IDs match case-insensitively if the document is in quirks mode.
+    /// The contents of `<template>` elements are skipped, see [`HtmlSelect::include_templates`].
+    /// With an index, see [`Html::build_index`], only the elements indexed under an ID, class or name of the rightmost
+    /// compound selector are tried.
     pub fn select<'a, 'b>(&'a self, selector: &'b Selector) -> HtmlSelect<'a, 'b> {
-        HtmlSelect { inner: self.tree.nodes(), selector }
+        let candidates = self.select_candidates(selector).map(Vec::into_iter);
+        HtmlSelect {
+            inner: self.tree.nodes(),
+            candidates,
+            selector,
+            quirks_mode: self.quirks_mode,
+            templates: None,
+            attached: HashMap::new(),
+        }
+    }
+
+    /// Returns the first element in document order matching a selector, given as a [`Selector`] or a string to parse.
+    ///
+    /// Unlike [`Html::select`], which visits elements in the order they were created, this is the first match of the
+    /// tree as it is now.
+    pub fn select_first<'s, S>(&self, selector: S) -> Result<Option<Node<'_>>, SelectorErrorKind<'s>>
+    where
+        S: ToSelector<'s>,
+    {
+        selector.with_selector(|selector| {
+            let matches = self.select(selector);
+            if matches.candidates.is_some() {
+                return matches.min_by_key(|node| tree_position(node.ptr));
+            }
+            let mut elements = self.tree.root().descendants().filter_map(|node| self.to_element(node));
+            elements.find(|element| selector.matches_with_quirks_mode(element, None, self.quirks_mode))
+        })
+    }
+
+    /// Returns the node with the given ID, see [`Node::id`].
+    ///
+    /// IDs stay valid while the document is edited, and detached nodes can still be looked up.
+    /// [`Html::compact`] rebuilds the tree and invalidates all IDs, use the map it returns to translate them.
+    pub fn node(&self, id: NodeId) -> Option<Node<'_>> {
+        self.tree.get(id).map(|node| self.to_node(node))
+    }
+
+    /// Wraps a node of this tree, which then matches selectors in the quirks mode of the document.
+    pub(crate) fn to_node<'a>(&'a self, ptr: NodeRef<'a, NodeKind>) -> Node<'a> {
+        Node { ptr, quirks_mode: self.quirks_mode }
+    }
+
+    /// Wraps a node of this tree only if it is an element, see [`Html::to_node`].
+    pub(crate) fn to_element<'a>(&'a self, ptr: NodeRef<'a, NodeKind>) -> Option<Node<'a>> {
+        Node::wrap_in(ptr, self.quirks_mode)
     }
 
     /// Returns the root `<html>` element.
     pub fn root_node(&self) -> Node {
         let root_node = self.tree.root().children().find(|child| child.value().is_element()).expect("html node missing");
-        Node::wrap(root_node).unwrap()
+        self.to_element(root_node).unwrap()
     }
 
     /// Serialize entire document into HTML.
@@ -99,51 +180,106 @@ impl Html {
 #[derive(Debug)]
 pub struct HtmlSelect<'a, 'b> {
     inner: Nodes<'a, NodeKind>,
+    /// The elements to try instead of all nodes, when the index can tell.
+    candidates: Option<std::vec::IntoIter<NodeRef<'a, NodeKind>>>,
     selector: &'b Selector,
+    quirks_mode: QuirksMode,
+    /// Maps template contents to their owner element, present when descending into templates.
+    templates: Option<HashMap<NodeId, NodeId>>,
+    /// Whether the nodes seen so far are reachable, so that each ancestor is walked up from once.
+    attached: HashMap<NodeId, bool>,
+}
+
+impl<'a, 'b> HtmlSelect<'a, 'b> {
+    /// Also yields matching elements inside the contents of `<template>` elements.
+    pub fn include_templates(mut self) -> Self {
+        let owners = self.inner.clone().filter_map(|node| match node.value() {
+            NodeKind::Element(e) => e.template_contents.map(|contents| (contents, node.id())),
+            _ => None,
+        });
+        self.templates = Some(owners.collect());
+        self.attached.clear();
+        self
+    }
+
+    fn next_node(&mut self) -> Option<NodeRef<'a, NodeKind>> {
+        match &mut self.candidates {
+            Some(candidates) => candidates.next(),
+            None => self.inner.next(),
+        }
+    }
+
+    fn next_node_back(&mut self) -> Option<NodeRef<'a, NodeKind>> {
+        match &mut self.candidates {
+            Some(candidates) => candidates.next_back(),
+            None => self.inner.next_back(),
+        }
+    }
+
+    fn accept(&mut self, node: NodeRef<'a, NodeKind>) -> Option<Node<'a>> {
+        let element = Node::wrap_in(node, self.quirks_mode)?;
+        let attached = index::is_attached(node, self.templates.as_ref(), &mut self.attached);
+        (attached && self.selector.matches_with_quirks_mode(&element, None, self.quirks_mode)).then_some(element)
+    }
 }
 
 impl<'a, 'b> Iterator for HtmlSelect<'a, 'b> {
     type Item = Node<'a>;
 
     fn next(&mut self) -> Option<Node<'a>> {
-        for node in self.inner.by_ref() {
-            match Node::wrap(node) {
-                Some(element) => {
-                    if element.ptr.parent().is_some() && self.selector.matches(&element) {
-                        return Some(element);
-                    }
-                }
-                None => {}
+        while let Some(node) = self.next_node() {
+            if let Some(element) = self.accept(node) {
+                return Some(element);
             }
         }
         None
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let (_, upper) = self.inner.size_hint();
+        let (_, upper) = match &self.candidates {
+            Some(candidates) => candidates.size_hint(),
+            None => self.inner.size_hint(),
+        };
         (0, upper)
     }
 }
 
 impl<'a, 'b> DoubleEndedIterator for HtmlSelect<'a, 'b> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        for node in self.inner.by_ref().rev() {
-            if let Some(element) = Node::wrap(node) {
-                if element.ptr.parent().is_some() && self.selector.matches(&element) {
-                    return Some(element);
-                }
+        while let Some(node) = self.next_node_back() {
+            if let Some(element) = self.accept(node) {
+                return Some(element);
             }
         }
         None
     }
 }
 
+/// Returns the index of the node and each of its ancestors among their siblings, which sort in document order.
+fn tree_position(node: NodeRef<NodeKind>) -> Vec<usize> {
+    let mut position: Vec<_> =
+        std::iter::once(node).chain(node.ancestors()).map(|node| node.prev_siblings().count()).collect();
+    position.reverse();
+    position
+}
+
+mod compact;
+mod edit;
+mod encoding;
+mod filter;
+mod graft;
+mod index;
+mod limits;
+mod options;
+mod parser;
 mod serializable;
+mod span;
 mod tree_sink;
+mod xml;
 
 #[cfg(test)]
 mod tests {
-    use super::{Html, Selector};
+    use super::{Html, Node, NodeKind, Selector};
 
     #[test]
     fn root_element_fragment() {
@@ -151,7 +287,7 @@ mod tests {
         let root_ref = html.root_node();
         let href = root_ref.select(&Selector::try_parse("a").unwrap()).next().unwrap();
         assert_eq!(href.inner_html(), "1");
-        assert_eq!(href.value().get_attribute("href").unwrap(), "http://github.com");
+        assert_eq!(href.as_data().unwrap().get_attribute("href").unwrap(), "http://github.com");
     }
 
     #[test]
@@ -187,10 +323,72 @@ mod tests {
         assert_eq!(upper, Some(10));
     }
 
-    #[cfg(feature = "atomic")]
     #[test]
-    fn html_is_send() {
-        fn send_sync<S: Send>() {}
+    fn select_skips_template_contents() {
+        let html = Html::parse_document("<p>1</p><template><p>2</p><template><p>3</p></template></template><p>4</p>");
+        let selector = Selector::try_parse("p").unwrap();
+        let result: Vec<_> = html.select(&selector).map(|e| e.inner_html()).collect();
+        assert_eq!(result, vec!["1", "4"]);
+        let result: Vec<_> = html.select(&selector).include_templates().map(|e| e.inner_html()).collect();
+        assert_eq!(result, vec!["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn select_skips_detached_nodes() {
+        let mut html = Html::parse_fragment("<div><p>1</p><p>2</p></div><template><div><p>3</p></div></template>");
+        let div = html.select(&Selector::try_parse("div").unwrap()).next().unwrap().id();
+        html.remove(div);
+        let selector = Selector::try_parse("p").unwrap();
+        assert_eq!(html.select(&selector).count(), 0);
+        let result: Vec<_> = html.select(&selector).include_templates().rev().map(|e| e.inner_html()).collect();
+        assert_eq!(result, vec!["3"]);
+    }
+
+    #[test]
+    fn select_first_is_in_document_order() {
+        let mut html = Html::parse_fragment(r#"<ul><li id="a">1</li></ul>"#);
+        let ul = html.select_first("ul").unwrap().unwrap().id();
+        let li = html.prepend_child(ul, NodeKind::element("li"));
+        html.set_inner_html(li, "0");
+        html.get_mut(li).unwrap().set_attribute("id", "a");
+        let by_id = Selector::new("#a");
+        for indexed in [false, true] {
+            if indexed {
+                html.build_index();
+            }
+            assert_eq!(html.select_first("li").unwrap().unwrap().inner_html(), "0");
+            assert_eq!(html.select_first(&by_id).unwrap().unwrap().inner_html(), "0");
+            assert_eq!(html.get_element_by_id("a").unwrap().inner_html(), "0");
+        }
+    }
+
+    #[test]
+    fn template_contents_are_not_children() {
+        let html = Html::parse_fragment("<template><td>cell</td></template>");
+        let template = html.select(&Selector::try_parse("template").unwrap()).next().unwrap();
+        assert!(template.first_child().is_none());
+        let contents = template.template_contents().unwrap();
+        assert!(contents.as_kind().is_fragment());
+        assert_eq!(contents.first_child().unwrap().as_data().unwrap().name(), "td");
+    }
+
+    #[test]
+    fn html_is_send_sync() {
+        fn send_sync<S: Send + Sync>() {}
         send_sync::<Html>();
+        send_sync::<Node>();
+        send_sync::<Selector>();
+    }
+
+    #[test]
+    fn select_across_threads() {
+        let source = "<p class=a>1</p><p class=b>2</p>".repeat(100);
+        let html = Html::parse_document(&source);
+        let selectors = [Selector::new("p.a"), Selector::new(".b"), Selector::new("p")];
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = selectors.iter().map(|s| scope.spawn(|| html.select(s).count())).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(counts, vec![100, 100, 200]);
     }
 }
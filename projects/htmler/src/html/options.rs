@@ -0,0 +1,279 @@
+use html5ever::{
+    driver::{self, ParseOpts, Parser},
+    tokenizer::TokenizerOpts,
+    tree_builder::{self, QuirksMode, TreeBuilder, TreeBuilderOpts},
+    Attribute, LocalName, Namespace, QualName,
+};
+use tendril::TendrilSink;
+
+use super::{
+    filter::Filtering,
+    limits::Limiter,
+    span::parse_tracked,
+    tree_sink::{Handle, HtmlSink},
+    Html, HtmlParser, NodeFilter, ParseLimits,
+};
+
+/// Options for parsing HTML documents and fragments.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{html::HtmlParseOptions, Selector};
+///
+/// // Parsed with the default `<body>` context, the table cell would be dropped.
+/// let fragment = HtmlParseOptions::new().context_element("tr").parse_fragment("<td>a</td>");
+/// assert_eq!(fragment.select(&Selector::new("td")).count(), 1);
+///
+/// // Without scripting, the contents of `<noscript>` are parsed as markup.
+/// let document = HtmlParseOptions::new()
+///     .scripting_enabled(false)
+///     .parse_document("<body><noscript><p>a</p></noscript>");
+/// assert_eq!(document.select(&Selector::new("noscript > p")).count(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlParseOptions {
+    scripting_enabled: bool,
+    exact_errors: bool,
+    source_spans: bool,
+    drop_doctype: bool,
+    iframe_srcdoc: bool,
+    quirks_mode: QuirksMode,
+    context_name: QualName,
+    context_attributes: Vec<Attribute>,
+    limits: ParseLimits,
+    filter: NodeFilter,
+}
+
+impl Default for HtmlParseOptions {
+    fn default() -> Self {
+        HtmlParseOptions {
+            scripting_enabled: true,
+            exact_errors: false,
+            source_spans: false,
+            drop_doctype: false,
+            iframe_srcdoc: false,
+            quirks_mode: QuirksMode::NoQuirks,
+            context_name: QualName::new(None, ns!(html), local_name!("body")),
+            context_attributes: Vec::new(),
+            limits: ParseLimits::default(),
+            filter: NodeFilter::default(),
+        }
+    }
+}
+
+impl HtmlParseOptions {
+    /// Creates the default options, which are used by [`Html::parse_document`] and [`Html::parse_fragment`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether scripting is considered enabled, which makes `<noscript>` contents raw text. Default: `true`.
+    pub fn scripting_enabled(mut self, enabled: bool) -> Self {
+        self.scripting_enabled = enabled;
+        self
+    }
+
+    /// Whether to report detailed error messages with their columns, at some performance penalty. Default: `false`.
+    ///
+    /// Columns are only tracked when parsing a string, not when feeding an [`HtmlParser`].
+    pub fn exact_errors(mut self, exact: bool) -> Self {
+        self.exact_errors = exact;
+        self
+    }
+
+    /// Whether to record where elements, text and comments are in the source. Default: `false`.
+    ///
+    /// Like columns, spans are only recorded when parsing a string. See [`Node::source_span`](crate::Node::source_span).
+    pub fn source_spans(mut self, spans: bool) -> Self {
+        self.source_spans = spans;
+        self
+    }
+
+    /// Whether to leave the doctype out of the tree. Default: `false`.
+    pub fn drop_doctype(mut self, drop: bool) -> Self {
+        self.drop_doctype = drop;
+        self
+    }
+
+    /// Whether the document is the `srcdoc` of an `<iframe>`, which never puts it into quirks mode. Default: `false`.
+    pub fn iframe_srcdoc(mut self, srcdoc: bool) -> Self {
+        self.iframe_srcdoc = srcdoc;
+        self
+    }
+
+    /// The quirks mode the parser starts in. Default: [`QuirksMode::NoQuirks`].
+    ///
+    /// A document's doctype still decides its own quirks mode, this mostly matters for fragments.
+    pub fn quirks_mode(mut self, mode: QuirksMode) -> Self {
+        self.quirks_mode = mode;
+        self
+    }
+
+    /// Parses fragments as the contents of the given HTML element, such as `tr` or `select`. Default: `body`.
+    pub fn context_element(self, tag: &str) -> Self {
+        self.context_element_ns(ns!(html), tag, Vec::new())
+    }
+
+    /// Parses fragments as the contents of an element in any namespace, such as SVG or MathML.
+    ///
+    /// Attributes matter for some contexts, e.g. `encoding` on MathML `annotation-xml`.
+    pub fn context_element_ns(mut self, ns: Namespace, tag: &str, attributes: Vec<Attribute>) -> Self {
+        self.context_name = QualName::new(None, ns, LocalName::from(tag));
+        self.context_attributes = attributes;
+        self
+    }
+
+    /// Bounds the size of the tree, see [`ParseLimits`]. Default: no limits.
+    pub fn limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Leaves some kinds of nodes out of the tree, see [`NodeFilter`]. Default: keep everything.
+    pub fn filter(mut self, filter: NodeFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Parses a string of HTML as a document.
+    pub fn parse_document(&self, document: &str) -> Html {
+        if !self.exact_errors && !self.source_spans {
+            return self.document_parser_for(Html::new_document()).one(document);
+        }
+        self.run(TreeBuilder::new(self.prepare(Html::new_document()), self.parse_opts().tree_builder), document)
+    }
+
+    /// Parses a string of HTML as a fragment.
+    pub fn parse_fragment(&self, fragment: &str) -> Html {
+        if !self.exact_errors && !self.source_spans {
+            return self.fragment_parser_for(Html::new_fragment()).one(fragment);
+        }
+        let mut sink = self.prepare(Html::new_fragment());
+        let context = tree_builder::create_element(&mut sink, self.context_name.clone(), self.context_attributes.clone());
+        self.run(TreeBuilder::new_for_fragment(sink, context, None, self.parse_opts().tree_builder), fragment)
+    }
+
+    /// Creates a push-style parser which builds a document.
+    pub fn document_parser(&self) -> HtmlParser {
+        HtmlParser::wrap(self.document_parser_for(Html::new_document()))
+    }
+
+    /// Creates a push-style parser which builds a fragment.
+    pub fn fragment_parser(&self) -> HtmlParser {
+        HtmlParser::wrap(self.fragment_parser_for(Html::new_fragment()))
+    }
+
+    fn parse_opts(&self) -> ParseOpts {
+        ParseOpts {
+            tokenizer: TokenizerOpts { exact_errors: self.exact_errors, ..Default::default() },
+            tree_builder: TreeBuilderOpts {
+                exact_errors: self.exact_errors,
+                scripting_enabled: self.scripting_enabled,
+                iframe_srcdoc: self.iframe_srcdoc,
+                drop_doctype: self.drop_doctype,
+                quirks_mode: self.quirks_mode,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn prepare(&self, mut html: Html) -> HtmlSink {
+        html.quirks_mode = self.quirks_mode;
+        let mut sink = HtmlSink::new(html);
+        sink.limiter = Limiter::new(self.limits);
+        sink.filtering = Filtering::new(self.filter);
+        sink
+    }
+
+    pub(super) fn document_parser_for(&self, document: Html) -> Parser<HtmlSink> {
+        driver::parse_document(self.prepare(document), self.parse_opts())
+    }
+
+    pub(super) fn fragment_parser_for(&self, fragment: Html) -> Parser<HtmlSink> {
+        let fragment = self.prepare(fragment);
+        driver::parse_fragment(fragment, self.parse_opts(), self.context_name.clone(), self.context_attributes.clone())
+    }
+
+    /// Parses the whole input character by character, so that errors and nodes know their position.
+    fn run(&self, builder: TreeBuilder<Handle, HtmlSink>, input: &str) -> Html {
+        let mut tokenizer = self.parse_opts().tokenizer;
+        if builder.is_fragment() {
+            tokenizer.initial_state = Some(builder.tokenizer_state_for_context_elem());
+        }
+        parse_tracked(builder, tokenizer, input, self.exact_errors, self.source_spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HtmlParseOptions;
+    use crate::{Html, Selector};
+    use html5ever::{tree_builder::QuirksMode, Attribute, QualName};
+
+    fn names(html: &Html) -> Vec<String> {
+        html.root_node().descendants().filter_map(|n| n.as_data().map(|d| d.name().to_string())).collect()
+    }
+
+    #[test]
+    fn default_matches_html() {
+        let source = "<!DOCTYPE html><noscript><p>a</p></noscript><td>b</td>";
+        assert_eq!(HtmlParseOptions::new().parse_document(source), Html::parse_document(source));
+        assert_eq!(HtmlParseOptions::new().parse_fragment(source), Html::parse_fragment(source));
+    }
+
+    #[test]
+    fn fragment_context() {
+        let cells = HtmlParseOptions::new().context_element("tr").parse_fragment("<td>a</td><th>b</th>");
+        assert_eq!(names(&cells), vec!["html", "td", "th"]);
+        assert_eq!(names(&Html::parse_fragment("<td>a</td><th>b</th>")), vec!["html"]);
+
+        let options = HtmlParseOptions::new().context_element("select").parse_fragment("<option>a<div>b</div>");
+        assert_eq!(names(&options), vec!["html", "option"]);
+
+        let svg = HtmlParseOptions::new().context_element_ns(ns!(svg), "svg", Vec::new()).parse_fragment("<foreignObject/>");
+        let element = svg.select(&Selector::new("*|foreignObject")).next().unwrap();
+        assert_eq!(element.as_data().unwrap().name.ns, ns!(svg));
+        assert_eq!(element.as_data().unwrap().name(), "foreignObject");
+
+        let html = Attribute { name: QualName::new(None, ns!(), local_name!("encoding")), value: "text/html".into() };
+        let math =
+            HtmlParseOptions::new().context_element_ns(ns!(mathml), "annotation-xml", vec![html]).parse_fragment("<p>a</p>");
+        assert_eq!(math.select(&Selector::new("p")).next().unwrap().as_data().unwrap().name.ns, ns!(html));
+    }
+
+    #[test]
+    fn tree_builder_flags() {
+        let source = "<body><noscript><p>a</p></noscript>";
+        let scripting = HtmlParseOptions::new().parse_document(source);
+        assert_eq!(scripting.select(&Selector::new("noscript > p")).count(), 0);
+        let no_scripting = HtmlParseOptions::new().scripting_enabled(false).parse_document(source);
+        assert_eq!(no_scripting.select(&Selector::new("noscript > p")).count(), 1);
+
+        let source = "<!DOCTYPE html><p>a";
+        let dropped = HtmlParseOptions::new().drop_doctype(true).parse_document(source);
+        assert!(!dropped.tree.root().children().any(|n| n.value().is_doctype()));
+
+        assert_eq!(Html::parse_document("<p>a").quirks_mode, QuirksMode::Quirks);
+        assert_eq!(HtmlParseOptions::new().iframe_srcdoc(true).parse_document("<p>a").quirks_mode, QuirksMode::NoQuirks);
+        let fragment = HtmlParseOptions::new().quirks_mode(QuirksMode::Quirks).parse_fragment("<p>a");
+        assert_eq!(fragment.quirks_mode, QuirksMode::Quirks);
+    }
+
+    #[test]
+    fn exact_errors() {
+        let coarse = HtmlParseOptions::new().parse_fragment("<p a a>");
+        let exact = HtmlParseOptions::new().exact_errors(true).parse_fragment("<p a a>");
+        assert_eq!(coarse.errors[0].column, None);
+        assert_eq!(exact.errors[0].column, Some(7));
+        assert_eq!(exact.tree, coarse.tree);
+    }
+
+    #[test]
+    fn streaming_with_options() {
+        let mut parser = HtmlParseOptions::new().context_element("tr").fragment_parser();
+        parser.feed(b"<td>a</t");
+        parser.feed(b"d>");
+        assert_eq!(parser.finish(), HtmlParseOptions::new().context_element("tr").parse_fragment("<td>a</td>"));
+    }
+}
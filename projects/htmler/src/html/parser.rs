@@ -0,0 +1,169 @@
+use std::{
+    fmt::{Debug, Formatter},
+    io::{self, Read, Write},
+};
+
+use html5ever::driver::Parser;
+use tendril::{stream::Utf8LossyDecoder, ByteTendril, TendrilSink};
+
+use super::{Html, HtmlParseOptions, HtmlSink};
+
+/// A push-style HTML parser which accepts the input as byte chunks.
+///
+/// Chunks are decoded as UTF-8, a multi-byte character may be split across chunk boundaries.
+/// The resulting tree is identical to the one produced by [`Html::parse_document`] or
+/// [`Html::parse_fragment`] on the concatenated input. Use [`HtmlParseOptions`] to create a parser
+/// with other options.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{html::HtmlParser, Selector};
+///
+/// let mut parser = HtmlParser::document();
+/// parser.feed(b"<ul><li>Foo</li><l");
+/// parser.feed(b"i>Bar</li></ul>");
+/// let html = parser.finish();
+/// assert_eq!(html.select(&Selector::new("li")).count(), 2);
+/// ```
+pub struct HtmlParser {
+    inner: Utf8LossyDecoder<Parser<HtmlSink>>,
+}
+
+impl Debug for HtmlParser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HtmlParser").finish_non_exhaustive()
+    }
+}
+
+impl HtmlParser {
+    /// Creates a parser which builds an HTML document.
+    pub fn document() -> Self {
+        HtmlParseOptions::default().document_parser()
+    }
+
+    /// Creates a parser which builds an HTML fragment in the `<body>` context.
+    pub fn fragment() -> Self {
+        HtmlParseOptions::default().fragment_parser()
+    }
+
+    pub(super) fn wrap(parser: Parser<HtmlSink>) -> Self {
+        HtmlParser { inner: parser.from_utf8() }
+    }
+
+    /// Feeds the next chunk of input to the parser.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        if !chunk.is_empty() {
+            self.inner.process(ByteTendril::from_slice(chunk));
+        }
+    }
+
+    /// Signals the end of input and returns the parsed tree.
+    pub fn finish(self) -> Html {
+        self.inner.finish()
+    }
+}
+
+impl Write for HtmlParser {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.feed(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Html {
+    /// Parses HTML read from `reader` as a document.
+    ///
+    /// The input is decoded as UTF-8, invalid sequences are replaced with `U+FFFD`.
+    pub fn parse_document_from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        HtmlParseOptions::default().document_parser().inner.read_from(reader)
+    }
+
+    /// Parses HTML read from `reader` as a fragment.
+    ///
+    /// The input is decoded as UTF-8, invalid sequences are replaced with `U+FFFD`.
+    pub fn parse_fragment_from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        HtmlParseOptions::default().fragment_parser().inner.read_from(reader)
+    }
+
+    /// Parses a string of HTML as a document, reporting detailed error messages with their columns.
+    ///
+    /// This is slower than [`Html::parse_document`] and meant for linting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use htmler::{error::ParseErrorKind, Html};
+    ///
+    /// let html = Html::parse_document_exact("<!DOCTYPE html>\n<p id=a id=b>");
+    /// let error = &html.errors[0];
+    /// assert_eq!(error.kind, ParseErrorKind::MalformedTag);
+    /// assert_eq!((error.line, error.column), (2, Some(13)));
+    /// ```
+    pub fn parse_document_exact(document: &str) -> Self {
+        HtmlParseOptions::default().exact_errors(true).parse_document(document)
+    }
+
+    /// Parses a string of HTML as a fragment, reporting detailed error messages with their columns.
+    pub fn parse_fragment_exact(fragment: &str) -> Self {
+        HtmlParseOptions::default().exact_errors(true).parse_fragment(fragment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HtmlParser;
+    use crate::{error::ParseErrorKind, Html};
+    use std::io::Write;
+
+    const SOURCE: &str = "<!DOCTYPE html><title>标题</title><p class=a>Hello, <b>world</b>!<table><td>1</table>";
+
+    #[test]
+    fn chunks_match_string_parse() {
+        let expected = Html::parse_document(SOURCE);
+        for size in [1, 2, 3, 7, 64] {
+            let mut parser = HtmlParser::document();
+            for chunk in SOURCE.as_bytes().chunks(size) {
+                parser.feed(chunk);
+            }
+            assert_eq!(parser.finish(), expected);
+        }
+    }
+
+    #[test]
+    fn fragment_chunks_match_string_parse() {
+        let mut parser = HtmlParser::fragment();
+        parser.write_all(SOURCE.as_bytes()).unwrap();
+        assert_eq!(parser.finish(), Html::parse_fragment(SOURCE));
+    }
+
+    #[test]
+    fn exact_errors_have_columns() {
+        let source = "<p>\n  <b>a &notit; b</i>\n</p>";
+        let html = Html::parse_fragment_exact(source);
+        let positions: Vec<_> = html.errors.iter().map(|e| (e.kind, e.line, e.column)).collect();
+        assert_eq!(
+            positions,
+            vec![
+                (ParseErrorKind::CharacterReference, 2, Some(13)),
+                (ParseErrorKind::MisnestedElement, 2, Some(20)),
+                (ParseErrorKind::MisnestedElement, 3, Some(4)),
+            ]
+        );
+        // Same tree as a normal parse, but with coarser errors.
+        assert_eq!(html.tree, Html::parse_fragment(source).tree);
+        assert!(Html::parse_fragment(source).errors.iter().all(|e| e.column.is_none()));
+    }
+
+    #[test]
+    fn reader_matches_string_parse() {
+        let document = Html::parse_document_from_reader(&mut SOURCE.as_bytes()).unwrap();
+        assert_eq!(document, Html::parse_document(SOURCE));
+        let fragment = Html::parse_fragment_from_reader(&mut SOURCE.as_bytes()).unwrap();
+        assert_eq!(fragment, Html::parse_fragment(SOURCE));
+    }
+}
@@ -21,4 +21,14 @@ mod tests {
         let html = Html::parse_document(src);
         assert_eq!(html.as_html(), src);
     }
+
+    #[test]
+    fn test_serialize_template() {
+        let src = r#"<html><head></head><body><template id="row"><tr><td>a</td></tr></template></body></html>"#;
+        let html = Html::parse_document(src);
+        assert_eq!(html.as_html(), src);
+        let template = html.select(&crate::Selector::new("template")).next().unwrap();
+        assert_eq!(template.inner_html(), "<tr><td>a</td></tr>");
+        assert_eq!(template.html(), r#"<template id="row"><tr><td>a</td></tr></template>"#);
+    }
 }
@@ -0,0 +1,208 @@
+use html5ever::{
+    tendril::StrTendril,
+    tokenizer::{
+        BufferQueue, CharacterTokens, NullCharacterToken, ParseError, StartTag, Tag, TagToken, Token, TokenSink,
+        TokenSinkResult, Tokenizer, TokenizerOpts, TokenizerResult,
+    },
+    tree_builder::{TreeBuilder, TreeSink},
+    LocalName,
+};
+
+use super::{
+    tree_sink::{Handle, HtmlSink},
+    Html,
+};
+use crate::node::SourceSpan;
+
+/// The spans of the token the tree builder is processing, read by the tree sink.
+#[derive(Debug, Default)]
+pub(crate) struct TokenSpans {
+    /// The span of the current token.
+    token: Option<SourceSpan>,
+    /// The name of the current token if it is a start tag.
+    tag: Option<LocalName>,
+    /// The span of the character tokens since the last other token.
+    text: Option<SourceSpan>,
+    /// Whether the current token is a character token.
+    in_text: bool,
+}
+
+impl TokenSpans {
+    /// Returns the span of the start tag which creates an element named `name`.
+    ///
+    /// Elements the tree builder inserts on its own have no span.
+    pub(crate) fn element(&mut self, name: &LocalName) -> Option<SourceSpan> {
+        match self.tag.take() {
+            Some(tag) if tag == *name => self.token,
+            tag => {
+                self.tag = tag;
+                None
+            }
+        }
+    }
+
+    /// Returns the span of the comment being inserted.
+    pub(crate) fn comment(&self) -> Option<SourceSpan> {
+        self.token
+    }
+
+    /// Returns the span of the text being inserted.
+    ///
+    /// Text is sometimes buffered until the next tag, then it spans all characters since the last other token.
+    pub(crate) fn text(&self) -> Option<SourceSpan> {
+        if self.in_text { self.token } else { self.text }
+    }
+}
+
+/// The position after a character of the input.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    offset: usize,
+    line: u64,
+    column: u64,
+}
+
+/// Sits between the tokenizer and the tree builder to attach source spans to tokens.
+struct SpanSink {
+    builder: TreeBuilder<Handle, HtmlSink>,
+    spans: bool,
+    /// Where the last token ended.
+    last: Location,
+    /// Where the character being tokenized ends.
+    next: Location,
+}
+
+impl TokenSink for SpanSink {
+    type Handle = Handle;
+
+    fn process_token(&mut self, token: Token, line_number: u64) -> TokenSinkResult<Handle> {
+        if !self.spans || matches!(token, ParseError(_)) {
+            return self.builder.process_token(token, line_number);
+        }
+        let span =
+            SourceSpan { start: self.last.offset, end: self.next.offset, line: self.last.line, column: self.last.column };
+        self.last = self.next;
+
+        let in_text = matches!(token, CharacterTokens(_) | NullCharacterToken);
+        let spans = &mut self.builder.sink.spans;
+        spans.token = Some(span);
+        spans.tag = match token {
+            TagToken(Tag { kind: StartTag, ref name, .. }) => Some(name.clone()),
+            _ => None,
+        };
+        spans.in_text = in_text;
+        if in_text {
+            spans.text = Some(spans.text.map_or(span, |text| text.to(span)));
+        }
+        let result = self.builder.process_token(token, line_number);
+        if !in_text {
+            self.builder.sink.spans.text = None;
+        }
+        result
+    }
+
+    fn end(&mut self) {
+        self.builder.end()
+    }
+
+    fn adjusted_current_node_present_but_not_in_html_namespace(&self) -> bool {
+        self.builder.adjusted_current_node_present_but_not_in_html_namespace()
+    }
+}
+
+/// Parses the whole input in small chunks, which tells where each token and error is.
+pub(super) fn parse_tracked(
+    builder: TreeBuilder<Handle, HtmlSink>,
+    opts: TokenizerOpts,
+    input: &str,
+    exact_errors: bool,
+    spans: bool,
+) -> Html {
+    let start = Location { offset: 0, line: 1, column: 1 };
+    let mut tokenizer = Tokenizer::new(SpanSink { builder, spans, last: start, next: start }, opts);
+    let mut queue = BufferQueue::new();
+    for (offset, chunk) in chunks(input) {
+        let here = tokenizer.sink.next;
+        tokenizer.sink.next = match chunk {
+            "\n" => Location { offset: offset + 1, line: here.line + 1, column: 1 },
+            _ => Location { offset: offset + chunk.len(), line: here.line, column: here.column + chunk.chars().count() as u64 },
+        };
+        if exact_errors {
+            tokenizer.sink.builder.sink.position.1 = Some(here.column);
+        }
+        queue.push_back(StrTendril::from_slice(chunk));
+        while let TokenizerResult::Script(_) = tokenizer.feed(&mut queue) {}
+    }
+    tokenizer.end();
+    tokenizer.sink.builder.sink.finish()
+}
+
+/// Splits the input into runs of letters and digits and single other characters.
+///
+/// Tokens end and errors start at ASCII punctuation or whitespace, so a chunk is where the tokenizer reports them.
+/// Character references are the exception, a name can go wrong at any character, so they are split into characters.
+fn chunks(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    let breaks = |c: char| c.is_ascii() && !c.is_ascii_alphanumeric();
+    let mut offset = 0;
+    let mut reference = false;
+    std::iter::from_fn(move || {
+        let rest = &input[offset..];
+        let first = rest.chars().next()?;
+        let len = if breaks(first) || reference { first.len_utf8() } else { rest.find(breaks).unwrap_or(rest.len()) };
+        reference = matches!(first, '&' | '#') || (reference && !breaks(first));
+        let start = offset;
+        offset += len;
+        Some((start, &rest[..len]))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{html::HtmlParseOptions, Html, Node, Selector};
+
+    fn source<'s>(node: Node, input: &'s str) -> &'s str {
+        &input[node.source_span().unwrap().range()]
+    }
+
+    #[test]
+    fn element_spans_cover_start_tags() {
+        let input = "<!DOCTYPE html>\n<p class=\"a<b\">one</p>\n  <p>二 <b>three</b></p>";
+        let html = HtmlParseOptions::new().source_spans(true).parse_document(input);
+        let spans: Vec<_> = html.select(&Selector::new("p, b")).map(|n| (source(n, input), n.source_span().unwrap())).collect();
+        assert_eq!(spans[0].0, "<p class=\"a<b\">");
+        assert_eq!((spans[0].1.line, spans[0].1.column), (2, 1));
+        assert_eq!(spans[1].0, "<p>");
+        assert_eq!((spans[1].1.line, spans[1].1.column), (3, 3));
+        assert_eq!(spans[2].0, "<b>");
+        assert_eq!((spans[2].1.line, spans[2].1.column), (3, 8));
+
+        // Elements the parser inserts on its own did not come from the source.
+        assert_eq!(html.select(&Selector::new("html, head, body")).filter_map(|n| n.source_span()).count(), 0);
+    }
+
+    #[test]
+    fn text_and_comment_spans() {
+        let input = "<div>a &amp; b < c<!-- note --></div><table><tr><td>x</td></tr></table>";
+        let html = HtmlParseOptions::new().source_spans(true).parse_fragment(input);
+        let div = html.select(&Selector::new("div")).next().unwrap();
+        let mut children = div.children();
+        let text = children.next().unwrap();
+        assert_eq!(&**text.as_text().unwrap(), "a & b < c");
+        assert_eq!(source(text, input), "a &amp; b < c");
+        assert_eq!(source(children.next().unwrap(), input), "<!-- note -->");
+        let cell = html.select(&Selector::new("td")).next().unwrap();
+        assert_eq!(source(cell, input), "<td>");
+        assert_eq!(source(cell.first_child().unwrap(), input), "x");
+    }
+
+    #[test]
+    fn spans_are_opt_in() {
+        let input = "<p>a</p>";
+        let html = Html::parse_fragment(input);
+        assert!(html.select(&Selector::new("p")).next().unwrap().source_span().is_none());
+        let exact = HtmlParseOptions::new().exact_errors(true).parse_fragment(input);
+        assert_eq!(exact, html);
+        let spans = HtmlParseOptions::new().source_spans(true).context_element("tr").parse_fragment("<td>a</td>");
+        assert_eq!(spans.select(&Selector::new("td")).next().unwrap().source_span().unwrap().range(), 0..4);
+    }
+}
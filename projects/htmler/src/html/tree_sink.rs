@@ -1,42 +1,114 @@
-use super::Html;
+use super::{filter::Filtering, limits::Limiter, span::TokenSpans, Html};
 use crate::{
-    node::{Doctype, NodeData, NodeKind, ProcessingInstruction},
+    error::ParseDiagnostic,
+    node::{Comment, Doctype, NodeData, NodeKind, ProcessingInstruction, Text},
     tendril_util::make as make_tendril,
 };
-use ego_tree::NodeId;
+use ego_tree::{NodeId, NodeRef};
 use html5ever::{
     tendril::StrTendril,
     tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink},
     Attribute, ExpandedName, QualName,
 };
-use std::borrow::Cow;
+use std::{borrow::Cow, mem};
 
-/// Note: does not support the `<template>` element.
-impl TreeSink for Html {
-    type Handle = NodeId;
-    type Output = Self;
+/// Refers to a node while the tree is being built.
+///
+/// Elements which may be dropped depending on where they are inserted are only added to the tree once the tree
+/// builder inserts them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handle {
+    /// A node of the tree.
+    Node(NodeId),
+    /// An element in [`HtmlSink::slots`].
+    Element(usize),
+    /// A node which was dropped and is never needed as an element.
+    Dropped,
+}
+
+/// An element the tree builder has a [`Handle::Element`] for.
+#[derive(Debug)]
+enum Slot {
+    /// Created, but not inserted yet.
+    Created { data: Box<NodeData>, template: bool },
+    /// Added to the tree.
+    Added(NodeId),
+    /// Left out of the tree, along with everything appended to it.
+    Dropped { name: QualName, integration_point: bool },
+}
+
+impl Slot {
+    fn dropped(data: &NodeData) -> Self {
+        Slot::Dropped { name: data.name.clone(), integration_point: is_integration_point(data) }
+    }
+}
+
+/// Builds an [`Html`] tree, along with the state which is only needed while parsing.
+///
+/// Implements the `TreeSink` trait from the `html5ever` crate, which allows HTML to be parsed.
+#[derive(Debug)]
+pub struct HtmlSink {
+    /// The tree being built.
+    pub(super) html: Html,
+    /// The source position the parser is at.
+    pub(super) position: (u64, Option<u64>),
+    /// The source spans of the token being parsed.
+    pub(super) spans: TokenSpans,
+    /// Enforces the limits.
+    pub(super) limiter: Limiter,
+    /// Drops filtered nodes.
+    pub(super) filtering: Filtering,
+    /// Elements the parser has a [`Handle::Element`] for.
+    slots: Vec<Slot>,
+}
 
-    fn finish(self) -> Self {
-        self
+impl HtmlSink {
+    /// Creates a sink which parses into `html`, usually an empty document or fragment.
+    pub fn new(html: Html) -> Self {
+        HtmlSink {
+            html,
+            position: (1, None),
+            spans: TokenSpans::default(),
+            limiter: Limiter::default(),
+            filtering: Filtering::default(),
+            slots: Vec::new(),
+        }
+    }
+}
+
+impl TreeSink for HtmlSink {
+    type Handle = Handle;
+    type Output = Html;
+
+    fn finish(self) -> Html {
+        self.html
     }
 
     // Signal a parse error.
     fn parse_error(&mut self, msg: Cow<'static, str>) {
-        self.errors.push(msg);
+        if self.accepts_error() {
+            let (line, column) = self.position;
+            self.html.errors.push(ParseDiagnostic::new(msg, line, column));
+        }
     }
 
     // Get a handle to the Document node.
     fn get_document(&mut self) -> Self::Handle {
-        self.tree.root().id()
+        Handle::Node(self.html.tree.root().id())
     }
 
     // What is the name of this element?
     //
     // Should never be called on a non-element node; feel free to panic!.
     fn elem_name(&self, target: &Self::Handle) -> ExpandedName {
-        match self.tree.get(*target).unwrap().value() {
-            NodeKind::Element(e) => e.name.expanded(),
-            _ => panic!("not an element"),
+        if let Handle::Element(index) = *target {
+            if let Slot::Dropped { ref name, .. } = self.slots[index] {
+                return name.expanded();
+            }
+        }
+        match self.element(*target) {
+            Some(e) => e.name.expanded(),
+            None => panic!("not an element"),
         }
     }
 
@@ -46,26 +118,38 @@ impl TreeSink for Html {
     // associated document fragment called the "template contents" should also be created. Later
     // calls to self.get_template_contents() with that given element return it.
     fn create_element(&mut self, name: QualName, attrs: Vec<Attribute>, flags: ElementFlags) -> Self::Handle {
-        if flags.template {
-            // todo: support template
+        if self.drops_element(&name) {
+            self.spans.element(&name.local);
+            return self.push_slot(Slot::Dropped { name, integration_point: false });
         }
-        let mut node = self.tree.orphan(NodeKind::Element(NodeData::new(name.clone(), attrs)));
-        if name.expanded() == expanded_name!(html "template") {
-            node.append(NodeKind::Fragment);
+        let attrs = self.limit_attributes(attrs, 0);
+        let mut data = NodeData::new(name, attrs);
+        data.xml = self.html.xml;
+        data.span = self.spans.element(&data.name.local);
+        if self.defers_elements() {
+            return self.push_slot(Slot::Created { data: Box::new(data), template: flags.template });
+        }
+        match self.add_element(data, flags.template) {
+            Slot::Added(id) => Handle::Node(id),
+            slot => self.push_slot(slot),
         }
-        node.id()
     }
 
     // Create a comment node.
     fn create_comment(&mut self, text: StrTendril) -> Self::Handle {
-        self.tree.orphan(NodeKind::Comment(make_tendril(text))).id()
+        if self.drops_comments() {
+            return Handle::Dropped;
+        }
+        let comment = Comment { comment: make_tendril(text), span: self.spans.comment() };
+        self.create_node(NodeKind::Comment(comment)).map_or(Handle::Dropped, Handle::Node)
     }
 
     // Create Processing Instruction.
     fn create_pi(&mut self, target: StrTendril, data: StrTendril) -> Self::Handle {
         let target = make_tendril(target);
         let data = make_tendril(data);
-        self.tree.orphan(NodeKind::ProcessingInstruction(ProcessingInstruction { target, data })).id()
+        self.create_node(NodeKind::ProcessingInstruction(ProcessingInstruction { target, data }))
+            .map_or(Handle::Dropped, Handle::Node)
     }
 
     // Append a node as the last child of the given node. If this would produce adjacent sibling
@@ -73,21 +157,39 @@ impl TreeSink for Html {
     //
     // The child node will not already have a parent.
     fn append(&mut self, parent: &Self::Handle, child: NodeOrText<Self::Handle>) {
-        let mut parent = self.tree.get_mut(*parent).unwrap();
+        let held = self.take_held_text();
+        let parent = self.node_id(*parent);
 
         match child {
-            NodeOrText::AppendNode(id) => {
-                parent.append_id(id);
+            NodeOrText::AppendNode(child) => {
+                if let (Some(id), Some(parent)) = (self.insert(child, parent), parent) {
+                    self.html.tree.get_mut(parent).unwrap().append_id(id);
+                }
             }
 
             NodeOrText::AppendText(text) => {
-                let text = make_tendril(text);
-                let can_concat = parent.last_child().map_or(false, |mut n| n.value().is_text());
+                let parent = match parent {
+                    Some(parent) => parent,
+                    None => return,
+                };
+                let last_text = self.html.tree.get(parent).unwrap().last_child().and_then(text_len);
+                let (text, span) = match last_text {
+                    Some(_) => (text, self.spans.text()),
+                    None => match self.hold_whitespace(held, parent, None, text) {
+                        Some(text) => text,
+                        None => return,
+                    },
+                };
+                let text = match self.limit_text(text, last_text) {
+                    Some(text) => Text { text: make_tendril(text), span },
+                    None => return,
+                };
+                let mut parent = self.html.tree.get_mut(parent).unwrap();
 
-                if can_concat {
+                if last_text.is_some() {
                     let mut last_child = parent.last_child().unwrap();
                     match *last_child.value() {
-                        NodeKind::Text(ref mut t) => t.push_tendril(&text),
+                        NodeKind::Text(ref mut t) => concat_text(t, text),
                         _ => unreachable!(),
                     }
                 }
@@ -104,7 +206,7 @@ impl TreeSink for Html {
         prev_element: &Self::Handle,
         child: NodeOrText<Self::Handle>,
     ) {
-        if self.tree.get(*element).unwrap().parent().is_some() {
+        if self.node_id(*element).is_some_and(|element| self.html.tree.get(element).unwrap().parent().is_some()) {
             self.append_before_sibling(element, child)
         }
         else {
@@ -118,7 +220,9 @@ impl TreeSink for Html {
         let public_id = make_tendril(public_id);
         let system_id = make_tendril(system_id);
         let doctype = Doctype { name, public_id, system_id };
-        self.tree.root_mut().append(NodeKind::Doctype(doctype));
+        if let Some(id) = self.create_node(NodeKind::Doctype(doctype)) {
+            self.html.tree.root_mut().append_id(id);
+        }
     }
 
     // Mark a HTML <script> element as "already started".
@@ -131,7 +235,31 @@ impl TreeSink for Html {
     // The tree builder promises this will never be called with something else than a template
     // element.
     fn get_template_contents(&mut self, target: &Self::Handle) -> Self::Handle {
-        self.tree.get(*target).unwrap().first_child().unwrap().id()
+        let id = match self.node_id(*target) {
+            Some(id) => id,
+            // Whatever is appended to the contents of a template which is not in the tree is dropped too.
+            None => return *target,
+        };
+        match self.html.tree.get(id).unwrap().value() {
+            NodeKind::Element(e) => Handle::Node(e.template_contents.expect("not a template element")),
+            _ => panic!("not an element"),
+        }
+    }
+
+    // Called whenever the line number changes.
+    fn set_current_line(&mut self, line_number: u64) {
+        self.position.0 = line_number;
+    }
+
+    // Returns true if the adjusted current node is an HTML integration point and the token is a
+    // start tag.
+    fn is_mathml_annotation_xml_integration_point(&self, handle: &Self::Handle) -> bool {
+        if let Handle::Element(index) = *handle {
+            if let Slot::Dropped { integration_point, .. } = self.slots[index] {
+                return integration_point;
+            }
+        }
+        self.element(*handle).is_some_and(is_integration_point)
     }
 
     // Do two handles refer to the same node?
@@ -141,7 +269,7 @@ impl TreeSink for Html {
 
     // Set the document's quirks mode.
     fn set_quirks_mode(&mut self, mode: QuirksMode) {
-        self.quirks_mode = mode;
+        self.html.quirks_mode = mode;
     }
 
     // Append a node as the sibling immediately before the given node. If that node has no parent,
@@ -153,32 +281,53 @@ impl TreeSink for Html {
     //
     // NB: new_node may have an old parent, from which it should be removed.
     fn append_before_sibling(&mut self, sibling: &Self::Handle, new_node: NodeOrText<Self::Handle>) {
-        if let NodeOrText::AppendNode(id) = new_node {
-            self.tree.get_mut(id).unwrap().detach();
+        let held = self.take_held_text();
+        if let NodeOrText::AppendNode(handle) = new_node {
+            if let Some(id) = self.node_id(handle) {
+                self.html.tree.get_mut(id).unwrap().detach();
+            }
         }
 
-        let mut sibling = self.tree.get_mut(*sibling).unwrap();
-        if sibling.parent().is_some() {
-            match new_node {
-                NodeOrText::AppendNode(id) => {
-                    sibling.insert_id_before(id);
+        let sibling = match self.node_id(*sibling) {
+            Some(sibling) => sibling,
+            None => return,
+        };
+        let parent = match self.html.tree.get(sibling).unwrap().parent() {
+            Some(parent) => parent.id(),
+            None => return,
+        };
+        match new_node {
+            NodeOrText::AppendNode(handle) => {
+                if let Some(id) = self.insert(handle, Some(parent)) {
+                    self.html.tree.get_mut(sibling).unwrap().insert_id_before(id);
                 }
+            }
 
-                NodeOrText::AppendText(text) => {
-                    let text = make_tendril(text);
-                    let can_concat = sibling.prev_sibling().map_or(false, |mut n| n.value().is_text());
+            NodeOrText::AppendText(text) => {
+                let prev_text = self.html.tree.get(sibling).unwrap().prev_sibling().and_then(text_len);
+                let (text, span) = match prev_text {
+                    Some(_) => (text, self.spans.text()),
+                    None => match self.hold_whitespace(held, parent, Some(sibling), text) {
+                        Some(text) => text,
+                        None => return,
+                    },
+                };
+                let text = match self.limit_text(text, prev_text) {
+                    Some(text) => Text { text: make_tendril(text), span },
+                    None => return,
+                };
+                let mut sibling = self.html.tree.get_mut(sibling).unwrap();
 
-                    if can_concat {
-                        let mut prev_sibling = sibling.prev_sibling().unwrap();
-                        match *prev_sibling.value() {
-                            NodeKind::Text(ref mut t) => t.push_tendril(&text),
-                            _ => unreachable!(),
-                        }
-                    }
-                    else {
-                        sibling.insert_before(NodeKind::Text(text));
+                if prev_text.is_some() {
+                    let mut prev_sibling = sibling.prev_sibling().unwrap();
+                    match *prev_sibling.value() {
+                        NodeKind::Text(ref mut t) => concat_text(t, text),
+                        _ => unreachable!(),
                     }
                 }
+                else {
+                    sibling.insert_before(NodeKind::Text(text));
+                }
             }
         }
     }
@@ -186,24 +335,143 @@ impl TreeSink for Html {
     // Add each attribute to the given element, if no attribute with that name already exists. The
     // tree builder promises this will never be called with something else than an element.
     fn add_attrs_if_missing(&mut self, target: &Self::Handle, attrs: Vec<Attribute>) {
-        let mut node = self.tree.get_mut(*target).unwrap();
-        let element = match *node.value() {
-            NodeKind::Element(ref mut e) => e,
-            _ => unreachable!(),
+        let existing = match self.element(*target) {
+            Some(e) => e.attrs.clone(),
+            None => return,
         };
+        let attrs = attrs.into_iter().filter(|attr| !existing.contains_key(&attr.name)).collect();
+        let attrs = self.limit_attributes(attrs, existing.len());
 
-        for attr in attrs {
-            element.attrs.entry(attr.name).or_insert_with(|| make_tendril(attr.value));
+        let add = |element: &mut NodeData| {
+            for attr in attrs {
+                element.attrs.entry(attr.name).or_insert_with(|| make_tendril(attr.value));
+            }
+        };
+        match (self.node_id(*target), *target) {
+            (Some(id), _) => match *self.html.tree.get_mut(id).unwrap().value() {
+                NodeKind::Element(ref mut e) => add(e),
+                _ => unreachable!(),
+            },
+            (None, Handle::Element(index)) => match self.slots[index] {
+                Slot::Created { ref mut data, .. } => add(data),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
         }
     }
 
     // Detach the given node from its parent.
     fn remove_from_parent(&mut self, target: &Self::Handle) {
-        self.tree.get_mut(*target).unwrap().detach();
+        self.take_held_text();
+        if let Some(id) = self.node_id(*target) {
+            self.html.tree.get_mut(id).unwrap().detach();
+        }
     }
 
     // Remove all the children from node and append them to new_parent.
     fn reparent_children(&mut self, node: &Self::Handle, new_parent: &Self::Handle) {
-        self.tree.get_mut(*new_parent).unwrap().reparent_from_id_append(*node);
+        self.take_held_text();
+        // The tree builder creates `new_parent` for this and appends it to `node` right after.
+        let node = self.node_id(*node);
+        self.insert(*new_parent, node);
+        if let (Some(node), Some(new_parent)) = (node, self.node_id(*new_parent)) {
+            self.html.tree.get_mut(new_parent).unwrap().reparent_from_id_append(node);
+        }
     }
 }
+
+impl HtmlSink {
+    /// Returns true if elements are only added to the tree once it is known where they go.
+    fn defers_elements(&self) -> bool {
+        // The context element of a fragment is created first and never inserted. It is added right away, so that the
+        // tree is the same as without limits or filters.
+        let tree = &self.html.tree;
+        (self.limits_depth() || self.filters_elements())
+            && !(tree.root().value().is_fragment() && tree.nodes().nth(1).is_none())
+    }
+
+    fn push_slot(&mut self, slot: Slot) -> Handle {
+        self.slots.push(slot);
+        Handle::Element(self.slots.len() - 1)
+    }
+
+    /// Returns the node `handle` refers to, if it is in the tree.
+    fn node_id(&self, handle: Handle) -> Option<NodeId> {
+        match handle {
+            Handle::Node(id) => Some(id),
+            Handle::Element(index) => match self.slots[index] {
+                Slot::Added(id) => Some(id),
+                _ => None,
+            },
+            Handle::Dropped => None,
+        }
+    }
+
+    /// Returns the element `handle` refers to, unless it was dropped.
+    fn element(&self, handle: Handle) -> Option<&NodeData> {
+        if let Handle::Element(index) = handle {
+            if let Slot::Created { ref data, .. } = self.slots[index] {
+                return Some(data);
+            }
+        }
+        match self.html.tree.get(self.node_id(handle)?).unwrap().value() {
+            NodeKind::Element(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Adds a detached element to the tree, unless there are too many nodes.
+    fn add_element(&mut self, mut data: NodeData, template: bool) -> Slot {
+        if !self.reserve_node() {
+            return Slot::dropped(&data);
+        }
+        if template {
+            data.template_contents = Some(self.html.tree.orphan(NodeKind::Fragment).id());
+        }
+        Slot::Added(self.html.tree.orphan(NodeKind::Element(data)).id())
+    }
+
+    /// Returns the node to insert into `parent`, adding a created element to the tree if it is kept there.
+    ///
+    /// A `parent` of `None` was dropped, so the element is dropped too.
+    fn insert(&mut self, handle: Handle, parent: Option<NodeId>) -> Option<NodeId> {
+        let index = match handle {
+            Handle::Element(index) if matches!(self.slots[index], Slot::Created { .. }) => index,
+            handle => return self.node_id(handle),
+        };
+        let kept = parent.is_some_and(|parent| self.can_nest(parent));
+        let (data, template) = match mem::replace(&mut self.slots[index], Slot::Added(self.html.tree.root().id())) {
+            Slot::Created { data, template } => (data, template),
+            _ => unreachable!(),
+        };
+        let slot = if kept { self.add_element(*data, template) } else { Slot::dropped(&data) };
+        self.slots[index] = slot;
+        self.node_id(handle)
+    }
+}
+
+/// Appends `text` to an adjacent text node, widening its span.
+pub(crate) fn concat_text(target: &mut Text, text: Text) {
+    target.text.push_str(&text.text);
+    target.span = match (target.span, text.span) {
+        (Some(span), Some(next)) => Some(span.to(next)),
+        (span, next) => span.or(next),
+    };
+}
+
+/// Returns the length of a text node.
+fn text_len(node: NodeRef<NodeKind>) -> Option<usize> {
+    match node.value() {
+        NodeKind::Text(t) => Some(t.len()),
+        _ => None,
+    }
+}
+
+/// Returns true if the element is a MathML `annotation-xml` which contains HTML.
+fn is_integration_point(element: &NodeData) -> bool {
+    element.name.expanded() == expanded_name!(mathml "annotation-xml")
+        && element.attrs.iter().any(|(name, value)| {
+            name.local == local_name!("encoding")
+                && (value.eq_ignore_ascii_case("text/html") || value.eq_ignore_ascii_case("application/xhtml+xml"))
+        })
+}
@@ -0,0 +1,89 @@
+use tendril::TendrilSink;
+use xml5ever::{
+    driver::{self, XmlParseOpts},
+    serialize::{self, SerializeOpts, TraversalScope},
+};
+
+use super::{Html, HtmlSink};
+
+impl Html {
+    /// Parses a string of XML as a document.
+    ///
+    /// Names keep their case and namespace, and processing instructions are kept in the tree.
+    /// Selectors match names case-sensitively, as they do in XML documents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use htmler::{Html, Selector};
+    ///
+    /// let xml = Html::parse_xml(
+    ///     r#"<feed xmlns="http://www.w3.org/2005/Atom"><entry><title>A</title></entry></feed>"#,
+    /// );
+    /// assert_eq!(xml.select(&Selector::new("entry > title")).count(), 1);
+    /// assert_eq!(xml.select(&Selector::new("ENTRY")).count(), 0);
+    /// ```
+    pub fn parse_xml(document: &str) -> Self {
+        let sink = HtmlSink::new(Html { xml: true, ..Html::new_document() });
+        driver::parse_document(sink, XmlParseOpts::default()).one(document)
+    }
+
+    /// Returns true if the tree was parsed from XML.
+    pub fn is_xml(&self) -> bool {
+        self.xml
+    }
+
+    /// Serialize entire document into XML.
+    pub fn as_xml(&self) -> String {
+        let opts = SerializeOpts { traversal_scope: TraversalScope::ChildrenOnly(None) };
+        let mut buf = Vec::new();
+        serialize::serialize(&mut buf, self, opts).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Html, NodeKind, Selector};
+
+    const FEED: &str = r#"<?xml-stylesheet href="feed.xsl"?><feed xmlns="http://www.w3.org/2005/Atom" xmlns:media="http://search.yahoo.com/mrss/"><entry><media:thumbnail url="a.png"/><linkTitle>A</linkTitle></entry></feed>"#;
+
+    #[test]
+    fn processing_instructions_and_namespaces() {
+        let xml = Html::parse_xml(FEED);
+        assert!(xml.is_xml());
+        assert!(!Html::parse_document(FEED).is_xml());
+        match xml.tree.root().first_child().unwrap().value() {
+            NodeKind::ProcessingInstruction(pi) => {
+                assert_eq!(&*pi.target, "xml-stylesheet");
+                assert_eq!(&*pi.data, r#"href="feed.xsl""#);
+            }
+            other => panic!("expected a processing instruction, got {:?}", other),
+        }
+        let root = xml.root_node();
+        assert_eq!(&*root.as_data().unwrap().name.ns, "http://www.w3.org/2005/Atom");
+        let thumbnail = xml.select(&Selector::new("entry > *")).next().unwrap();
+        assert_eq!(&*thumbnail.as_data().unwrap().name.ns, "http://search.yahoo.com/mrss/");
+        assert_eq!(thumbnail.as_data().unwrap().name(), "thumbnail");
+    }
+
+    #[test]
+    fn names_are_case_sensitive() {
+        let xml = Html::parse_xml(FEED);
+        assert_eq!(xml.select(&Selector::new("linkTitle")).count(), 1);
+        assert_eq!(xml.select(&Selector::new("linktitle")).count(), 0);
+        assert!(xml.select(&Selector::new("linkTitle")).next().unwrap().as_data().unwrap().is_a("linkTitle"));
+        assert!(!xml.select(&Selector::new("linkTitle")).next().unwrap().as_data().unwrap().is_a("linktitle"));
+
+        // XHTML elements in an XML document are not HTML elements in an HTML document.
+        let xhtml = Html::parse_xml(r#"<html xmlns="http://www.w3.org/1999/xhtml"><body><DIV/><div/></body></html>"#);
+        assert_eq!(xhtml.select(&Selector::new("div")).count(), 1);
+        assert_eq!(Html::parse_document("<DIV></DIV><div></div>").select(&Selector::new("div")).count(), 2);
+    }
+
+    #[test]
+    fn serialize_xml() {
+        let source = r#"<?pi data?><root><a b="c">text</a><empty></empty></root>"#;
+        assert_eq!(Html::parse_xml(source).as_xml(), source);
+    }
+}
@@ -1,4 +1,3 @@
-#![feature(once_cell)]
 #![doc = include_str!("../readme.md")]
 #![warn(
     missing_docs,
@@ -15,24 +14,32 @@
 #[macro_use]
 extern crate html5ever;
 
-pub use crate::{html::Html, node::NodeKind, node_ref::Node, selector::Selector};
+pub use crate::{diff::diff, html::Html, node::NodeKind, node_mut::NodeMut, node_ref::Node, selector::Selector};
 
+pub use encoding_rs::Encoding;
 pub use selectors::attr::CaseSensitivity;
 
+pub mod builder;
+pub mod diff;
 pub mod error;
 pub mod html;
 pub mod node;
+pub mod node_mut;
 pub mod node_ref;
 pub mod selector;
+pub mod visit;
+pub mod xpath;
 
 pub(crate) mod tendril_util {
     use html5ever::tendril;
-    /// Atomic equivalent to the default `StrTendril` type.
-    pub type HtmlStr = tendril::Tendril<tendril::fmt::UTF8, tendril::Atomic>;
+    /// Owned string type of text and attribute values.
+    ///
+    /// Tendrils are never `Sync`, so the tree stores plain strings to be shareable across threads.
+    pub type HtmlStr = String;
 
-    /// Convert a standard tendril into an atomic one.
+    /// Convert a parser tendril into an owned string.
     pub fn make(s: tendril::StrTendril) -> HtmlStr {
-        s.into_send().into()
+        String::from(&*s)
     }
 }
 
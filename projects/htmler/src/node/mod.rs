@@ -1,11 +1,16 @@
 //! HTML nodes.
 
 use fmt::Debug;
-use std::cell::OnceCell;
+use std::sync::OnceLock;
 
-use std::{fmt, ops::Deref, slice::Iter as SliceIter};
+use std::{
+    fmt,
+    ops::{Deref, Range},
+    slice::Iter as SliceIter,
+};
 
 use crate::{CaseSensitivity, HtmlStr};
+use ego_tree::NodeId;
 use html5ever::{Attribute, LocalName, QualName};
 use indexmap::IndexMap;
 
@@ -22,9 +27,9 @@ pub enum NodeKind {
     /// A doctype.
     Doctype(Doctype),
     /// A comment.
-    Comment(HtmlStr),
+    Comment(Comment),
     /// Text.
-    Text(HtmlStr),
+    Text(Text),
     /// An element.
     Element(NodeData),
     /// A processing instruction.
@@ -32,6 +37,21 @@ pub enum NodeKind {
 }
 
 impl NodeKind {
+    /// Creates an HTML element without attributes.
+    pub fn element(name: &str) -> Self {
+        NodeKind::Element(NodeData::new(QualName::new(None, ns!(html), LocalName::from(name)), Vec::new()))
+    }
+
+    /// Creates a text node.
+    pub fn text(text: &str) -> Self {
+        NodeKind::Text(HtmlStr::from(text).into())
+    }
+
+    /// Creates a comment.
+    pub fn comment(comment: &str) -> Self {
+        NodeKind::Comment(HtmlStr::from(comment).into())
+    }
+
     /// Returns true if node is the document root.
     pub fn is_document(&self) -> bool {
         matches!(*self, NodeKind::Document)
@@ -61,6 +81,11 @@ impl NodeKind {
     pub fn is_element(&self) -> bool {
         matches!(*self, NodeKind::Element(_))
     }
+
+    /// Returns true if node is a processing instruction.
+    pub fn is_processing_instruction(&self) -> bool {
+        matches!(*self, NodeKind::ProcessingInstruction(_))
+    }
 }
 
 // Always use one line.
@@ -70,8 +95,8 @@ impl Debug for NodeKind {
             NodeKind::Document => write!(f, "Document"),
             NodeKind::Fragment => write!(f, "Fragment"),
             NodeKind::Doctype(d) => write!(f, "Doctype({:?})", d),
-            NodeKind::Comment(c) => write!(f, "Comment({:?})", c),
-            NodeKind::Text(t) => write!(f, "Text({:?})", t),
+            NodeKind::Comment(c) => write!(f, "Comment({:?})", c.comment),
+            NodeKind::Text(t) => write!(f, "Text({:?})", t.text),
             NodeKind::Element(e) => write!(f, "Element({:?})", e),
             NodeKind::ProcessingInstruction(pi) => write!(f, "ProcessingInstruction({:?})", pi),
         }
@@ -113,19 +138,24 @@ impl Debug for Doctype {
 }
 
 /// An HTML element.
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone)]
 pub struct NodeData {
     pub(crate) name: QualName,
     pub(crate) attrs: IndexMap<QualName, HtmlStr>,
-    id: OnceCell<Option<HtmlStr>>,
-    classes: OnceCell<Vec<LocalName>>,
+    pub(crate) template_contents: Option<NodeId>,
+    /// Whether the element belongs to an XML document, where names are case-sensitive.
+    pub(crate) xml: bool,
+    /// Where the start tag is in the source, if it was recorded.
+    pub(crate) span: Option<SourceSpan>,
+    id: OnceLock<Option<HtmlStr>>,
+    classes: OnceLock<Vec<LocalName>>,
 }
 
 impl NodeData {
     #[doc(hidden)]
     pub fn new(name: QualName, attributes: Vec<Attribute>) -> Self {
         let attrs = attributes.into_iter().map(|a| (a.name, crate::tendril_util::make(a.value))).collect();
-        NodeData { attrs, name, id: OnceCell::new(), classes: OnceCell::new() }
+        NodeData { attrs, name, template_contents: None, xml: false, span: None, id: OnceLock::new(), classes: OnceLock::new() }
     }
 
     /// Chick if element is the given tag
@@ -137,7 +167,7 @@ impl NodeData {
     /// # use htmler::node::NodeData;
     /// ```
     pub fn is_a(&self, tag: &str) -> bool {
-        self.name.local.as_ref().eq_ignore_ascii_case(tag)
+        if self.xml { self.name.local.as_ref() == tag } else { self.name.local.as_ref().eq_ignore_ascii_case(tag) }
     }
 
     /// Returns the element name.
@@ -192,8 +222,110 @@ impl NodeData {
     pub fn attributes(&self) -> HtmlAttributes {
         HtmlAttributes { inner: self.attrs.iter() }
     }
+
+    /// Returns the ID of the fragment holding the contents of a `<template>` element.
+    ///
+    /// The fragment is not a child of the element, so it is skipped by tree traversal.
+    pub fn template_contents(&self) -> Option<NodeId> {
+        self.template_contents
+    }
+
+    /// Sets the value of an attribute, adding it if it is missing.
+    ///
+    /// Names of HTML elements are lowercased, as by the DOM's `setAttribute`.
+    pub fn set_attribute(&mut self, attr: &str, value: &str) {
+        let qualname = QualName::new(None, ns!(), self.adjust_case(attr));
+        self.invalidate(&qualname);
+        self.attrs.insert(qualname, value.into());
+    }
+
+    /// Replaces all attributes.
+    pub(crate) fn replace_attributes(&mut self, attrs: IndexMap<QualName, HtmlStr>) {
+        self.attrs = attrs;
+        self.id = OnceLock::new();
+        self.classes = OnceLock::new();
+    }
+
+    /// Removes an attribute, returning its value.
+    pub fn remove_attribute(&mut self, attr: &str) -> Option<HtmlStr> {
+        let qualname = QualName::new(None, ns!(), self.adjust_case(attr));
+        self.invalidate(&qualname);
+        self.attrs.shift_remove(&qualname)
+    }
+
+    /// Adds a class if the element does not have it yet, returns false if it did.
+    pub fn add_class(&mut self, class: &str) -> bool {
+        let mut classes = self.class_list();
+        if classes.contains(&class) {
+            return false;
+        }
+        classes.push(class);
+        let value = classes.join(" ");
+        self.set_attribute("class", &value);
+        true
+    }
+
+    /// Removes a class, returns false if the element did not have it.
+    pub fn remove_class(&mut self, class: &str) -> bool {
+        let classes = self.class_list();
+        if !classes.contains(&class) {
+            return false;
+        }
+        let value = classes.into_iter().filter(|c| *c != class).collect::<Vec<_>>().join(" ");
+        self.set_attribute("class", &value);
+        true
+    }
+
+    /// Adds the class if it is missing or removes it otherwise, returns true if the element has it afterwards.
+    pub fn toggle_class(&mut self, class: &str) -> bool {
+        !self.remove_class(class) && self.add_class(class)
+    }
+
+    /// Changes the element name, keeping its namespace.
+    ///
+    /// Names of HTML elements are lowercased.
+    pub fn rename(&mut self, name: &str) {
+        self.name.local = self.adjust_case(name);
+    }
+
+    /// Returns the classes in the order they are written, as the DOM's `classList` does.
+    fn class_list(&self) -> Vec<&str> {
+        let mut classes = Vec::new();
+        for class in self.get_attribute("class").unwrap_or("").split_ascii_whitespace() {
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+        }
+        classes
+    }
+
+    fn adjust_case(&self, name: &str) -> LocalName {
+        if self.xml || self.name.ns != ns!(html) { LocalName::from(name) } else { LocalName::from(name.to_ascii_lowercase()) }
+    }
+
+    /// Clears the cached ID or classes if `attr` feeds them.
+    fn invalidate(&mut self, attr: &QualName) {
+        match attr.local.as_ref() {
+            "id" => self.id = OnceLock::new(),
+            "class" => self.classes = OnceLock::new(),
+            _ => {}
+        }
+    }
+}
+
+// The cached ID and classes are derived from the attributes.
+impl PartialEq for NodeData {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.attrs == other.attrs
+            && self.template_contents == other.template_contents
+            && self.xml == other.xml
+            && self.span == other.span
+    }
 }
 
+impl Eq for NodeData {}
+
 /// Iterator over classes.
 #[allow(missing_debug_implementations)]
 #[derive(Clone)]
@@ -237,6 +369,89 @@ impl Debug for NodeData {
     }
 }
 
+/// A text node.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Text {
+    /// The text.
+    pub text: HtmlStr,
+    pub(crate) span: Option<SourceSpan>,
+}
+
+impl From<HtmlStr> for Text {
+    fn from(text: HtmlStr) -> Self {
+        Text { text, span: None }
+    }
+}
+
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.text.deref()
+    }
+}
+
+impl Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{:?}", self.deref())
+    }
+}
+
+/// A comment node.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// The comment text.
+    pub comment: HtmlStr,
+    pub(crate) span: Option<SourceSpan>,
+}
+
+impl From<HtmlStr> for Comment {
+    fn from(comment: HtmlStr) -> Self {
+        Comment { comment, span: None }
+    }
+}
+
+impl Deref for Comment {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.comment.deref()
+    }
+}
+
+impl Debug for Comment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{:?}", self.deref())
+    }
+}
+
+/// Where a node was found in the parsed source.
+///
+/// For an element, this is its start tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceSpan {
+    /// The byte offset the node starts at.
+    pub start: usize,
+    /// The byte offset just past the end of the node.
+    pub end: usize,
+    /// The 1-based line the node starts on.
+    pub line: u64,
+    /// The 1-based column the node starts at, counted in characters.
+    pub column: u64,
+}
+
+impl SourceSpan {
+    /// Returns the byte range of the node.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns a span from the start of `self` to the end of `other`.
+    pub(crate) fn to(self, other: SourceSpan) -> Self {
+        SourceSpan { end: other.end.max(self.end), ..self }
+    }
+}
+
 /// HTML Processing Instruction.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ProcessingInstruction {
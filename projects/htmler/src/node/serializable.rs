@@ -6,6 +6,8 @@ use html5ever::serialize::{Serializer, TraversalScope};
 use crate::NodeKind;
 
 /// Serialize an HTML node using html5ever serializer.
+///
+/// The contents of a `<template>` element are written as its children, as browsers do.
 pub(crate) fn serialize<S: Serializer>(
     self_node: NodeRef<NodeKind>,
     serializer: &mut S,
@@ -15,6 +17,7 @@ pub(crate) fn serialize<S: Serializer>(
         match edge {
             Edge::Open(node) => {
                 if node == self_node && traversal_scope == TraversalScope::ChildrenOnly(None) {
+                    serialize_template_contents(node, serializer)?;
                     continue;
                 }
 
@@ -23,14 +26,18 @@ pub(crate) fn serialize<S: Serializer>(
                         serializer.write_doctype(doctype.name())?;
                     }
                     NodeKind::Comment(ref comment) => {
-                        serializer.write_comment(comment)?;
+                        serializer.write_comment(&comment.comment)?;
                     }
                     NodeKind::Text(ref text) => {
-                        serializer.write_text(text)?;
+                        serializer.write_text(&text.text)?;
+                    }
+                    NodeKind::ProcessingInstruction(ref pi) => {
+                        serializer.write_processing_instruction(&pi.target, &pi.data)?;
                     }
                     NodeKind::Element(ref elem) => {
                         let attrs = elem.attrs.iter().map(|(k, v)| (k, &v[..]));
                         serializer.start_elem(elem.name.clone(), attrs)?;
+                        serialize_template_contents(node, serializer)?;
                     }
                     _ => (),
                 }
@@ -50,3 +57,13 @@ pub(crate) fn serialize<S: Serializer>(
 
     Ok(())
 }
+
+fn serialize_template_contents<S: Serializer>(node: NodeRef<NodeKind>, serializer: &mut S) -> Result<(), Error> {
+    match node.value() {
+        NodeKind::Element(e) => match e.template_contents.and_then(|id| node.tree().get(id)) {
+            Some(contents) => serialize(contents, serializer, TraversalScope::ChildrenOnly(None)),
+            None => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,193 @@
+//! Mutable element references.
+
+use crate::{html::HtmlIndex, node::NodeData, Html, HtmlStr, NodeKind};
+use ego_tree::NodeId;
+use std::fmt::{Debug, Formatter};
+
+/// A mutable reference to an element, obtained from [`Html::get_mut`].
+///
+/// The cached ID and classes of the element, and the index of the document if it has one, are refreshed by every
+/// change to its attributes.
+///
+/// # Examples
+///
+/// ```
+/// # use htmler::{Html, Selector};
+/// let mut html = Html::parse_fragment(r#"<p class="a">text</p>"#);
+/// let id = html.select(&Selector::new("p")).next().unwrap().id();
+/// let mut p = html.get_mut(id).unwrap();
+/// p.set_attribute("id", "intro");
+/// p.toggle_class("a");
+/// p.add_class("b");
+/// p.rename("div");
+/// assert_eq!(html.root_node().inner_html(), r#"<div class="b" id="intro">text</div>"#);
+/// ```
+pub struct NodeMut<'a> {
+    pub(crate) ptr: ego_tree::NodeMut<'a, NodeKind>,
+    pub(crate) index: Option<&'a mut HtmlIndex>,
+}
+
+impl<'a> Debug for NodeMut<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("NodeMut").field(&self.id()).finish()
+    }
+}
+
+impl<'a> NodeMut<'a> {
+    /// Wraps a `NodeMut` only if it references a `Node::Element`.
+    pub fn wrap(mut node: ego_tree::NodeMut<'a, NodeKind>) -> Option<Self> {
+        if node.value().is_element() { Some(NodeMut { ptr: node, index: None }) } else { None }
+    }
+
+    /// Returns the ID of the element in its tree.
+    pub fn id(&self) -> NodeId {
+        self.ptr.id()
+    }
+
+    /// Returns the element data for changing it.
+    ///
+    /// The index of the document cannot follow changes made through it, so lookups scan the tree until
+    /// [`Html::build_index`] is called again.
+    pub fn as_data_mut(&mut self) -> &mut NodeData {
+        if let Some(index) = self.index.as_deref_mut() {
+            index.invalidate();
+        }
+        self.data_mut()
+    }
+
+    fn data_mut(&mut self) -> &mut NodeData {
+        match self.ptr.value() {
+            NodeKind::Element(e) => e,
+            _ => unreachable!("NodeMut always references an element"),
+        }
+    }
+
+    /// Sets the value of an attribute, adding it if it is missing.
+    ///
+    /// Names of HTML elements are lowercased, as by the DOM's `setAttribute`.
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        self.data_mut().set_attribute(name, value);
+        self.refresh_index();
+    }
+
+    /// Removes an attribute, returning its value.
+    pub fn remove_attribute(&mut self, name: &str) -> Option<HtmlStr> {
+        let removed = self.data_mut().remove_attribute(name);
+        self.refresh_index();
+        removed
+    }
+
+    /// Adds a class if the element does not have it yet, returns false if it did.
+    pub fn add_class(&mut self, class: &str) -> bool {
+        let added = self.data_mut().add_class(class);
+        self.refresh_index();
+        added
+    }
+
+    /// Removes a class, returns false if the element did not have it.
+    pub fn remove_class(&mut self, class: &str) -> bool {
+        let removed = self.data_mut().remove_class(class);
+        self.refresh_index();
+        removed
+    }
+
+    /// Adds the class if it is missing or removes it otherwise, returns true if the element has it afterwards.
+    pub fn toggle_class(&mut self, class: &str) -> bool {
+        let has = self.data_mut().toggle_class(class);
+        self.refresh_index();
+        has
+    }
+
+    /// Changes the element name, keeping its namespace.
+    pub fn rename(&mut self, name: &str) {
+        self.data_mut().rename(name);
+        self.refresh_index();
+    }
+
+    fn refresh_index(&mut self) {
+        let id = self.id();
+        if let (Some(index), NodeKind::Element(data)) = (self.index.as_deref_mut(), self.ptr.value()) {
+            index.refresh(id, data);
+        }
+    }
+}
+
+impl Html {
+    /// Returns a mutable reference to the element with the given ID, or `None` if it is not an element.
+    pub fn get_mut(&mut self, id: NodeId) -> Option<NodeMut<'_>> {
+        let mut node = NodeMut::wrap(self.tree.get_mut(id)?)?;
+        node.index = self.index.0.as_mut();
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Html, Selector};
+
+    fn first_id(html: &Html, selector: &str) -> ego_tree::NodeId {
+        html.select(&Selector::new(selector)).next().unwrap().id()
+    }
+
+    #[test]
+    fn attributes() {
+        let mut html = Html::parse_fragment(r#"<p id="a" title="t">x</p>"#);
+        let id = first_id(&html, "p");
+        assert_eq!(html.root_node().first_child().unwrap().as_data().unwrap().id(), Some("a"));
+
+        let mut p = html.get_mut(id).unwrap();
+        p.set_attribute("ID", "b");
+        assert_eq!(p.as_data_mut().id(), Some("b"));
+        assert_eq!(p.remove_attribute("title").as_deref(), Some("t"));
+        assert_eq!(p.remove_attribute("title"), None);
+        assert_eq!(html.select(&Selector::new("#b")).count(), 1);
+        assert_eq!(html.select(&Selector::new("#a")).count(), 0);
+
+        html.get_mut(id).unwrap().remove_attribute("id");
+        assert_eq!(html.root_node().inner_html(), "<p>x</p>");
+        let text = html.root_node().first_child().unwrap().first_child().unwrap().id();
+        assert!(html.get_mut(text).is_none());
+    }
+
+    #[test]
+    fn classes() {
+        let mut html = Html::parse_fragment(r#"<p class="a b a">x</p>"#);
+        let id = first_id(&html, "p");
+        assert!(html.root_node().first_child().unwrap().has_class("a"));
+
+        let mut p = html.get_mut(id).unwrap();
+        assert!(!p.add_class("b"));
+        assert!(p.add_class("c"));
+        assert!(p.remove_class("a"));
+        assert!(!p.remove_class("a"));
+        assert!(!p.toggle_class("b"));
+        assert!(p.toggle_class("d"));
+        assert_eq!(p.as_data_mut().classes().collect::<Vec<_>>(), ["c", "d"]);
+        assert_eq!(html.select(&Selector::new(".a, .b")).count(), 0);
+        assert_eq!(html.select(&Selector::new(".c.d")).count(), 1);
+        assert_eq!(html.root_node().inner_html(), r#"<p class="c d">x</p>"#);
+    }
+
+    #[test]
+    fn rename() {
+        let mut html = Html::parse_fragment("<p>x</p><svg><path/></svg>");
+        let p = first_id(&html, "p");
+        html.get_mut(p).unwrap().rename("SECTION");
+        let path = first_id(&html, "path");
+        html.get_mut(path).unwrap().rename("linearGradient");
+        assert_eq!(html.root_node().inner_html(), "<section>x</section><svg><linearGradient></linearGradient></svg>");
+    }
+
+    #[test]
+    fn mutation_keeps_equality() {
+        let mut html = Html::parse_fragment(r#"<p class="a">x</p>"#);
+        let same = html.clone();
+        assert!(html.root_node().first_child().unwrap().has_class("a"));
+        assert_eq!(html, same);
+        let id = first_id(&html, "p");
+        html.get_mut(id).unwrap().toggle_class("a");
+        assert_ne!(html, same);
+        html.get_mut(id).unwrap().set_attribute("class", "a");
+        assert_eq!(html, same);
+    }
+}
@@ -16,7 +16,7 @@ impl<'a> selectors::Element for Node<'a> {
     }
 
     fn parent_element(&self) -> Option<Self> {
-        self.ptr.parent().and_then(Node::wrap)
+        self.ptr.parent().and_then(self.element_wrapper())
     }
 
     fn parent_node_is_shadow_root(&self) -> bool {
@@ -32,16 +32,15 @@ impl<'a> selectors::Element for Node<'a> {
     }
 
     fn prev_sibling_element(&self) -> Option<Self> {
-        self.ptr.prev_siblings().find(|sibling| sibling.value().is_element()).map(Node::new)
+        self.ptr.prev_siblings().find_map(self.element_wrapper())
     }
 
     fn next_sibling_element(&self) -> Option<Self> {
-        self.ptr.next_siblings().find(|sibling| sibling.value().is_element()).map(Node::new)
+        self.ptr.next_siblings().find_map(self.element_wrapper())
     }
 
     fn is_html_element_in_html_document(&self) -> bool {
-        // FIXME: Is there more to this?
-        self.as_data().unwrap().name.ns == ns!(html)
+        self.as_data().is_some_and(|data| data.name.ns == ns!(html) && !data.xml)
     }
 
     fn has_local_name(&self, name: &CssLocalName) -> bool {
@@ -59,7 +58,7 @@ impl<'a> selectors::Element for Node<'a> {
     }
 
     fn is_same_type(&self, other: &Self) -> bool {
-        self.as_data().unwrap().name == other.as_data().unwrap().name
+        self.as_data().zip(other.as_data()).is_some_and(|(data, other)| data.name == other.name)
     }
 
     fn attr_matches(
@@ -68,10 +67,12 @@ impl<'a> selectors::Element for Node<'a> {
         local_name: &CssLocalName,
         operation: &AttrSelectorOperation<&CssString>,
     ) -> bool {
-        self.as_data().unwrap().attrs.iter().any(|(key, value)| {
-            !matches!(*ns, NamespaceConstraint::Specific(url) if *url != key.ns)
-                && local_name.0 == key.local
-                && operation.eval_str(value)
+        self.as_data().is_some_and(|data| {
+            data.attrs.iter().any(|(key, value)| {
+                !matches!(*ns, NamespaceConstraint::Specific(url) if *url != key.ns)
+                    && local_name.0 == key.local
+                    && operation.eval_str(value)
+            })
         })
     }
 
@@ -97,14 +98,11 @@ impl<'a> selectors::Element for Node<'a> {
     }
 
     fn has_id(&self, id: &CssLocalName, case_sensitivity: CaseSensitivity) -> bool {
-        match self.as_data().unwrap().id() {
-            Some(val) => case_sensitivity.eq(id.0.as_bytes(), val.as_bytes()),
-            None => false,
-        }
+        self.as_data().and_then(|data| data.id()).is_some_and(|val| case_sensitivity.eq(id.0.as_bytes(), val.as_bytes()))
     }
 
     fn has_class(&self, name: &CssLocalName, case_sensitivity: CaseSensitivity) -> bool {
-        self.as_data().unwrap().classes().any(|c| case_sensitivity.eq(c.as_bytes(), name.0.as_bytes()))
+        self.as_data().is_some_and(|data| data.classes().any(|c| case_sensitivity.eq(c.as_bytes(), name.0.as_bytes())))
     }
 
     fn imported_part(&self, _: &CssLocalName) -> Option<CssLocalName> {
@@ -183,4 +181,18 @@ mod tests {
             <Node as Element>::has_class(&element, &CssLocalName::from("my_class"), CaseSensitivity::CaseSensitive)
         );
     }
+
+    #[test]
+    fn test_text_node() {
+        let fragment = Html::parse_fragment("<p id=a class=b title=c>hey there</p>");
+        let p = fragment.select_first("p").unwrap().unwrap();
+        let text = p.first_child().unwrap();
+        assert!(!text.has_id(&CssLocalName::from("a"), CaseSensitivity::CaseSensitive));
+        assert!(!<Node as Element>::has_class(&text, &CssLocalName::from("b"), CaseSensitivity::CaseSensitive));
+        assert!(!text.is_same_type(&p));
+        assert!(!p.is_same_type(&text));
+        for selector in ["#a", ".b", "[title]", "p"] {
+            assert!(!Selector::new(selector).matches(&text), "{}", selector);
+        }
+    }
 }
@@ -1,25 +1,40 @@
 //! Element references.
 
 use crate::{
-    node::{Doctype, NodeData, ProcessingInstruction},
-    HtmlStr, NodeKind, Selector,
+    error::SelectorErrorKind,
+    node::{Doctype, NodeData, ProcessingInstruction, SourceSpan},
+    selector::ToSelector,
+    Html, HtmlStr, NodeKind, Selector,
 };
 use ego_tree::{
     iter::{Edge, Traverse},
-    NodeRef,
+    NodeId, NodeRef,
+};
+use html5ever::{
+    serialize::{serialize, SerializeOpts, TraversalScope},
+    tree_builder::QuirksMode,
 };
-use html5ever::serialize::{serialize, SerializeOpts, TraversalScope};
 use std::fmt::{Debug, Formatter};
 
 /// A reference to the actual element node, most interfaces are based on this type.
 ///
 /// This wrapper implements the `Element` trait from the `selectors` crate, which allows it to be
 /// matched against CSS selectors.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy)]
 pub struct Node<'a> {
     pub(crate) ptr: NodeRef<'a, NodeKind>,
+    /// The quirks mode of the document the node was reached from, which decides how selectors match.
+    pub(crate) quirks_mode: QuirksMode,
+}
+
+impl<'a> PartialEq for Node<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr
+    }
 }
 
+impl<'a> Eq for Node<'a> {}
+
 impl<'a> Debug for Node<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("ElementHandler").field(&self.ptr.id()).finish()
@@ -27,21 +42,96 @@ impl<'a> Debug for Node<'a> {
 }
 
 impl<'a> Node<'a> {
-    fn new(node: NodeRef<'a, NodeKind>) -> Self {
-        Node { ptr: node }
-    }
-
     /// Wraps a `NodeRef` only if it references a `Node::Element`.
+    ///
+    /// The element matches selectors as in a document without quirks. Elements reached from an [`Html`] match in its
+    /// quirks mode.
     pub fn wrap(node: NodeRef<'a, NodeKind>) -> Option<Self> {
-        if node.value().is_element() { Some(Node::new(node)) } else { None }
+        Self::wrap_in(node, QuirksMode::NoQuirks)
+    }
+
+    /// Wraps a `NodeRef` of a document in a quirks mode, only if it references an element.
+    pub(crate) fn wrap_in(node: NodeRef<'a, NodeKind>, quirks_mode: QuirksMode) -> Option<Self> {
+        if node.value().is_element() { Some(Node { ptr: node, quirks_mode }) } else { None }
+    }
+
+    /// Wraps another node of the same document.
+    pub(crate) fn related(&self, node: NodeRef<'a, NodeKind>) -> Node<'a> {
+        Node { ptr: node, quirks_mode: self.quirks_mode }
+    }
+
+    /// Returns a function wrapping nodes of the same document, see [`Node::related`].
+    fn wrapper(&self) -> impl Fn(NodeRef<'a, NodeKind>) -> Node<'a> + Copy {
+        let quirks_mode = self.quirks_mode;
+        move |ptr| Node { ptr, quirks_mode }
+    }
+
+    /// Returns a function wrapping elements of the same document, see [`Node::wrap`].
+    pub(crate) fn element_wrapper(&self) -> impl Fn(NodeRef<'a, NodeKind>) -> Option<Node<'a>> + Copy {
+        let quirks_mode = self.quirks_mode;
+        move |node| Node::wrap_in(node, quirks_mode)
+    }
+
+    /// Returns the ID of this node in its tree.
+    pub fn id(&self) -> NodeId {
+        self.ptr.id()
     }
 
     /// Returns an iterator over descendent elements matching a selector.
+    ///
+    /// Classes and IDs match case-insensitively if the document is in quirks mode.
+    /// The contents of `<template>` elements are skipped, see [`Select::include_templates`].
     pub fn select<'b>(&self, selector: &'b Selector) -> Select<'a, 'b> {
         let mut inner = self.ptr.traverse();
         inner.next(); // Skip Edge::Open(self).
 
-        Select { scope: *self, inner, selector }
+        Select { scope: *self, inner, selector, include_templates: false, suspended: Vec::new() }
+    }
+
+    /// Returns the first descendent element matching a selector, given as a [`Selector`] or a string to parse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use htmler::Html;
+    /// let html = Html::parse_fragment("<div><p>1</p><p>2</p></div>");
+    /// let div = html.select_first("div").unwrap().unwrap();
+    /// assert_eq!(div.select_first("p").unwrap().unwrap().inner_html(), "1");
+    /// assert!(div.select_first("p[").is_err());
+    /// ```
+    pub fn select_first<'s, S>(&self, selector: S) -> Result<Option<Node<'a>>, SelectorErrorKind<'s>>
+    where
+        S: ToSelector<'s>,
+    {
+        selector.with_selector(|selector| self.select(selector).next())
+    }
+
+    /// Checks if this is an element matching a selector, given as a [`Selector`] or a string to parse.
+    pub fn matches<'s, S>(&self, selector: S) -> Result<bool, SelectorErrorKind<'s>>
+    where
+        S: ToSelector<'s>,
+    {
+        selector.with_selector(|selector| self.as_data().is_some() && selector.matches(self))
+    }
+
+    /// Returns the nearest element matching a selector, starting with this node and walking up its ancestors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use htmler::Html;
+    /// let html = Html::parse_fragment("<div class=card><p><a href=x>link</a></p></div>");
+    /// let link = html.select_first("a").unwrap().unwrap();
+    /// assert!(link.closest(".card").unwrap().unwrap().is_a("div"));
+    /// assert_eq!(link.closest("a").unwrap(), Some(link));
+    /// assert_eq!(link.closest("section").unwrap(), None);
+    /// ```
+    pub fn closest<'s, S>(&self, selector: S) -> Result<Option<Node<'a>>, SelectorErrorKind<'s>>
+    where
+        S: ToSelector<'s>,
+    {
+        let mut elements = std::iter::once(self.ptr).chain(self.ptr.ancestors()).filter_map(self.element_wrapper());
+        selector.with_selector(|selector| elements.find(|element| selector.matches(element)))
     }
 
     fn serialize(&self, traversal_scope: TraversalScope) -> String {
@@ -61,42 +151,108 @@ impl<'a> Node<'a> {
     }
 
     /// Returns the inner HTML of this element.
+    ///
+    /// For a `<template>` element, this is the HTML of its contents.
     pub fn inner_html(&self) -> String {
         self.serialize(TraversalScope::ChildrenOnly(None))
     }
 
+    /// Returns a deep copy of this node as a standalone fragment.
+    ///
+    /// Source spans are not copied.
+    pub fn to_fragment(&self) -> Html {
+        Html::fragment_of(self.ptr, self.quirks_mode)
+    }
+
     /// Returns an iterator over descendent text nodes.
     pub fn text(&self) -> Text<'a> {
         Text { inner: self.ptr.traverse() }
     }
-    /// Returns an iterator over descendent elements.
+    /// Returns the parent node, which is the document or fragment root for top-level nodes.
+    pub fn parent(&self) -> Option<Node<'a>> {
+        self.ptr.parent().map(self.wrapper())
+    }
+    /// Returns the parent if it is an element.
+    pub fn parent_element(&self) -> Option<Node<'a>> {
+        self.ptr.parent().and_then(self.element_wrapper())
+    }
+    /// Returns an iterator over the ancestor nodes, from the parent up to the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = Node<'a>> {
+        self.ptr.ancestors().map(self.wrapper())
+    }
+    /// Returns an iterator over the ancestor elements, from the parent up.
+    pub fn ancestors_elements(&self) -> impl Iterator<Item = Node<'a>> {
+        self.ptr.ancestors().filter_map(self.element_wrapper())
+    }
+    /// Returns the next sibling node.
+    pub fn next_sibling(&self) -> Option<Node<'a>> {
+        self.ptr.next_sibling().map(self.wrapper())
+    }
+    /// Returns the previous sibling node.
+    pub fn prev_sibling(&self) -> Option<Node<'a>> {
+        self.ptr.prev_sibling().map(self.wrapper())
+    }
+    /// Returns an iterator over the following sibling nodes.
+    pub fn next_siblings(&self) -> impl Iterator<Item = Node<'a>> {
+        self.ptr.next_siblings().map(self.wrapper())
+    }
+    /// Returns an iterator over the preceding sibling nodes, nearest first.
+    pub fn prev_siblings(&self) -> impl Iterator<Item = Node<'a>> {
+        self.ptr.prev_siblings().map(self.wrapper())
+    }
+    /// Returns the next sibling which is an element, skipping text and comments.
+    pub fn next_element_sibling(&self) -> Option<Node<'a>> {
+        self.ptr.next_siblings().find_map(self.element_wrapper())
+    }
+    /// Returns the previous sibling which is an element, skipping text and comments.
+    pub fn prev_element_sibling(&self) -> Option<Node<'a>> {
+        self.ptr.prev_siblings().find_map(self.element_wrapper())
+    }
+    /// Returns the position of this node among all children of its parent, or `None` for a root.
+    pub fn index_in_parent(&self) -> Option<usize> {
+        self.ptr.parent()?;
+        Some(self.ptr.prev_siblings().count())
+    }
+    /// Returns an iterator over the child nodes, including text and comments.
     pub fn children(&self) -> impl Iterator<Item = Node<'a>> {
-        self.ptr.children().map(Node::new)
+        self.ptr.children().map(self.wrapper())
     }
-    /// Returns the first child element.
+    /// Returns an iterator over the child elements.
+    pub fn child_elements(&self) -> impl Iterator<Item = Node<'a>> {
+        self.ptr.children().filter_map(self.element_wrapper())
+    }
+    /// Returns the first child node.
     pub fn first_child(&self) -> Option<Node<'a>> {
-        self.ptr.first_child().map(Node::new)
+        self.ptr.first_child().map(self.wrapper())
     }
-    /// Returns the last child element.
+    /// Returns the last child node.
     pub fn last_child(&self) -> Option<Node<'a>> {
-        self.ptr.last_child().map(Node::new)
+        self.ptr.last_child().map(self.wrapper())
     }
-
-    /// Returns the parent element.
+    /// Returns the first child element.
+    pub fn first_element_child(&self) -> Option<Node<'a>> {
+        self.ptr.children().find_map(self.element_wrapper())
+    }
+    /// Returns the last child element.
+    pub fn last_element_child(&self) -> Option<Node<'a>> {
+        self.ptr.children().rev().find_map(self.element_wrapper())
+    }
+    /// Returns an iterator over this node and all nodes below it, in document order.
     pub fn descendants(&self) -> impl Iterator<Item = Node<'a>> {
-        self.ptr.descendants().map(Node::new)
+        self.ptr.descendants().map(self.wrapper())
     }
-    /// Returns the parent element.
+
+    /// Checks if this is an element with the given class, `false` for other nodes.
     pub fn has_class(&self, class: &str) -> bool {
-        self.as_data().unwrap().has_class(class)
+        self.as_data().is_some_and(|data| data.has_class(class))
     }
-    /// Returns the parent element.
+    /// Checks if this is an element with the given attribute, `false` for other nodes.
     pub fn has_attribute(&self, name: &str) -> bool {
-        self.as_data().unwrap().has_attribute(name)
+        self.as_data().is_some_and(|data| data.has_attribute(name))
     }
-    /// Returns the value of an attribute.
+    /// Returns the value of an attribute, or an empty string if it is missing or this is not an element.
     pub fn get_attribute(&self, name: &str) -> &'a str {
-        self.as_data().unwrap().get_attribute(name).unwrap_or("")
+        self.as_data().and_then(|data| data.get_attribute(name)).unwrap_or("")
     }
 }
 
@@ -121,42 +277,58 @@ impl<'a> Node<'a> {
             None => false,
         }
     }
-    /// Returns the next sibling element.
+    /// Returns the kind of this node.
     pub fn as_kind(&self) -> &'a NodeKind {
         self.ptr.value()
     }
-    /// Returns the parent element.
+    /// Returns the element data if this is an element.
     pub fn as_data(&self) -> Option<&'a NodeData> {
         match self.as_kind() {
             NodeKind::Element(ref e) => Some(e),
             _ => None,
         }
     }
-    /// Returns the parent element.
+    /// Returns the doctype if this is one.
     pub fn as_doctype(&self) -> Option<&'a Doctype> {
         match self.as_kind() {
             NodeKind::Doctype(t) => Some(t),
             _ => None,
         }
     }
-    /// Returns the parent element.
+    /// Returns the text if this is a text node.
     pub fn as_text(&self) -> Option<&'a HtmlStr> {
         match self.as_kind() {
-            NodeKind::Text(t) => Some(t),
+            NodeKind::Text(t) => Some(&t.text),
             _ => None,
         }
     }
-    /// Returns self as an element.
+    /// Returns where this element, text or comment was found in the source.
+    ///
+    /// Only recorded when parsing with [`HtmlParseOptions::source_spans`](crate::html::HtmlParseOptions::source_spans).
+    pub fn source_span(&self) -> Option<SourceSpan> {
+        match self.as_kind() {
+            NodeKind::Element(e) => e.span,
+            NodeKind::Text(t) => t.span,
+            NodeKind::Comment(c) => c.span,
+            _ => None,
+        }
+    }
+    /// Returns the contents fragment if this is a `<template>` element.
+    pub fn template_contents(&self) -> Option<Node<'a>> {
+        let id = self.as_data()?.template_contents?;
+        self.ptr.tree().get(id).map(self.wrapper())
+    }
+    /// Returns the processing instruction if this is one.
     pub fn as_processing_instruction(&self) -> Option<&ProcessingInstruction> {
         match self.as_kind() {
             NodeKind::ProcessingInstruction(ref pi) => Some(pi),
             _ => None,
         }
     }
-    /// Returns the parent element.
+    /// Returns the comment text if this is a comment.
     pub fn as_comment(&self) -> Option<&'a HtmlStr> {
         match self.as_kind() {
-            NodeKind::Comment(t) => Some(t),
+            NodeKind::Comment(t) => Some(&t.comment),
             _ => None,
         }
     }
@@ -175,22 +347,43 @@ pub struct Select<'a, 'b> {
     scope: Node<'a>,
     inner: Traverse<'a, NodeKind>,
     selector: &'b Selector,
+    include_templates: bool,
+    /// Traversals interrupted to walk the contents of a template.
+    suspended: Vec<Traverse<'a, NodeKind>>,
+}
+
+impl<'a, 'b> Select<'a, 'b> {
+    /// Also yields matching elements inside the contents of `<template>` elements.
+    pub fn include_templates(mut self) -> Self {
+        self.include_templates = true;
+        self
+    }
 }
 
 impl<'a, 'b> Iterator for Select<'a, 'b> {
     type Item = Node<'a>;
 
     fn next(&mut self) -> Option<Node<'a>> {
-        for edge in &mut self.inner {
-            if let Edge::Open(node) = edge {
-                if let Some(element) = Node::wrap(node) {
-                    if self.selector.matches_with_scope(&element, Some(self.scope)) {
-                        return Some(element);
-                    }
+        loop {
+            let node = match self.inner.next() {
+                Some(Edge::Open(node)) => node,
+                Some(Edge::Close(_)) => continue,
+                None => {
+                    self.inner = self.suspended.pop()?;
+                    continue;
+                }
+            };
+            if let Some(element) = Node::wrap_in(node, self.scope.quirks_mode) {
+                if let Some(contents) = element.template_contents().filter(|_| self.include_templates) {
+                    let mut contents = contents.ptr.traverse();
+                    contents.next(); // Skip Edge::Open(fragment).
+                    self.suspended.push(std::mem::replace(&mut self.inner, contents));
+                }
+                if self.selector.matches_with_scope(&element, Some(self.scope)) {
+                    return Some(element);
                 }
             }
         }
-        None
     }
 }
 
@@ -216,8 +409,11 @@ impl<'a> Iterator for Text<'a> {
 }
 
 mod element;
+mod owned;
 mod serializable;
 
+pub use self::owned::OwnedNode;
+
 #[cfg(test)]
 mod tests {
     use crate::{html::Html, selector::Selector};
@@ -241,4 +437,87 @@ mod tests {
         let element2 = element1.select(&sel2).next().unwrap();
         assert_eq!(element2.inner_html(), "3");
     }
+
+    #[test]
+    fn test_select_template_contents() {
+        let html = r"
+            <div>
+                <b>1</b>
+                <template><b>2</b><template><b>3</b></template></template>
+                <b>4</b>
+            </div>
+        ";
+        let fragment = Html::parse_fragment(html);
+        let div = fragment.select(&Selector::try_parse("div").unwrap()).next().unwrap();
+        let b = Selector::try_parse("b").unwrap();
+        let result: Vec<_> = div.select(&b).map(|e| e.inner_html()).collect();
+        assert_eq!(result, vec!["1", "4"]);
+        let result: Vec<_> = div.select(&b).include_templates().map(|e| e.inner_html()).collect();
+        assert_eq!(result, vec!["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_navigation() {
+        let fragment = Html::parse_fragment("<ul>a<li id=x>1</li><!-- c --><li>2</li>b</ul>");
+        let li = fragment.select(&Selector::try_parse("#x").unwrap()).next().unwrap();
+        let ul = li.parent().unwrap();
+        assert!(ul.is_a("ul"));
+        assert_eq!(li.parent_element(), Some(ul));
+        assert_eq!(li.index_in_parent(), Some(1));
+        let root = fragment.root_node().parent().unwrap();
+        assert!(root.as_kind().is_fragment());
+        assert_eq!((root.parent(), root.index_in_parent()), (None, None));
+
+        let names: Vec<_> = li.ancestors_elements().map(|e| e.as_data().unwrap().name().to_owned()).collect();
+        assert_eq!(names, ["ul", "html"]);
+        assert_eq!(li.ancestors().count(), 3);
+
+        assert_eq!(li.next_sibling().unwrap().as_comment().map(|c| c.trim()), Some("c"));
+        assert_eq!(li.prev_sibling().unwrap().as_text().map(|t| &**t), Some("a"));
+        assert_eq!(li.next_element_sibling().unwrap().inner_html(), "2");
+        assert_eq!(li.prev_element_sibling(), None);
+        assert_eq!(li.next_siblings().count(), 3);
+        assert_eq!(li.prev_siblings().count(), 1);
+
+        assert_eq!(ul.children().count(), 5);
+        assert_eq!(ul.child_elements().count(), 2);
+        assert_eq!(ul.first_element_child(), Some(li));
+        assert_eq!(ul.last_element_child().unwrap().inner_html(), "2");
+        assert!(ul.first_child().unwrap().as_text().is_some());
+    }
+
+    #[test]
+    fn test_element_methods_on_other_nodes() {
+        let fragment = Html::parse_fragment("<p class=a>text</p>");
+        let p = fragment.select(&Selector::try_parse("p").unwrap()).next().unwrap();
+        let text = p.first_child().unwrap();
+        assert!(p.has_class("a"));
+        assert!(!text.has_class("a"));
+        assert!(!text.has_attribute("class"));
+        assert_eq!(text.get_attribute("class"), "");
+    }
+
+    #[test]
+    fn test_queries() {
+        let fragment = Html::parse_fragment("<div class=card><ul><li>a</li><li class=x>b</li></ul></div>");
+        let li = fragment.select_first("li.x").unwrap().unwrap();
+        let text = li.first_child().unwrap();
+        assert!(li.matches("li").unwrap());
+        let card_li = Selector::new(".card li");
+        assert!(li.matches(&card_li).unwrap());
+        assert!(!text.matches("*").unwrap());
+        assert!(li.matches("li[").is_err());
+
+        let card = Selector::new(".card");
+        assert!(text.closest(&card).unwrap().unwrap().is_a("div"));
+        assert_eq!(text.closest("li").unwrap(), Some(li));
+        assert!(li.closest("p").unwrap().is_none());
+
+        let ul = li.parent().unwrap();
+        assert_eq!(ul.select_first("li").unwrap().unwrap().inner_html(), "a");
+        assert_eq!(ul.select_first(Selector::new(":scope > .x")).unwrap(), Some(li));
+        assert!(ul.select_first(&card_li).unwrap().unwrap().is_a("li"));
+        assert!(fragment.select_first("p").unwrap().is_none());
+        assert!(fragment.select_first(">").is_err());
+    }
 }
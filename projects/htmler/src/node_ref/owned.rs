@@ -0,0 +1,106 @@
+use std::{
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+
+use ego_tree::NodeId;
+
+use crate::{Html, Node, Selector};
+
+/// A node together with a shared reference to its document.
+///
+/// Unlike [`Node`], this does not borrow the document, so it can be stored or sent to other threads. To keep nodes of
+/// a document which is still being edited, use [`Node::id`] and [`Html::node`] instead.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use htmler::{node_ref::OwnedNode, Html, Selector};
+///
+/// let html = Arc::new(Html::parse_fragment("<p>1</p><p>2</p>"));
+/// let nodes = OwnedNode::select(&html, &Selector::new("p"));
+/// let handle =
+///     std::thread::spawn(move || nodes.iter().map(|n| n.node().inner_html()).collect::<Vec<_>>());
+/// assert_eq!(handle.join().unwrap(), ["1", "2"]);
+/// ```
+#[derive(Clone)]
+pub struct OwnedNode {
+    html: Arc<Html>,
+    id: NodeId,
+}
+
+impl OwnedNode {
+    /// Returns a handle to the node `id` of the document, or `None` if it has no such node.
+    pub fn new(html: Arc<Html>, id: NodeId) -> Option<Self> {
+        html.node(id)?;
+        Some(OwnedNode { html, id })
+    }
+
+    /// Returns handles to the elements matching a selector, as [`Html::select`] does.
+    pub fn select(html: &Arc<Html>, selector: &Selector) -> Vec<Self> {
+        html.select(selector).map(|node| OwnedNode { html: html.clone(), id: node.id() }).collect()
+    }
+
+    /// Returns the ID of the node.
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Returns the document.
+    pub fn html(&self) -> &Arc<Html> {
+        &self.html
+    }
+
+    /// Borrows the node.
+    pub fn node(&self) -> Node<'_> {
+        self.html.node(self.id).unwrap()
+    }
+}
+
+impl Debug for OwnedNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("OwnedNode").field(&self.id).finish()
+    }
+}
+
+/// Handles are equal if they point to the same node of the same document.
+impl PartialEq for OwnedNode {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.html, &other.html) && self.id == other.id
+    }
+}
+
+impl Eq for OwnedNode {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::OwnedNode;
+    use crate::{Html, Selector};
+
+    #[test]
+    fn test_owned_node() {
+        let html = Arc::new(Html::parse_fragment("<ul><li>a</li><li>b</li></ul>"));
+        let items = OwnedNode::select(&html, &Selector::new("li"));
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].node().inner_html(), "b");
+        assert_eq!(OwnedNode::new(html.clone(), items[0].id()), Some(items[0].clone()));
+
+        let copy = Arc::new(Html::clone(&html));
+        assert_ne!(OwnedNode::new(copy, items[0].id()).unwrap(), items[0]);
+        let empty = Arc::new(Html::new_fragment());
+        assert_eq!(OwnedNode::new(empty, items[1].id()), None);
+    }
+
+    #[test]
+    fn test_node_by_id() {
+        let mut html = Html::parse_fragment("<p>a</p><p>b</p>");
+        let id = html.select_first("p").unwrap().unwrap().id();
+        html.remove(id);
+        assert_eq!(html.node(id).unwrap().inner_html(), "a");
+        assert!(html.node(id).unwrap().parent().is_none());
+    }
+}
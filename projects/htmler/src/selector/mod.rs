@@ -4,7 +4,7 @@ use std::{convert::TryFrom, fmt};
 
 use smallvec::SmallVec;
 
-use html5ever::{LocalName, Namespace};
+use html5ever::{tree_builder::QuirksMode, LocalName, Namespace};
 use selectors::{
     matching,
     parser::{SelectorList, SelectorParseErrorKind},
@@ -18,7 +18,7 @@ use crate::{error::SelectorErrorKind, Node};
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Selector {
     /// The CSS selectors.
-    selectors: SmallVec<[selectors::parser::Selector<Simple>; 1]>,
+    pub(crate) selectors: SmallVec<[selectors::parser::Selector<Simple>; 1]>,
 }
 
 impl Selector {
@@ -36,6 +36,8 @@ impl Selector {
     }
 
     /// Returns true if the element matches this selector.
+    ///
+    /// Classes and IDs match case-insensitively if the element was reached from a document in quirks mode.
     pub fn matches(&self, element: &Node) -> bool {
         self.matches_with_scope(element, None)
     }
@@ -44,8 +46,17 @@ impl Selector {
     /// The optional `scope` argument is used to specify which element has `:scope` pseudo-class.
     /// When it is `None`, `:scope` will match the root element.
     pub fn matches_with_scope(&self, element: &Node, scope: Option<Node>) -> bool {
-        let mut context =
-            matching::MatchingContext::new(matching::MatchingMode::Normal, None, None, matching::QuirksMode::NoQuirks);
+        self.matches_with_quirks_mode(element, scope, element.quirks_mode)
+    }
+
+    /// Returns true if the element matches this selector in a document with the given quirks mode.
+    pub fn matches_with_quirks_mode(&self, element: &Node, scope: Option<Node>, quirks_mode: QuirksMode) -> bool {
+        let quirks_mode = match quirks_mode {
+            QuirksMode::Quirks => matching::QuirksMode::Quirks,
+            QuirksMode::LimitedQuirks => matching::QuirksMode::LimitedQuirks,
+            QuirksMode::NoQuirks => matching::QuirksMode::NoQuirks,
+        };
+        let mut context = matching::MatchingContext::new(matching::MatchingMode::Normal, None, None, quirks_mode);
         context.scope_element = scope.map(|x| selectors::Element::opaque(&x));
         self.selectors.iter().any(|s| matching::matches_selector(s, 0, None, element, &mut context, &mut |_, _| {}))
     }
@@ -173,6 +184,57 @@ impl<'i> TryFrom<&'i str> for Selector {
     }
 }
 
+/// A [`Selector`], by value or by reference, or a string to parse into one.
+///
+/// Queries like [`Node::select_first`](crate::Node::select_first) take it, so that a selector passed by reference is
+/// not cloned. It is implemented for `&str`, `Selector` and `&Selector`, and cannot be implemented outside this crate.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{selector::ToSelector, Html, Selector};
+///
+/// fn contains<'s>(html: &Html, selector: impl ToSelector<'s>) -> bool {
+///     matches!(html.select_first(selector), Ok(Some(_)))
+/// }
+///
+/// let html = Html::parse_fragment("<p>a</p>");
+/// assert!(contains(&html, "p"));
+/// assert!(!contains(&html, &Selector::new("div")));
+/// ```
+pub trait ToSelector<'s>: sealed::Sealed {
+    /// Calls `f` with the selector, parsing it first if needed.
+    fn with_selector<R>(self, f: impl FnOnce(&Selector) -> R) -> Result<R, SelectorErrorKind<'s>>;
+}
+
+impl<'s> ToSelector<'s> for &'s str {
+    fn with_selector<R>(self, f: impl FnOnce(&Selector) -> R) -> Result<R, SelectorErrorKind<'s>> {
+        Ok(f(&Selector::try_parse(self)?))
+    }
+}
+
+impl<'s> ToSelector<'s> for Selector {
+    fn with_selector<R>(self, f: impl FnOnce(&Selector) -> R) -> Result<R, SelectorErrorKind<'s>> {
+        Ok(f(&self))
+    }
+}
+
+impl<'s> ToSelector<'s> for &Selector {
+    fn with_selector<R>(self, f: impl FnOnce(&Selector) -> R) -> Result<R, SelectorErrorKind<'s>> {
+        Ok(f(self))
+    }
+}
+
+mod sealed {
+    use super::Selector;
+
+    pub trait Sealed {}
+
+    impl Sealed for &str {}
+    impl Sealed for Selector {}
+    impl Sealed for &Selector {}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -187,6 +249,34 @@ mod tests {
         let _sel: Selector = (*s).try_into().unwrap();
     }
 
+    #[test]
+    fn quirks_mode_matching() {
+        let source = "<p class=Foo id=Bar><b class=Foo></b></p>";
+        let quirks = crate::Html::parse_document(source);
+        let no_quirks = crate::Html::parse_document(&format!("<!DOCTYPE html>{}", source));
+        for (selector, count) in [(".foo", 2), ("#bar", 1), ("p.FOO", 1)] {
+            let selector = Selector::new(selector);
+            assert_eq!(quirks.select(&selector).count(), count);
+            assert_eq!(no_quirks.select(&selector).count(), 0);
+        }
+
+        let paragraph = quirks.select(&Selector::new("p")).next().unwrap();
+        assert_eq!(paragraph.select(&Selector::new(".FOO")).count(), 1);
+        assert!(Selector::new("#BAR").matches(&paragraph));
+        assert!(!Selector::new("#BAR").matches_with_quirks_mode(&paragraph, None, QuirksMode::NoQuirks));
+        // Values of other attributes stay case-sensitive.
+        assert!(!Selector::new("[class=foo]").matches(&paragraph));
+
+        // Elements follow the mode of the document they are reached from.
+        let mut changed = no_quirks.clone();
+        changed.quirks_mode = QuirksMode::Quirks;
+        let paragraph = changed.select(&Selector::new("p")).next().unwrap();
+        assert!(Selector::new("#BAR").matches(&paragraph));
+        assert_eq!(paragraph.select(&Selector::new(".FOO")).count(), 1);
+        assert_eq!(changed.root_node().select(&Selector::new(".foo")).count(), 2);
+        assert!(!Selector::new("#BAR").matches(&Node::wrap(paragraph.ptr).unwrap()));
+    }
+
     #[test]
     #[should_panic]
     fn invalid_selector_conversions() {
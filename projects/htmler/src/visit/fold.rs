@@ -0,0 +1,209 @@
+use ego_tree::{iter::Edge, NodeId};
+
+use crate::{html::concat_text, Html, HtmlStr, Node, NodeKind};
+
+/// What a [`Fold`] does with an element.
+#[derive(Debug, Clone)]
+pub enum FoldElement {
+    /// Copies the element, folding its children into it.
+    Keep,
+    /// Puts a node in place of the element, folding the children into it if it is an element or dropping them if not.
+    Replace(Box<NodeKind>),
+    /// Drops the element, folding its children into its place.
+    Unwrap,
+    /// Drops the element with its children.
+    Remove,
+}
+
+/// Hooks which rebuild a tree, see [`Html::fold`].
+///
+/// The hooks default to copying nodes unchanged. Doctypes and processing instructions are always copied.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{
+///     visit::{Fold, FoldElement},
+///     Html, HtmlStr, Node,
+/// };
+///
+/// /// Drops scripts, unwraps `<span>` and shouts.
+/// struct Clean;
+///
+/// impl Fold for Clean {
+///     fn fold_element(&mut self, element: Node<'_>) -> FoldElement {
+///         match element.as_data().unwrap().name() {
+///             "script" => FoldElement::Remove,
+///             "span" => FoldElement::Unwrap,
+///             _ => FoldElement::Keep,
+///         }
+///     }
+///
+///     fn fold_text(&mut self, _: Node<'_>, text: &str) -> Option<HtmlStr> {
+///         Some(text.to_uppercase())
+///     }
+/// }
+///
+/// let html = Html::parse_fragment("<p>a <span>b</span><script>c</script></p>");
+/// assert_eq!(html.fold(&mut Clean).root_node().inner_html(), "<p>A B</p>");
+/// ```
+pub trait Fold {
+    /// Decides what to do with an element, before its children are folded.
+    fn fold_element(&mut self, element: Node<'_>) -> FoldElement {
+        let _ = element;
+        FoldElement::Keep
+    }
+
+    /// Returns the new content of a text node, or `None` to drop it.
+    fn fold_text(&mut self, node: Node<'_>, text: &str) -> Option<HtmlStr> {
+        let _ = node;
+        Some(text.into())
+    }
+
+    /// Returns the new content of a comment, or `None` to drop it.
+    fn fold_comment(&mut self, node: Node<'_>, comment: &str) -> Option<HtmlStr> {
+        let _ = node;
+        Some(comment.into())
+    }
+}
+
+impl Html {
+    /// Builds a new tree from the nodes a [`Fold`] keeps or replaces, in document order.
+    ///
+    /// Text which ends up next to other text is merged, as when parsing.
+    pub fn fold<F>(&self, folder: &mut F) -> Html
+    where
+        F: Fold + ?Sized,
+    {
+        let mut html = if self.tree.root().value().is_fragment() { Html::new_fragment() } else { Html::new_document() };
+        html.quirks_mode = self.quirks_mode;
+        html.encoding = self.encoding;
+        html.xml = self.xml;
+        // The parent to fold into for each open element, repeated for unwrapped ones.
+        let mut parents = vec![html.tree.root().id()];
+        let mut edges = self.tree.root().traverse();
+        edges.next(); // Skip Edge::Open(root).
+        while let Some(edge) = edges.next() {
+            let node = match edge {
+                Edge::Open(node) => node,
+                Edge::Close(node) => {
+                    if node.value().is_element() {
+                        parents.pop();
+                    }
+                    continue;
+                }
+            };
+            let parent = *parents.last().unwrap();
+            let wrapped = self.to_node(node);
+            let kind = match node.value() {
+                NodeKind::Element(element) => match folder.fold_element(wrapped) {
+                    FoldElement::Keep => {
+                        let mut element = element.clone();
+                        let contents = element.template_contents.and_then(|id| node.tree().get(id));
+                        element.template_contents = contents.map(|contents| html.copy_node(contents));
+                        NodeKind::Element(element)
+                    }
+                    FoldElement::Replace(mut kind) => {
+                        if let NodeKind::Element(ref mut replacement) = *kind {
+                            replacement.xml = element.xml;
+                        }
+                        *kind
+                    }
+                    FoldElement::Unwrap => {
+                        parents.push(parent);
+                        continue;
+                    }
+                    FoldElement::Remove => {
+                        edges.find(|edge| matches!(edge, Edge::Close(closed) if *closed == node));
+                        continue;
+                    }
+                },
+                NodeKind::Text(text) => match folder.fold_text(wrapped, &text.text) {
+                    Some(text) => NodeKind::text(&text),
+                    None => continue,
+                },
+                NodeKind::Comment(comment) => match folder.fold_comment(wrapped, &comment.comment) {
+                    Some(comment) => NodeKind::comment(&comment),
+                    None => continue,
+                },
+                other => other.clone(),
+            };
+            let folds_children = kind.is_element();
+            let id = append(&mut html, parent, kind);
+            if node.value().is_element() {
+                if folds_children {
+                    parents.push(id);
+                }
+                else {
+                    edges.find(|edge| matches!(edge, Edge::Close(closed) if *closed == node));
+                }
+            }
+        }
+        html
+    }
+}
+
+/// Appends a node, merging text into a preceding text node.
+fn append(html: &mut Html, parent: NodeId, kind: NodeKind) -> NodeId {
+    let mut parent = html.tree.get_mut(parent).unwrap();
+    if let NodeKind::Text(text) = kind {
+        if let Some(mut last) = parent.last_child() {
+            if let NodeKind::Text(previous) = last.value() {
+                concat_text(previous, text);
+                return last.id();
+            }
+        }
+        return parent.append(NodeKind::Text(text)).id();
+    }
+    parent.append(kind).id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fold, FoldElement};
+    use crate::{Html, HtmlStr, Node, NodeKind};
+
+    struct Rewrite;
+
+    impl Fold for Rewrite {
+        fn fold_element(&mut self, element: Node<'_>) -> FoldElement {
+            match element.as_data().unwrap().name() {
+                "b" => FoldElement::Replace(Box::new(NodeKind::element("strong"))),
+                "i" => FoldElement::Unwrap,
+                "hr" => FoldElement::Replace(Box::new(NodeKind::text("---"))),
+                "img" => FoldElement::Replace(Box::new(NodeKind::comment("image"))),
+                _ => FoldElement::Keep,
+            }
+        }
+
+        fn fold_comment(&mut self, _: Node<'_>, comment: &str) -> Option<HtmlStr> {
+            (comment != "drop").then(|| comment.into())
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        let html = Html::parse_document("<!DOCTYPE html><p class=x>a<i>b</i><hr>c<!--drop--><b>d</b><img></p>");
+        let folded = html.fold(&mut Rewrite);
+        assert_eq!(
+            folded.as_html(),
+            "<!DOCTYPE html><html><head></head><body><p class=\"x\">ab</p>---c<strong>d</strong><!--image--><p></p></body></html>"
+        );
+        // Text merged across the unwrapped element.
+        let p = folded.select_first("p").unwrap().unwrap();
+        assert_eq!(p.children().count(), 1);
+        assert_eq!(html.fold(&mut Rewrite).as_html(), folded.as_html());
+    }
+
+    #[test]
+    fn test_fold_template() {
+        struct Keep;
+        impl Fold for Keep {}
+
+        let html = Html::parse_fragment("<template><p>a</p></template>");
+        let folded = html.fold(&mut Keep);
+        assert_eq!(folded.as_html(), html.as_html());
+        let template = folded.select_first("template").unwrap().unwrap();
+        assert!(std::ptr::eq(template.template_contents().unwrap().ptr.tree(), &folded.tree));
+    }
+}
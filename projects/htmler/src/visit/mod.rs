@@ -0,0 +1,195 @@
+//! Walking and transforming trees.
+//!
+//! A [`Visitor`] is called for each node in document order and steers the walk with [`Walk`]. A [`Fold`] rebuilds a
+//! new [`Html`] from the nodes it keeps or replaces. Both skip the contents of `<template>` elements, as
+//! [`Html::select`] does, though [`Fold`] copies them along with a kept template.
+
+use ego_tree::iter::Edge;
+
+use crate::{Html, Node, NodeKind};
+
+pub use self::fold::{Fold, FoldElement};
+
+mod fold;
+
+/// How a walk goes on after a hook.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Walk {
+    /// Goes on with the children of an element, then its siblings.
+    #[default]
+    Continue,
+    /// Goes on with the siblings of an element, skipping its children but still leaving it.
+    SkipChildren,
+    /// Ends the walk.
+    Stop,
+}
+
+/// Hooks called while walking a tree, see [`Node::walk`].
+///
+/// All hooks default to [`Walk::Continue`].
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{
+///     visit::{Visitor, Walk},
+///     Html, Node,
+/// };
+///
+/// /// Collects the text outside of `<code>` elements.
+/// struct Prose(String);
+///
+/// impl<'a> Visitor<'a> for Prose {
+///     fn visit_element(&mut self, element: Node<'a>) -> Walk {
+///         if element.is_a("code") { Walk::SkipChildren } else { Walk::Continue }
+///     }
+///
+///     fn visit_text(&mut self, _: Node<'a>, text: &'a str) -> Walk {
+///         self.0.push_str(text);
+///         Walk::Continue
+///     }
+/// }
+///
+/// let html = Html::parse_fragment("<p>Call <code>walk</code> on a node.</p>");
+/// let mut prose = Prose(String::new());
+/// html.walk(&mut prose);
+/// assert_eq!(prose.0, "Call  on a node.");
+/// ```
+pub trait Visitor<'a> {
+    /// Called when entering an element, before its children.
+    fn visit_element(&mut self, element: Node<'a>) -> Walk {
+        let _ = element;
+        Walk::Continue
+    }
+
+    /// Called when leaving an element, after its children, where [`Walk::SkipChildren`] goes on as
+    /// [`Walk::Continue`].
+    fn leave_element(&mut self, element: Node<'a>) -> Walk {
+        let _ = element;
+        Walk::Continue
+    }
+
+    /// Called for a text node.
+    fn visit_text(&mut self, node: Node<'a>, text: &'a str) -> Walk {
+        let _ = (node, text);
+        Walk::Continue
+    }
+
+    /// Called for a comment.
+    fn visit_comment(&mut self, node: Node<'a>, comment: &'a str) -> Walk {
+        let _ = (node, comment);
+        Walk::Continue
+    }
+
+    /// Called for a doctype or processing instruction.
+    fn visit_other(&mut self, node: Node<'a>) -> Walk {
+        let _ = node;
+        Walk::Continue
+    }
+}
+
+impl<'a> Node<'a> {
+    /// Walks this node and its descendants in document order, returning [`Walk::Stop`] if a hook stopped the walk.
+    pub fn walk<V>(&self, visitor: &mut V) -> Walk
+    where
+        V: Visitor<'a> + ?Sized,
+    {
+        let mut edges = self.ptr.traverse();
+        while let Some(edge) = edges.next() {
+            let walk = match edge {
+                Edge::Open(node) => {
+                    let wrapped = self.related(node);
+                    match node.value() {
+                        NodeKind::Element(_) => match visitor.visit_element(wrapped) {
+                            Walk::SkipChildren => {
+                                // Up to the close of the element, which leaves it.
+                                edges.find(|edge| matches!(edge, Edge::Close(closed) if *closed == node));
+                                visitor.leave_element(wrapped)
+                            }
+                            walk => walk,
+                        },
+                        NodeKind::Text(text) => visitor.visit_text(wrapped, &text.text),
+                        NodeKind::Comment(comment) => visitor.visit_comment(wrapped, &comment.comment),
+                        NodeKind::Doctype(_) | NodeKind::ProcessingInstruction(_) => visitor.visit_other(wrapped),
+                        NodeKind::Document | NodeKind::Fragment => Walk::Continue,
+                    }
+                }
+                Edge::Close(node) if node.value().is_element() => visitor.leave_element(self.related(node)),
+                Edge::Close(_) => Walk::Continue,
+            };
+            if walk == Walk::Stop {
+                return Walk::Stop;
+            }
+        }
+        Walk::Continue
+    }
+}
+
+impl Html {
+    /// Walks the whole tree in document order, see [`Node::walk`].
+    pub fn walk<'a, V>(&'a self, visitor: &mut V) -> Walk
+    where
+        V: Visitor<'a> + ?Sized,
+    {
+        self.to_node(self.tree.root()).walk(visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Visitor, Walk};
+    use crate::{Html, Node};
+
+    /// Records the hooks, skipping the children of `<b>` and stopping at `<i>`.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl<'a> Visitor<'a> for Recorder {
+        fn visit_element(&mut self, element: Node<'a>) -> Walk {
+            let name = element.as_data().unwrap().name();
+            self.0.push(format!("<{}>", name));
+            match name {
+                "b" => Walk::SkipChildren,
+                "i" => Walk::Stop,
+                _ => Walk::Continue,
+            }
+        }
+
+        fn leave_element(&mut self, element: Node<'a>) -> Walk {
+            self.0.push(format!("</{}>", element.as_data().unwrap().name()));
+            Walk::Continue
+        }
+
+        fn visit_text(&mut self, _: Node<'a>, text: &'a str) -> Walk {
+            self.0.push(text.to_owned());
+            Walk::Continue
+        }
+
+        fn visit_comment(&mut self, _: Node<'a>, comment: &'a str) -> Walk {
+            self.0.push(format!("<!--{}-->", comment));
+            Walk::Continue
+        }
+
+        fn visit_other(&mut self, _: Node<'a>) -> Walk {
+            self.0.push("other".to_owned());
+            Walk::Continue
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let html = Html::parse_document("<!DOCTYPE html><p>a<!--c--><b>skipped</b>d</p><p><i>e</i>f</p>");
+        let mut recorder = Recorder::default();
+        assert_eq!(html.walk(&mut recorder), Walk::Stop);
+        let expected = [
+            "other", "<html>", "<head>", "</head>", "<body>", "<p>", "a", "<!--c-->", "<b>", "</b>", "d", "</p>", "<p>",
+            "<i>",
+        ];
+        assert_eq!(recorder.0, expected);
+
+        let p = html.select_first("p").unwrap().unwrap();
+        let mut recorder = Recorder::default();
+        assert_eq!(p.walk(&mut recorder), Walk::Continue);
+        assert_eq!(recorder.0, ["<p>", "a", "<!--c-->", "<b>", "</b>", "d", "</p>"]);
+    }
+}
@@ -0,0 +1,328 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use ego_tree::NodeId;
+use html5ever::QualName;
+
+use super::{
+    parser::{Arithmetic, Axis, Comparison, Expr, NodeTest, Step},
+    to_number, XPathNode, XPathValue,
+};
+use crate::{error::XPathError, Node, NodeKind};
+
+/// The context an expression is evaluated in.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Context<'a> {
+    pub(super) node: XPathNode<'a>,
+    /// The position of the node in the node-set being filtered, counted from 1.
+    pub(super) position: usize,
+    /// The size of the node-set being filtered.
+    pub(super) size: usize,
+}
+
+/// Evaluates expressions over one tree.
+#[derive(Debug, Default)]
+pub(super) struct Evaluator {
+    /// The position of each node in document order, numbered a whole tree at a time when first needed.
+    order: RefCell<HashMap<NodeId, usize>>,
+}
+
+impl Evaluator {
+    pub(super) fn eval<'a>(&self, expr: &Expr, context: &Context<'a>) -> Result<XPathValue<'a>, XPathError> {
+        let value = match expr {
+            Expr::Or(left, right) => {
+                XPathValue::Boolean(self.eval(left, context)?.boolean() || self.eval(right, context)?.boolean())
+            }
+            Expr::And(left, right) => {
+                XPathValue::Boolean(self.eval(left, context)?.boolean() && self.eval(right, context)?.boolean())
+            }
+            Expr::Compare(op, left, right) => {
+                XPathValue::Boolean(compare(*op, &self.eval(left, context)?, &self.eval(right, context)?))
+            }
+            Expr::Arithmetic(op, left, right) => {
+                let (left, right) = (self.eval(left, context)?.number(), self.eval(right, context)?.number());
+                XPathValue::Number(match op {
+                    Arithmetic::Add => left + right,
+                    Arithmetic::Subtract => left - right,
+                    Arithmetic::Multiply => left * right,
+                    Arithmetic::Divide => left / right,
+                    Arithmetic::Modulo => left % right,
+                })
+            }
+            Expr::Negate(expr) => XPathValue::Number(-self.eval(expr, context)?.number()),
+            Expr::Union(left, right) => {
+                let mut nodes = self.nodes(left, context)?;
+                nodes.extend(self.nodes(right, context)?);
+                XPathValue::Nodes(self.sorted(nodes))
+            }
+            Expr::Literal(literal) => XPathValue::String(literal.clone()),
+            Expr::Number(number) => XPathValue::Number(*number),
+            Expr::Variable(name) => return Err(XPathError::UnknownVariable { name: name.clone() }),
+            Expr::Function(function, args) => self.call(*function, args, context)?,
+            Expr::Filter(expr, predicates) => {
+                let nodes = self.nodes(expr, context)?;
+                XPathValue::Nodes(predicates.iter().try_fold(nodes, |nodes, predicate| self.filter(nodes, predicate))?)
+            }
+            Expr::Path(start, steps) => {
+                let nodes = self.nodes(start, context)?;
+                XPathValue::Nodes(steps.iter().try_fold(nodes, |nodes, step| self.step(&nodes, step))?)
+            }
+            Expr::Root => XPathValue::Nodes(vec![XPathNode::Node(self.root(context.node))]),
+            Expr::ContextNode => XPathValue::Nodes(vec![context.node]),
+        };
+        Ok(value)
+    }
+
+    /// Evaluates an expression which must result in a node-set.
+    pub(super) fn nodes<'a>(&self, expr: &Expr, context: &Context<'a>) -> Result<Vec<XPathNode<'a>>, XPathError> {
+        match self.eval(expr, context)? {
+            XPathValue::Nodes(nodes) => Ok(nodes),
+            _ => Err(XPathError::NotANodeSet),
+        }
+    }
+
+    /// Returns the root of the tree containing a node.
+    pub(super) fn root<'a>(&self, node: XPathNode<'a>) -> Node<'a> {
+        let node = node.parent_or_self();
+        node.ancestors().last().unwrap_or(node)
+    }
+
+    /// Keeps the nodes for which a predicate holds, a number being compared to the position.
+    fn filter<'a>(&self, nodes: Vec<XPathNode<'a>>, predicate: &Expr) -> Result<Vec<XPathNode<'a>>, XPathError> {
+        let size = nodes.len();
+        let mut kept = Vec::new();
+        for (index, node) in nodes.into_iter().enumerate() {
+            let context = Context { node, position: index + 1, size };
+            let keep = match self.eval(predicate, &context)? {
+                XPathValue::Number(n) => n == context.position as f64,
+                other => other.boolean(),
+            };
+            if keep {
+                kept.push(node);
+            }
+        }
+        Ok(kept)
+    }
+
+    fn step<'a>(&self, nodes: &[XPathNode<'a>], step: &Step) -> Result<Vec<XPathNode<'a>>, XPathError> {
+        let mut result = Vec::new();
+        for node in nodes {
+            // In the order of the axis, so that positions count backwards on reverse axes.
+            let selected = axis(*node, step.axis).into_iter().filter(|n| matches(*n, step.axis, &step.test)).collect();
+            result.extend(step.predicates.iter().try_fold(selected, |nodes, predicate| self.filter(nodes, predicate))?);
+        }
+        Ok(self.sorted(result))
+    }
+
+    /// Sorts nodes in document order, dropping duplicates.
+    fn sorted<'a>(&self, mut nodes: Vec<XPathNode<'a>>) -> Vec<XPathNode<'a>> {
+        nodes.sort_by_key(|node| self.order_key(*node));
+        nodes.dedup();
+        nodes
+    }
+
+    /// Orders nodes before their namespaces, then attributes, then descendants.
+    fn order_key(&self, node: XPathNode<'_>) -> (usize, usize, usize) {
+        let element = node.parent_or_self();
+        let mut order = self.order.borrow_mut();
+        if !order.contains_key(&element.id()) {
+            let root = element.ancestors().last().unwrap_or(element);
+            let offset = order.len();
+            order.extend(root.descendants().enumerate().map(|(i, n)| (n.id(), offset + i)));
+        }
+        let position = order[&element.id()];
+        match node {
+            XPathNode::Node(_) => (position, 0, 0),
+            XPathNode::Namespace { .. } => (position, 1, 0),
+            XPathNode::Attribute { name, .. } => {
+                (position, 2, element.as_data().and_then(|data| data.attrs.get_index_of(name)).unwrap_or(0))
+            }
+        }
+    }
+}
+
+/// Returns the nodes on an axis, in the order of the axis.
+fn axis(node: XPathNode<'_>, axis: Axis) -> Vec<XPathNode<'_>> {
+    let node = match node {
+        XPathNode::Node(node) => node,
+        XPathNode::Attribute { element, .. } | XPathNode::Namespace { element, .. } => {
+            return match axis {
+                Axis::Self_ => vec![node],
+                Axis::Parent => vec![XPathNode::Node(element)],
+                Axis::Ancestor => nodes(std::iter::once(element).chain(element.ancestors())),
+                Axis::AncestorOrSelf => {
+                    let mut nodes = vec![node];
+                    nodes.extend(axis_of(element, Axis::AncestorOrSelf));
+                    nodes
+                }
+                Axis::Following => nodes(element.descendants().skip(1).chain(following(element))),
+                Axis::Preceding => axis_of(element, Axis::Preceding),
+                _ => Vec::new(),
+            };
+        }
+    };
+    axis_of(node, axis)
+}
+
+/// Returns the nodes on an axis of a tree node.
+fn axis_of(node: Node<'_>, axis: Axis) -> Vec<XPathNode<'_>> {
+    match axis {
+        Axis::Ancestor => nodes(node.ancestors()),
+        Axis::AncestorOrSelf => nodes(std::iter::once(node).chain(node.ancestors())),
+        Axis::Attribute => match node.as_data() {
+            Some(data) => data
+                .attrs
+                .iter()
+                .filter(|(name, _)| name.ns != ns!(xmlns))
+                .map(|(name, value)| XPathNode::Attribute { element: node, name, value })
+                .collect(),
+            None => Vec::new(),
+        },
+        Axis::Child => nodes(node.children()),
+        Axis::Descendant => nodes(node.descendants().skip(1)),
+        Axis::DescendantOrSelf => nodes(node.descendants()),
+        Axis::Following => nodes(following(node)),
+        Axis::FollowingSibling => nodes(node.next_siblings()),
+        Axis::Namespace => namespaces(node),
+        Axis::Parent => nodes(node.parent()),
+        Axis::Preceding => {
+            nodes(std::iter::once(node).chain(node.ancestors()).flat_map(|n| {
+                n.prev_siblings().flat_map(|sibling| sibling.descendants().collect::<Vec<_>>().into_iter().rev())
+            }))
+        }
+        Axis::PrecedingSibling => nodes(node.prev_siblings()),
+        Axis::Self_ => nodes(Some(node)),
+    }
+}
+
+/// Returns the nodes after a node in document order, other than its descendants.
+fn following(node: Node<'_>) -> impl Iterator<Item = Node<'_>> {
+    std::iter::once(node).chain(node.ancestors()).flat_map(|n| n.next_siblings().flat_map(|sibling| sibling.descendants()))
+}
+
+/// Wraps tree nodes, leaving out doctypes which are not part of the XPath data model.
+fn nodes<'a>(nodes: impl IntoIterator<Item = Node<'a>>) -> Vec<XPathNode<'a>> {
+    nodes.into_iter().filter(|node| !node.as_kind().is_doctype()).map(XPathNode::Node).collect()
+}
+
+/// Returns the namespaces in scope of an element: `xml`, its own, and those declared on it or its ancestors.
+fn namespaces<'a>(node: Node<'a>) -> Vec<XPathNode<'a>> {
+    let Some(data) = node.as_data()
+    else {
+        return Vec::new();
+    };
+    let mut namespaces: Vec<XPathNode> = Vec::new();
+    let mut add = |prefix: &'a str, uri: &'a str| {
+        if !namespaces.iter().any(|n| matches!(n, XPathNode::Namespace { prefix: p, .. } if *p == prefix)) {
+            namespaces.push(XPathNode::Namespace { element: node, prefix, uri });
+        }
+    };
+    if !data.name.ns.is_empty() {
+        add(data.name.prefix.as_deref().unwrap_or(""), &data.name.ns);
+    }
+    for element in std::iter::once(node).chain(node.ancestors()).filter_map(|n| n.as_data()) {
+        for (name, value) in element.attrs.iter().filter(|(name, _)| name.ns == ns!(xmlns)) {
+            add(if &*name.local == "xmlns" { "" } else { &name.local }, value.as_str());
+        }
+    }
+    add("xml", "http://www.w3.org/XML/1998/namespace");
+    namespaces
+}
+
+/// Checks a node against the node test of a step.
+fn matches(node: XPathNode<'_>, axis: Axis, test: &NodeTest) -> bool {
+    let principal = match (node, axis) {
+        (XPathNode::Attribute { .. }, Axis::Attribute) | (XPathNode::Namespace { .. }, Axis::Namespace) => true,
+        (XPathNode::Node(node), _) => node.as_kind().is_element() && !matches!(axis, Axis::Attribute | Axis::Namespace),
+        _ => false,
+    };
+    let kind = match node {
+        XPathNode::Node(node) => Some(node.as_kind()),
+        _ => None,
+    };
+    match test {
+        NodeTest::Any => true,
+        NodeTest::Text => kind.is_some_and(|kind| kind.is_text()),
+        NodeTest::Comment => kind.is_some_and(|kind| kind.is_comment()),
+        NodeTest::ProcessingInstruction(target) => match kind {
+            Some(NodeKind::ProcessingInstruction(pi)) => target.as_ref().is_none_or(|target| *target == pi.target),
+            _ => false,
+        },
+        NodeTest::Principal => principal,
+        NodeTest::Namespace(namespace) => principal && qual_name(node).is_some_and(|name| name.ns == *namespace),
+        NodeTest::Name(namespace, local) => {
+            principal
+                && match node {
+                    XPathNode::Namespace { prefix, .. } => namespace.is_none() && prefix == local,
+                    _ => {
+                        let Some(name) = qual_name(node)
+                        else {
+                            return false;
+                        };
+                        let html = node.parent_or_self().as_data().is_some_and(|data| data.name.ns == ns!(html) && !data.xml);
+                        // Unprefixed names match elements of any namespace but only attributes without one.
+                        let namespace_matches = match namespace {
+                            Some(namespace) => name.ns == *namespace,
+                            None => matches!(node, XPathNode::Node(_)) || name.ns.is_empty(),
+                        };
+                        // Names in HTML documents are lowercase, but XPath is often written in upper case.
+                        let local_matches =
+                            if html { str::eq_ignore_ascii_case(&name.local, local) } else { *name.local == **local };
+                        namespace_matches && local_matches
+                    }
+                }
+        }
+    }
+}
+
+fn qual_name(node: XPathNode<'_>) -> Option<&QualName> {
+    match node {
+        XPathNode::Node(node) => node.as_data().map(|data| &data.name),
+        XPathNode::Attribute { name, .. } => Some(name),
+        XPathNode::Namespace { .. } => None,
+    }
+}
+
+/// Compares two values, which holds for node-sets if it holds for any of their nodes.
+fn compare(op: Comparison, left: &XPathValue<'_>, right: &XPathValue<'_>) -> bool {
+    match (left, right) {
+        (XPathValue::Nodes(left), XPathValue::Nodes(right)) => {
+            let right: Vec<_> = right.iter().map(|node| XPathValue::String(node.string_value())).collect();
+            left.iter().any(|node| {
+                let node = XPathValue::String(node.string_value());
+                right.iter().any(|other| compare_scalars(op, &node, other))
+            })
+        }
+        (XPathValue::Nodes(nodes), XPathValue::Boolean(_)) => {
+            compare_scalars(op, &XPathValue::Boolean(!nodes.is_empty()), right)
+        }
+        (XPathValue::Boolean(_), XPathValue::Nodes(_)) => compare(op.flip(), right, left),
+        (XPathValue::Nodes(nodes), other) => nodes.iter().any(|node| {
+            let value = match other {
+                XPathValue::Number(_) => XPathValue::Number(to_number(&node.string_value())),
+                _ => XPathValue::String(node.string_value()),
+            };
+            compare_scalars(op, &value, other)
+        }),
+        (_, XPathValue::Nodes(_)) => compare(op.flip(), right, left),
+        _ => compare_scalars(op, left, right),
+    }
+}
+
+/// Compares values which are not node-sets, converting them to a common type.
+fn compare_scalars(op: Comparison, left: &XPathValue<'_>, right: &XPathValue<'_>) -> bool {
+    let equal = match (left, right) {
+        _ if !matches!(op, Comparison::Eq | Comparison::Ne) => None,
+        (XPathValue::Boolean(_), _) | (_, XPathValue::Boolean(_)) => Some(left.boolean() == right.boolean()),
+        (XPathValue::Number(_), _) | (_, XPathValue::Number(_)) => Some(left.number() == right.number()),
+        _ => Some(left.string() == right.string()),
+    };
+    let (left, right) = (left.number(), right.number());
+    match op {
+        Comparison::Eq => equal.unwrap(),
+        Comparison::Ne => !equal.unwrap(),
+        Comparison::Lt => left < right,
+        Comparison::Le => left <= right,
+        Comparison::Gt => left > right,
+        Comparison::Ge => left >= right,
+    }
+}
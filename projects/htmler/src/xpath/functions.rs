@@ -0,0 +1,222 @@
+use std::collections::HashSet;
+
+use super::{
+    eval::{Context, Evaluator},
+    parser::Expr,
+    XPathNode, XPathValue,
+};
+use crate::error::XPathError;
+
+/// A function of the XPath 1.0 core library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Function {
+    Last,
+    Position,
+    Count,
+    Id,
+    LocalName,
+    NamespaceUri,
+    Name,
+    String,
+    Concat,
+    StartsWith,
+    Contains,
+    SubstringBefore,
+    SubstringAfter,
+    Substring,
+    StringLength,
+    NormalizeSpace,
+    Translate,
+    Boolean,
+    Not,
+    True,
+    False,
+    Lang,
+    Number,
+    Sum,
+    Floor,
+    Ceiling,
+    Round,
+}
+
+impl Function {
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        let function = match name {
+            "last" => Self::Last,
+            "position" => Self::Position,
+            "count" => Self::Count,
+            "id" => Self::Id,
+            "local-name" => Self::LocalName,
+            "namespace-uri" => Self::NamespaceUri,
+            "name" => Self::Name,
+            "string" => Self::String,
+            "concat" => Self::Concat,
+            "starts-with" => Self::StartsWith,
+            "contains" => Self::Contains,
+            "substring-before" => Self::SubstringBefore,
+            "substring-after" => Self::SubstringAfter,
+            "substring" => Self::Substring,
+            "string-length" => Self::StringLength,
+            "normalize-space" => Self::NormalizeSpace,
+            "translate" => Self::Translate,
+            "boolean" => Self::Boolean,
+            "not" => Self::Not,
+            "true" => Self::True,
+            "false" => Self::False,
+            "lang" => Self::Lang,
+            "number" => Self::Number,
+            "sum" => Self::Sum,
+            "floor" => Self::Floor,
+            "ceiling" => Self::Ceiling,
+            "round" => Self::Round,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    /// The least and most number of arguments.
+    pub(super) fn arity(self) -> (usize, usize) {
+        match self {
+            Self::Last | Self::Position | Self::True | Self::False => (0, 0),
+            Self::LocalName
+            | Self::NamespaceUri
+            | Self::Name
+            | Self::String
+            | Self::StringLength
+            | Self::NormalizeSpace
+            | Self::Number => (0, 1),
+            Self::Count
+            | Self::Id
+            | Self::Boolean
+            | Self::Not
+            | Self::Lang
+            | Self::Sum
+            | Self::Floor
+            | Self::Ceiling
+            | Self::Round => (1, 1),
+            Self::StartsWith | Self::Contains | Self::SubstringBefore | Self::SubstringAfter => (2, 2),
+            Self::Substring => (2, 3),
+            Self::Translate => (3, 3),
+            Self::Concat => (2, usize::MAX),
+        }
+    }
+}
+
+impl Evaluator {
+    /// Calls a function, whose arguments were checked against its arity when parsing.
+    pub(super) fn call<'a>(
+        &self,
+        function: Function,
+        args: &[Expr],
+        context: &Context<'a>,
+    ) -> Result<XPathValue<'a>, XPathError> {
+        let value = |i: usize| self.eval(&args[i], context);
+        let string = |i: usize| value(i).map(|v| v.string());
+        let number = |i: usize| value(i).map(|v| v.number());
+        // The first node of the argument, or the context node without one.
+        let node = || match args.first() {
+            Some(arg) => match self.eval(arg, context)? {
+                XPathValue::Nodes(nodes) => Ok(nodes.first().copied()),
+                _ => Err(XPathError::NotANodeSet),
+            },
+            None => Ok(Some(context.node)),
+        };
+        // The string value of the argument, or of the context node without one.
+        let string_or_context = || match args.first() {
+            Some(_) => string(0),
+            None => Ok(context.node.string_value()),
+        };
+        let result = match function {
+            Function::Last => XPathValue::Number(context.size as f64),
+            Function::Position => XPathValue::Number(context.position as f64),
+            Function::Count => XPathValue::Number(self.nodes(&args[0], context)?.len() as f64),
+            Function::Id => {
+                let ids = match value(0)? {
+                    XPathValue::Nodes(nodes) => nodes.iter().map(|n| n.string_value()).collect::<Vec<_>>().join(" "),
+                    other => other.string(),
+                };
+                let ids: HashSet<_> = ids.split(is_space).filter(|id| !id.is_empty()).collect();
+                let root = self.root(context.node);
+                let elements =
+                    root.descendants().filter(|n| n.as_data().and_then(|d| d.id()).is_some_and(|id| ids.contains(id)));
+                XPathValue::Nodes(elements.map(XPathNode::Node).collect())
+            }
+            Function::LocalName => XPathValue::String(node()?.map(|n| n.local_name().to_owned()).unwrap_or_default()),
+            Function::NamespaceUri => XPathValue::String(node()?.map(|n| n.namespace_uri().to_owned()).unwrap_or_default()),
+            Function::Name => XPathValue::String(node()?.map(|n| n.name()).unwrap_or_default()),
+            Function::String => XPathValue::String(string_or_context()?),
+            Function::Concat => XPathValue::String((0..args.len()).map(string).collect::<Result<_, _>>()?),
+            Function::StartsWith => XPathValue::Boolean(string(0)?.starts_with(&string(1)?)),
+            Function::Contains => XPathValue::Boolean(string(0)?.contains(&string(1)?)),
+            Function::SubstringBefore => {
+                let (s, pattern) = (string(0)?, string(1)?);
+                XPathValue::String(s.find(&pattern).map(|i| s[..i].to_owned()).unwrap_or_default())
+            }
+            Function::SubstringAfter => {
+                let (s, pattern) = (string(0)?, string(1)?);
+                XPathValue::String(s.find(&pattern).map(|i| s[i + pattern.len()..].to_owned()).unwrap_or_default())
+            }
+            Function::Substring => {
+                let s = string(0)?;
+                let start = round(number(1)?);
+                let end = if args.len() > 2 { start + round(number(2)?) } else { f64::INFINITY };
+                let chars = s.chars().enumerate().filter(|(i, _)| (*i + 1) as f64 >= start && ((*i + 1) as f64) < end);
+                XPathValue::String(chars.map(|(_, c)| c).collect())
+            }
+            Function::StringLength => XPathValue::Number(string_or_context()?.chars().count() as f64),
+            Function::NormalizeSpace => {
+                let s = string_or_context()?;
+                XPathValue::String(s.split(is_space).filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" "))
+            }
+            Function::Translate => {
+                let (s, from, to) = (string(0)?, string(1)?, string(2)?);
+                let to: Vec<char> = to.chars().collect();
+                let translated = s.chars().filter_map(|c| match from.chars().position(|f| f == c) {
+                    Some(i) => to.get(i).copied(),
+                    None => Some(c),
+                });
+                XPathValue::String(translated.collect())
+            }
+            Function::Boolean => XPathValue::Boolean(value(0)?.boolean()),
+            Function::Not => XPathValue::Boolean(!value(0)?.boolean()),
+            Function::True => XPathValue::Boolean(true),
+            Function::False => XPathValue::Boolean(false),
+            Function::Lang => {
+                let lang = string(0)?.to_ascii_lowercase();
+                let element = context.node.parent_or_self();
+                let declared = std::iter::once(element).chain(element.ancestors()).find_map(|e| {
+                    let data = e.as_data()?;
+                    data.attrs.iter().find(|(name, _)| &*name.local == "lang").map(|(_, value)| value.to_ascii_lowercase())
+                });
+                XPathValue::Boolean(declared.is_some_and(|declared| {
+                    declared == lang || declared.strip_prefix(&lang).is_some_and(|rest| rest.starts_with('-'))
+                }))
+            }
+            Function::Number => XPathValue::Number(match args.first() {
+                Some(_) => number(0)?,
+                None => super::to_number(&context.node.string_value()),
+            }),
+            Function::Sum => {
+                XPathValue::Number(self.nodes(&args[0], context)?.iter().map(|n| super::to_number(&n.string_value())).sum())
+            }
+            Function::Floor => XPathValue::Number(number(0)?.floor()),
+            Function::Ceiling => XPathValue::Number(number(0)?.ceil()),
+            Function::Round => XPathValue::Number(round(number(0)?)),
+        };
+        Ok(result)
+    }
+}
+
+/// Rounds half up, as opposed to away from zero, keeping negative zero.
+fn round(n: f64) -> f64 {
+    if !n.is_finite() || n.fract() == 0.0 {
+        return n;
+    }
+    let rounded = (n + 0.5).floor();
+    if rounded == 0.0 && n < 0.0 { -0.0 } else { rounded }
+}
+
+/// The whitespace of XML.
+pub(super) fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
@@ -0,0 +1,373 @@
+//! XPath 1.0 queries.
+//!
+//! Expressions are evaluated over the tree as XPath sees an XML document. Element and attribute names of HTML
+//! documents match case-insensitively, and unprefixed element names match elements of any namespace, so `//svg` finds
+//! SVG elements. The prefixes `html`, `svg`, `math`, `xlink`, `xml` and `xmlns` are bound to their namespaces. The
+//! contents of `<template>` elements are not part of the tree, as with [`Html::select`].
+
+use html5ever::QualName;
+
+use crate::{error::XPathError, Html, Node, NodeKind};
+
+use self::{
+    eval::{Context, Evaluator},
+    parser::Expr,
+};
+
+mod eval;
+mod functions;
+mod parser;
+
+/// A parsed XPath 1.0 expression.
+///
+/// # Examples
+///
+/// ```
+/// use htmler::{xpath::XPath, Html};
+///
+/// let html =
+///     Html::parse_fragment(r#"<ul><li><a href="/a">A</a></li><li><a href="/b">B</a></li></ul>"#);
+/// let hrefs = html.xpath(&XPath::parse("//li[last()]/a/@href").unwrap()).unwrap();
+/// assert_eq!(hrefs.string(), "/b");
+/// let count = html.xpath(&XPath::parse("count(//a[starts-with(@href, '/')])").unwrap()).unwrap();
+/// assert_eq!(count.number(), 2.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPath {
+    expr: Expr,
+}
+
+/// The result of an XPath expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XPathValue<'a> {
+    /// Nodes in document order.
+    Nodes(Vec<XPathNode<'a>>),
+    /// A boolean.
+    Boolean(bool),
+    /// A number.
+    Number(f64),
+    /// A string.
+    String(String),
+}
+
+/// A node as XPath sees it: a node of the tree, or an attribute or namespace of an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XPathNode<'a> {
+    /// A node of the tree.
+    Node(Node<'a>),
+    /// An attribute.
+    Attribute {
+        /// The element with the attribute.
+        element: Node<'a>,
+        /// The attribute name.
+        name: &'a QualName,
+        /// The attribute value.
+        value: &'a str,
+    },
+    /// A namespace in scope of an element.
+    Namespace {
+        /// The element.
+        element: Node<'a>,
+        /// The prefix, empty for the default namespace.
+        prefix: &'a str,
+        /// The namespace URI.
+        uri: &'a str,
+    },
+}
+
+impl XPath {
+    /// Parses an expression.
+    pub fn parse(expression: &str) -> Result<Self, XPathError> {
+        Ok(XPath { expr: parser::parse(expression)? })
+    }
+
+    /// Evaluates the expression with a node as the context node.
+    ///
+    /// Fails if a node-set is required where the expression gives another value, or a variable is referenced.
+    pub fn evaluate<'a>(&self, node: Node<'a>) -> Result<XPathValue<'a>, XPathError> {
+        Evaluator::default().eval(&self.expr, &Context { node: XPathNode::Node(node), position: 1, size: 1 })
+    }
+}
+
+impl Html {
+    /// Evaluates an XPath expression with the root as the context node.
+    pub fn xpath(&self, xpath: &XPath) -> Result<XPathValue<'_>, XPathError> {
+        xpath.evaluate(self.to_node(self.tree.root()))
+    }
+}
+
+impl<'a> Node<'a> {
+    /// Evaluates an XPath expression with this node as the context node.
+    pub fn xpath(&self, xpath: &XPath) -> Result<XPathValue<'a>, XPathError> {
+        xpath.evaluate(*self)
+    }
+}
+
+impl<'a> XPathValue<'a> {
+    /// Converts the value as the `boolean()` function does.
+    pub fn boolean(&self) -> bool {
+        match self {
+            Self::Nodes(nodes) => !nodes.is_empty(),
+            Self::Boolean(b) => *b,
+            Self::Number(n) => *n != 0.0 && !n.is_nan(),
+            Self::String(s) => !s.is_empty(),
+        }
+    }
+
+    /// Converts the value as the `number()` function does.
+    pub fn number(&self) -> f64 {
+        match self {
+            Self::Boolean(b) => u8::from(*b) as f64,
+            Self::Number(n) => *n,
+            _ => to_number(&self.string()),
+        }
+    }
+
+    /// Converts the value as the `string()` function does, taking the first node of a node-set.
+    pub fn string(&self) -> String {
+        match self {
+            Self::Nodes(nodes) => nodes.first().map(|node| node.string_value()).unwrap_or_default(),
+            Self::Boolean(b) => b.to_string(),
+            Self::Number(n) => number_to_string(*n),
+            Self::String(s) => s.clone(),
+        }
+    }
+
+    /// Returns the nodes of a node-set.
+    pub fn into_nodes(self) -> Option<Vec<XPathNode<'a>>> {
+        match self {
+            Self::Nodes(nodes) => Some(nodes),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> XPathNode<'a> {
+    /// Returns the tree node, or `None` for an attribute or namespace.
+    pub fn as_node(&self) -> Option<Node<'a>> {
+        match self {
+            Self::Node(node) => Some(*node),
+            _ => None,
+        }
+    }
+
+    /// Returns the string value: the text of an element or root, or the value of other nodes.
+    pub fn string_value(&self) -> String {
+        match self {
+            Self::Node(node) => match node.as_kind() {
+                NodeKind::Document | NodeKind::Fragment | NodeKind::Element(_) => node.text().collect(),
+                NodeKind::Text(text) => text.text.clone(),
+                NodeKind::Comment(comment) => comment.comment.clone(),
+                NodeKind::ProcessingInstruction(pi) => pi.data.clone(),
+                NodeKind::Doctype(_) => String::new(),
+            },
+            Self::Attribute { value, .. } => value.to_string(),
+            Self::Namespace { uri, .. } => uri.to_string(),
+        }
+    }
+
+    /// Returns the name without prefix, as the `local-name()` function does.
+    pub fn local_name(&self) -> &'a str {
+        match self {
+            Self::Node(node) => match node.as_kind() {
+                NodeKind::Element(element) => &element.name.local,
+                NodeKind::ProcessingInstruction(pi) => &pi.target,
+                _ => "",
+            },
+            Self::Attribute { name, .. } => &name.local,
+            Self::Namespace { prefix, .. } => prefix,
+        }
+    }
+
+    /// Returns the namespace URI of an element or attribute, as the `namespace-uri()` function does.
+    pub fn namespace_uri(&self) -> &'a str {
+        match self {
+            Self::Node(node) => node.as_data().map_or("", |data| &data.name.ns),
+            Self::Attribute { name, .. } => &name.ns,
+            Self::Namespace { .. } => "",
+        }
+    }
+
+    /// Returns the name with its prefix, as the `name()` function does.
+    pub fn name(&self) -> String {
+        let name = match self {
+            Self::Node(node) => node.as_data().map(|data| &data.name),
+            Self::Attribute { name, .. } => Some(*name),
+            Self::Namespace { .. } => None,
+        };
+        match name.and_then(|name| name.prefix.as_ref()) {
+            Some(prefix) => format!("{}:{}", prefix, self.local_name()),
+            None => self.local_name().to_owned(),
+        }
+    }
+
+    /// Returns the node itself, or the element of an attribute or namespace.
+    fn parent_or_self(&self) -> Node<'a> {
+        match self {
+            Self::Node(node) => *node,
+            Self::Attribute { element, .. } | Self::Namespace { element, .. } => *element,
+        }
+    }
+}
+
+/// Converts a string to a number, only accepting an optional minus sign and decimal digits.
+fn to_number(s: &str) -> f64 {
+    let s = s.trim_matches(functions::is_space);
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let valid = digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1
+        && digits.chars().any(|c| c.is_ascii_digit());
+    if valid { s.parse().unwrap_or(f64::NAN) } else { f64::NAN }
+}
+
+/// Formats a number without an exponent, and integers without a decimal point.
+fn number_to_string(n: f64) -> String {
+    match n {
+        _ if n.is_nan() => "NaN".to_owned(),
+        f64::INFINITY => "Infinity".to_owned(),
+        f64::NEG_INFINITY => "-Infinity".to_owned(),
+        _ if n == 0.0 => "0".to_owned(),
+        _ => n.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{XPath, XPathNode, XPathValue};
+    use crate::{error::XPathError, Html};
+
+    const SOURCE: &str = r#"<!DOCTYPE html>
+        <html lang="en-GB"><body>
+        <div id="main" class="card"><h1>Title</h1><!-- note --><p class="a">one <b>two</b></p><p>three</p></div>
+        <ul><li>1</li><li>2</li><li>3</li></ul>
+        <svg><a xlink:href="x"></a></svg>
+        </body></html>"#;
+
+    fn eval<'a>(html: &'a Html, expr: &str) -> XPathValue<'a> {
+        html.xpath(&XPath::parse(expr).unwrap()).unwrap()
+    }
+
+    fn strings(html: &Html, expr: &str) -> Vec<String> {
+        eval(html, expr).into_nodes().unwrap().iter().map(|n| n.string_value()).collect()
+    }
+
+    fn names(html: &Html, expr: &str) -> Vec<String> {
+        eval(html, expr).into_nodes().unwrap().iter().map(|n| n.name()).collect()
+    }
+
+    #[test]
+    fn test_paths() {
+        let html = Html::parse_document(SOURCE);
+        assert_eq!(strings(&html, "//li"), ["1", "2", "3"]);
+        assert_eq!(strings(&html, "/html/body/ul/li[2]"), ["2"]);
+        assert_eq!(strings(&html, "//LI[last()]"), ["3"]);
+        assert_eq!(strings(&html, "//li[position() > 1][1]"), ["2"]);
+        assert_eq!(strings(&html, "(//li)[last()]"), ["3"]);
+        assert_eq!(strings(&html, "//div/p[@class='a']/b/text()"), ["two"]);
+        assert_eq!(strings(&html, "//p[b]"), ["one two"]);
+        assert_eq!(strings(&html, "//div/comment()"), [" note "]);
+        assert_eq!(strings(&html, "//*[@id='main']/@class"), ["card"]);
+        assert_eq!(strings(&html, "//li[. = '2'] | //h1"), ["Title", "2"]);
+        assert_eq!(strings(&html, "id('main')/h1"), ["Title"]);
+        assert_eq!(names(&html, "//svg/a/@xlink:href"), ["xlink:href"]);
+        assert_eq!(names(&html, "//svg:*"), ["svg", "a"]);
+        assert_eq!(names(&html, "/*"), ["html"]);
+        assert_eq!(names(&html, "//p/.."), ["div"]);
+    }
+
+    #[test]
+    fn test_axes() {
+        let html = Html::parse_document(SOURCE);
+        assert_eq!(names(&html, "//b/ancestor::*"), ["html", "body", "div", "p"]);
+        assert_eq!(names(&html, "//b/ancestor::*[1]"), ["p"]);
+        assert_eq!(names(&html, "//b/ancestor-or-self::*[2]"), ["p"]);
+        assert_eq!(strings(&html, "//li[2]/following-sibling::li"), ["3"]);
+        assert_eq!(strings(&html, "//li[3]/preceding-sibling::li[1]"), ["2"]);
+        assert_eq!(names(&html, "//h1/following::*[position() < 4]"), ["p", "b", "p"]);
+        assert_eq!(names(&html, "//ul/preceding::*[1]"), ["p"]);
+        assert_eq!(names(&html, "//p[2]/preceding::*"), ["head", "h1", "p", "b"]);
+        assert_eq!(names(&html, "//ul/descendant::*"), ["li", "li", "li"]);
+        assert_eq!(names(&html, "//ul/descendant-or-self::*[1]"), ["ul"]);
+        assert_eq!(names(&html, "//li/parent::*"), ["ul"]);
+        assert_eq!(names(&html, "//ul/self::ul"), ["ul"]);
+        assert_eq!(names(&html, "//div/attribute::*"), ["id", "class"]);
+        assert_eq!(names(&html, "//div/@id/parent::*"), ["div"]);
+        assert_eq!(names(&html, "//div/@id/following::*[1]"), ["h1"]);
+        let namespaces = eval(&html, "//a/namespace::*").into_nodes().unwrap();
+        let prefixes: Vec<_> = namespaces.iter().map(|n| n.local_name()).collect();
+        assert_eq!(prefixes, ["", "xml"]);
+        assert!(matches!(namespaces[0], XPathNode::Namespace { uri: "http://www.w3.org/2000/svg", .. }));
+        // The doctype is not a node in XPath.
+        assert_eq!(eval(&html, "count(/node())").number(), 1.0);
+    }
+
+    #[test]
+    fn test_functions() {
+        let html = Html::parse_document(SOURCE);
+        let string = |expr| eval(&html, expr).string();
+        assert_eq!(string("string(//p)"), "one two");
+        assert_eq!(string("concat('a', 1, true())"), "a1true");
+        assert_eq!(string("substring('12345', 1.5, 2.6)"), "234");
+        assert_eq!(string("substring('12345', 0, 3)"), "12");
+        assert_eq!(string("substring-before('1999/04/01', '/')"), "1999");
+        assert_eq!(string("substring-after('1999/04/01', '/')"), "04/01");
+        assert_eq!(string("normalize-space('  a \n b ')"), "a b");
+        assert_eq!(string("translate('--aaa--', 'abc-', 'ABC')"), "AAA");
+        assert_eq!(string("local-name(//svg/a/@*)"), "href");
+        assert_eq!(string("namespace-uri(//svg)"), "http://www.w3.org/2000/svg");
+        assert_eq!(string("name(//svg/a/@*)"), "xlink:href");
+        assert_eq!(string("string-length(//h1)"), "5");
+        assert_eq!(string("sum(//li)"), "6");
+        assert_eq!(string("count(//li) div 4"), "0.75");
+        assert_eq!(string("-7 mod 3"), "-1");
+        assert_eq!(string("1 div 0"), "Infinity");
+        assert_eq!(string("number('a')"), "NaN");
+        assert_eq!(string("round(-2.5)"), "-2");
+        assert_eq!(string("floor(2.5) + ceiling(2.5)"), "5");
+        assert_eq!(eval(&html, "contains(//h1, 'itl')"), XPathValue::Boolean(true));
+        assert_eq!(eval(&html, "starts-with(//h1, 'itl')"), XPathValue::Boolean(false));
+        assert_eq!(eval(&html, "boolean(//table) or not(//ul)"), XPathValue::Boolean(false));
+        assert_eq!(eval(&html, "count(//*[lang('en')])"), eval(&html, "count(//*)"));
+        assert_eq!(eval(&html, "//li[lang('fr')]"), XPathValue::Nodes(Vec::new()));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let html = Html::parse_document(SOURCE);
+        let boolean = |expr| eval(&html, expr).boolean();
+        assert!(boolean("//li = 2"));
+        assert!(boolean("//li != 2"));
+        assert!(!boolean("//li = 4"));
+        assert!(boolean("//li > 2"));
+        assert!(boolean("2 < //li"));
+        assert!(boolean("//li = '3'"));
+        assert!(boolean("//li = //p/../../ul/li"));
+        assert!(boolean("//li = true()"));
+        assert!(!boolean("//table = true()"));
+        assert!(boolean("'1.0' = 1"));
+        assert!(!boolean("'1.0' = '1'"));
+        assert!(boolean("true() = 'x'"));
+        assert!(boolean("1 < 2 and 2 <= 2 and 3 >= 2"));
+    }
+
+    #[test]
+    fn test_node_context() {
+        let html = Html::parse_document(SOURCE);
+        let ul = html.select_first("ul").unwrap().unwrap();
+        let items = ul.xpath(&XPath::parse("li[position() != 2]").unwrap()).unwrap();
+        let items: Vec<_> = items.into_nodes().unwrap().iter().map(|n| n.as_node().unwrap().inner_html()).collect();
+        assert_eq!(items, ["1", "3"]);
+        assert_eq!(ul.xpath(&XPath::parse("count(/html)").unwrap()).unwrap().number(), 1.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let html = Html::parse_document(SOURCE);
+        let error = |expr| html.xpath(&XPath::parse(expr).unwrap()).unwrap_err();
+        assert_eq!(error("'a'/b"), XPathError::NotANodeSet);
+        assert_eq!(error("1 | //a"), XPathError::NotANodeSet);
+        assert_eq!(error("count(1)"), XPathError::NotANodeSet);
+        assert_eq!(error("$x"), XPathError::UnknownVariable { name: "x".into() });
+        assert_eq!(XPath::parse("//p[").unwrap_err(), XPathError::Syntax { position: 4 });
+    }
+}
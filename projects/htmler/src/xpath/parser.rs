@@ -0,0 +1,670 @@
+use std::{iter::Peekable, str::CharIndices};
+
+use html5ever::Namespace;
+
+use super::functions::Function;
+use crate::error::XPathError;
+
+/// A parsed expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+    Arithmetic(Arithmetic, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    Literal(String),
+    Number(f64),
+    Variable(String),
+    Function(Function, Vec<Expr>),
+    /// A primary expression followed by predicates.
+    Filter(Box<Expr>, Vec<Expr>),
+    /// Steps from the node-set of an expression.
+    Path(Box<Expr>, Vec<Step>),
+    /// The root of the tree of the context node.
+    Root,
+    /// The context node.
+    ContextNode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+/// A location step.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Step {
+    pub(super) axis: Axis,
+    pub(super) test: NodeTest,
+    pub(super) predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Namespace,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    Self_,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum NodeTest {
+    /// `node()`
+    Any,
+    /// `text()`
+    Text,
+    /// `comment()`
+    Comment,
+    /// `processing-instruction()`, with an optional target.
+    ProcessingInstruction(Option<String>),
+    /// `*`, nodes of the principal type of the axis.
+    Principal,
+    /// `prefix:*`
+    Namespace(Namespace),
+    /// A name with an optional prefix.
+    Name(Option<Namespace>, String),
+}
+
+impl Comparison {
+    /// The comparison with the operands swapped.
+    pub(super) fn flip(self) -> Self {
+        match self {
+            Self::Lt => Self::Gt,
+            Self::Le => Self::Ge,
+            Self::Gt => Self::Lt,
+            Self::Ge => Self::Le,
+            other => other,
+        }
+    }
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Option<Self> {
+        let axis = match name {
+            "ancestor" => Self::Ancestor,
+            "ancestor-or-self" => Self::AncestorOrSelf,
+            "attribute" => Self::Attribute,
+            "child" => Self::Child,
+            "descendant" => Self::Descendant,
+            "descendant-or-self" => Self::DescendantOrSelf,
+            "following" => Self::Following,
+            "following-sibling" => Self::FollowingSibling,
+            "namespace" => Self::Namespace,
+            "parent" => Self::Parent,
+            "preceding" => Self::Preceding,
+            "preceding-sibling" => Self::PrecedingSibling,
+            "self" => Self::Self_,
+            _ => return None,
+        };
+        Some(axis)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Dot,
+    DotDot,
+    At,
+    Comma,
+    ColonColon,
+    Slash,
+    DoubleSlash,
+    Pipe,
+    Plus,
+    Minus,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Multiply,
+    And,
+    Or,
+    Mod,
+    Div,
+    Literal(String),
+    Number(f64),
+    Variable(String),
+    /// A name, possibly with a prefix.
+    Name(String),
+    /// `*` or `prefix:*` in a name test.
+    Wildcard(Option<String>),
+}
+
+impl Token {
+    /// Whether a following `*` or name is an operand rather than an operator, as the spec disambiguates them.
+    fn expects_operand(&self) -> bool {
+        !matches!(
+            self,
+            Token::RParen
+                | Token::RBracket
+                | Token::Dot
+                | Token::DotDot
+                | Token::Literal(_)
+                | Token::Number(_)
+                | Token::Variable(_)
+                | Token::Name(_)
+                | Token::Wildcard(_)
+        )
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start(c) || c.is_ascii_digit() || c == '.' || c == '-'
+}
+
+/// Splits an expression into tokens with their byte offsets.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, XPathError> {
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let operand = tokens.last().is_none_or(|(token, _)| token.expects_operand());
+        let mut next_is = |expected: char| chars.next_if(|(_, c)| *c == expected).is_some();
+        let token = match c {
+            ' ' | '\t' | '\n' | '\r' => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            '@' => Token::At,
+            ',' => Token::Comma,
+            '|' => Token::Pipe,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '=' => Token::Eq,
+            '!' if next_is('=') => Token::Ne,
+            '<' if next_is('=') => Token::Le,
+            '<' => Token::Lt,
+            '>' if next_is('=') => Token::Ge,
+            '>' => Token::Gt,
+            ':' if next_is(':') => Token::ColonColon,
+            '/' if next_is('/') => Token::DoubleSlash,
+            '/' => Token::Slash,
+            '*' if operand => Token::Wildcard(None),
+            '*' => Token::Multiply,
+            '.' if next_is('.') => Token::DotDot,
+            '.' if !chars.peek().is_some_and(|(_, c)| c.is_ascii_digit()) => Token::Dot,
+            '0'..='9' | '.' => {
+                let mut end = start + 1;
+                while let Some((i, c)) = chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '.') {
+                    end = i + c.len_utf8();
+                }
+                let number = &source[start..end];
+                if number.matches('.').count() > 1 {
+                    return Err(XPathError::Syntax { position: start });
+                }
+                Token::Number(number.parse().map_err(|_| XPathError::Syntax { position: start })?)
+            }
+            '"' | '\'' => {
+                let end = source[start + 1..].find(c).ok_or(XPathError::Syntax { position: start })? + start + 1;
+                while chars.next_if(|(i, _)| *i <= end).is_some() {}
+                Token::Literal(source[start + 1..end].to_owned())
+            }
+            '$' if chars.peek().is_some_and(|(_, c)| is_name_start(*c)) => match read_name(source, &mut chars) {
+                Token::Name(name) => Token::Variable(name),
+                _ => return Err(XPathError::Syntax { position: start }),
+            },
+            c if is_name_start(c) => {
+                let name = match read_name(source, &mut chars) {
+                    Token::Name(rest) => Token::Name(format!("{}{}", c, rest)),
+                    Token::Wildcard(Some(rest)) => Token::Wildcard(Some(format!("{}{}", c, rest))),
+                    other => other,
+                };
+                match name {
+                    _ if operand => name,
+                    Token::Name(name) => match name.as_str() {
+                        "and" => Token::And,
+                        "or" => Token::Or,
+                        "mod" => Token::Mod,
+                        "div" => Token::Div,
+                        _ => return Err(XPathError::Syntax { position: start }),
+                    },
+                    _ => return Err(XPathError::Syntax { position: start }),
+                }
+            }
+            _ => return Err(XPathError::Syntax { position: start }),
+        };
+        tokens.push((token, start));
+    }
+    Ok(tokens)
+}
+
+/// Reads the rest of a name, with a prefix if a single `:` follows, or a `prefix:*` wildcard.
+fn read_name(source: &str, chars: &mut Peekable<CharIndices>) -> Token {
+    let mut name = String::new();
+    while let Some((_, c)) = chars.next_if(|(_, c)| is_name_char(*c)) {
+        name.push(c);
+    }
+    let colon = chars.peek().map(|(i, _)| *i).filter(|i| source[*i..].starts_with(':') && !source[*i..].starts_with("::"));
+    if let Some(i) = colon {
+        match source[i + 1..].chars().next() {
+            Some('*') => {
+                chars.next();
+                chars.next();
+                return Token::Wildcard(Some(name));
+            }
+            Some(c) if is_name_start(c) => {
+                chars.next();
+                name.push(':');
+                while let Some((_, c)) = chars.next_if(|(_, c)| is_name_char(*c)) {
+                    name.push(c);
+                }
+            }
+            _ => {}
+        }
+    }
+    Token::Name(name)
+}
+
+/// Returns the namespace of a name test prefix.
+fn namespace(prefix: &str) -> Result<Namespace, XPathError> {
+    let namespace = match prefix {
+        "html" => ns!(html),
+        "svg" => ns!(svg),
+        "math" => ns!(mathml),
+        "xlink" => ns!(xlink),
+        "xml" => ns!(xml),
+        "xmlns" => ns!(xmlns),
+        _ => return Err(XPathError::UnknownPrefix { prefix: prefix.to_owned() }),
+    };
+    Ok(namespace)
+}
+
+/// How deeply expressions can nest, which bounds the recursion of the parser and evaluator.
+const MAX_DEPTH: usize = 128;
+
+/// Parses an expression.
+pub(super) fn parse(source: &str) -> Result<Expr, XPathError> {
+    let mut parser = Parser { tokens: tokenize(source)?, index: 0, end: source.len(), depth: 0 };
+    let expr = parser.expr()?;
+    match parser.tokens.get(parser.index) {
+        Some((_, position)) => Err(XPathError::Syntax { position: *position }),
+        None => Ok(expr),
+    }
+}
+
+/// A recursive descent parser following the grammar of the XPath 1.0 spec.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+    /// The length of the source, where errors at the end are reported.
+    end: usize,
+    /// How many expressions enclose the current one.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.index + offset).map(|(token, _)| token)
+    }
+
+    /// The byte offset of the next token.
+    fn position(&self) -> usize {
+        self.tokens.get(self.index).map_or(self.end, |(_, position)| *position)
+    }
+
+    fn error(&self) -> XPathError {
+        XPathError::Syntax { position: self.position() }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).map(|(token, _)| token.clone());
+        self.index += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matched = self.peek() == Some(token);
+        if matched {
+            self.index += 1;
+        }
+        matched
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), XPathError> {
+        if self.eat(token) { Ok(()) } else { Err(self.error()) }
+    }
+
+    fn expr(&mut self) -> Result<Expr, XPathError> {
+        if self.depth == MAX_DEPTH {
+            return Err(XPathError::TooDeep { position: self.position() });
+        }
+        self.depth += 1;
+        let expr = self.or();
+        self.depth -= 1;
+        expr
+    }
+
+    fn or(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.and()?;
+        while self.eat(&Token::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.equality()?;
+        while self.eat(&Token::And) {
+            left = Expr::And(Box::new(left), Box::new(self.equality()?));
+        }
+        Ok(left)
+    }
+
+    fn equality(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.relational()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Eq) => Comparison::Eq,
+                Some(Token::Ne) => Comparison::Ne,
+                _ => return Ok(left),
+            };
+            self.index += 1;
+            left = Expr::Compare(op, Box::new(left), Box::new(self.relational()?));
+        }
+    }
+
+    fn relational(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.additive()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Lt) => Comparison::Lt,
+                Some(Token::Le) => Comparison::Le,
+                Some(Token::Gt) => Comparison::Gt,
+                Some(Token::Ge) => Comparison::Ge,
+                _ => return Ok(left),
+            };
+            self.index += 1;
+            left = Expr::Compare(op, Box::new(left), Box::new(self.additive()?));
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => Arithmetic::Add,
+                Some(Token::Minus) => Arithmetic::Subtract,
+                _ => return Ok(left),
+            };
+            self.index += 1;
+            left = Expr::Arithmetic(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Multiply) => Arithmetic::Multiply,
+                Some(Token::Div) => Arithmetic::Divide,
+                Some(Token::Mod) => Arithmetic::Modulo,
+                _ => return Ok(left),
+            };
+            self.index += 1;
+            left = Expr::Arithmetic(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, XPathError> {
+        let mut negations = 0;
+        while self.eat(&Token::Minus) {
+            negations += 1;
+        }
+        let mut left = self.path()?;
+        while self.eat(&Token::Pipe) {
+            left = Expr::Union(Box::new(left), Box::new(self.path()?));
+        }
+        // `--x` is `x`, but a single negation still converts to a number.
+        if negations > 0 {
+            left = Expr::Negate(Box::new(left));
+            if negations % 2 == 0 {
+                left = Expr::Negate(Box::new(left));
+            }
+        }
+        Ok(left)
+    }
+
+    fn path(&mut self) -> Result<Expr, XPathError> {
+        match self.peek() {
+            Some(Token::Slash) => {
+                self.index += 1;
+                if !self.starts_step() {
+                    return Ok(Expr::Root);
+                }
+                Ok(Expr::Path(Box::new(Expr::Root), self.relative_path(Vec::new())?))
+            }
+            Some(Token::DoubleSlash) => {
+                self.index += 1;
+                Ok(Expr::Path(Box::new(Expr::Root), self.relative_path(vec![Step::descendant_or_self()])?))
+            }
+            Some(Token::Variable(_) | Token::LParen | Token::Literal(_) | Token::Number(_)) => self.filter_path(),
+            Some(Token::Name(name)) if self.peek_at(1) == Some(&Token::LParen) && !is_node_type(name) => self.filter_path(),
+            _ => Ok(Expr::Path(Box::new(Expr::ContextNode), self.relative_path(Vec::new())?)),
+        }
+    }
+
+    /// Parses a primary expression with its predicates, and the steps from it.
+    fn filter_path(&mut self) -> Result<Expr, XPathError> {
+        let mut expr = self.primary()?;
+        let predicates = self.predicates()?;
+        if !predicates.is_empty() {
+            expr = Expr::Filter(Box::new(expr), predicates);
+        }
+        let steps = match self.peek() {
+            Some(Token::Slash) => {
+                self.index += 1;
+                Vec::new()
+            }
+            Some(Token::DoubleSlash) => {
+                self.index += 1;
+                vec![Step::descendant_or_self()]
+            }
+            _ => return Ok(expr),
+        };
+        Ok(Expr::Path(Box::new(expr), self.relative_path(steps)?))
+    }
+
+    fn starts_step(&self) -> bool {
+        matches!(self.peek(), Some(Token::Dot | Token::DotDot | Token::At | Token::Name(_) | Token::Wildcard(_)))
+    }
+
+    fn relative_path(&mut self, mut steps: Vec<Step>) -> Result<Vec<Step>, XPathError> {
+        steps.push(self.step()?);
+        loop {
+            match self.peek() {
+                Some(Token::Slash) => {}
+                Some(Token::DoubleSlash) => steps.push(Step::descendant_or_self()),
+                _ => return Ok(steps),
+            }
+            self.index += 1;
+            steps.push(self.step()?);
+        }
+    }
+
+    fn step(&mut self) -> Result<Step, XPathError> {
+        let axis = match (self.peek(), self.peek_at(1)) {
+            (Some(Token::Dot), _) => {
+                self.index += 1;
+                return Ok(Step { axis: Axis::Self_, test: NodeTest::Any, predicates: Vec::new() });
+            }
+            (Some(Token::DotDot), _) => {
+                self.index += 1;
+                return Ok(Step { axis: Axis::Parent, test: NodeTest::Any, predicates: Vec::new() });
+            }
+            (Some(Token::At), _) => {
+                self.index += 1;
+                Axis::Attribute
+            }
+            (Some(Token::Name(name)), Some(Token::ColonColon)) => {
+                let axis = Axis::from_name(name).ok_or_else(|| self.error())?;
+                self.index += 2;
+                axis
+            }
+            _ => Axis::Child,
+        };
+        let test = self.node_test()?;
+        Ok(Step { axis, test, predicates: self.predicates()? })
+    }
+
+    fn node_test(&mut self) -> Result<NodeTest, XPathError> {
+        let error = self.error();
+        match self.next() {
+            Some(Token::Wildcard(None)) => Ok(NodeTest::Principal),
+            Some(Token::Wildcard(Some(prefix))) => Ok(NodeTest::Namespace(namespace(&prefix)?)),
+            Some(Token::Name(name)) if is_node_type(&name) && self.eat(&Token::LParen) => {
+                let test = match name.as_str() {
+                    "node" => NodeTest::Any,
+                    "text" => NodeTest::Text,
+                    "comment" => NodeTest::Comment,
+                    _ => match self.peek() {
+                        Some(Token::Literal(target)) => {
+                            let target = target.clone();
+                            self.index += 1;
+                            NodeTest::ProcessingInstruction(Some(target))
+                        }
+                        _ => NodeTest::ProcessingInstruction(None),
+                    },
+                };
+                self.expect(&Token::RParen)?;
+                Ok(test)
+            }
+            Some(Token::Name(name)) => match name.split_once(':') {
+                Some((prefix, local)) => Ok(NodeTest::Name(Some(namespace(prefix)?), local.to_owned())),
+                None => Ok(NodeTest::Name(None, name)),
+            },
+            _ => Err(error),
+        }
+    }
+
+    fn predicates(&mut self) -> Result<Vec<Expr>, XPathError> {
+        let mut predicates = Vec::new();
+        while self.eat(&Token::LBracket) {
+            predicates.push(self.expr()?);
+            self.expect(&Token::RBracket)?;
+        }
+        Ok(predicates)
+    }
+
+    fn primary(&mut self) -> Result<Expr, XPathError> {
+        let error = self.error();
+        match self.next() {
+            Some(Token::Variable(name)) => Ok(Expr::Variable(name)),
+            Some(Token::Literal(literal)) => Ok(Expr::Literal(literal)),
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::LParen) => {
+                let expr = self.expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) => {
+                let function = Function::from_name(&name).ok_or(XPathError::UnknownFunction { name: name.clone() })?;
+                self.expect(&Token::LParen)?;
+                let mut args = Vec::new();
+                if !self.eat(&Token::RParen) {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(&Token::RParen) {
+                            break;
+                        }
+                        self.expect(&Token::Comma)?;
+                    }
+                }
+                let (min, max) = function.arity();
+                if args.len() < min || args.len() > max {
+                    return Err(XPathError::ArgumentCount { function: name });
+                }
+                Ok(Expr::Function(function, args))
+            }
+            _ => Err(error),
+        }
+    }
+}
+
+impl Step {
+    /// The step `//` stands for.
+    fn descendant_or_self() -> Self {
+        Step { axis: Axis::DescendantOrSelf, test: NodeTest::Any, predicates: Vec::new() }
+    }
+}
+
+fn is_node_type(name: &str) -> bool {
+    matches!(name, "node" | "text" | "comment" | "processing-instruction")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Axis, Expr, NodeTest, Step, MAX_DEPTH};
+    use crate::error::XPathError;
+
+    #[test]
+    fn test_operator_names() {
+        // `div` is a name test at the start and an operator after an operand.
+        let steps = vec![Step { axis: Axis::Child, test: NodeTest::Name(None, "div".into()), predicates: Vec::new() }];
+        let div = Expr::Path(Box::new(Expr::ContextNode), steps);
+        assert_eq!(parse("div").unwrap(), div);
+        assert!(matches!(parse("div div div").unwrap(), Expr::Arithmetic(..)));
+        assert!(matches!(parse("* * *").unwrap(), Expr::Arithmetic(..)));
+        assert_eq!(parse("a b"), Err(XPathError::Syntax { position: 2 }));
+    }
+
+    #[test]
+    fn test_nesting() {
+        let nested = |depth| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(parse(&nested(2000)), Err(XPathError::TooDeep { position: MAX_DEPTH }));
+        assert_eq!(parse(&"not(".repeat(2000)), Err(XPathError::TooDeep { position: 4 * MAX_DEPTH }));
+        assert_eq!(parse("---1"), Ok(Expr::Negate(Box::new(Expr::Number(1.0)))));
+        assert!(parse(&format!("{}1", "-".repeat(100_000))).is_ok());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse("//p["), Err(XPathError::Syntax { position: 4 }));
+        assert_eq!(parse("'a"), Err(XPathError::Syntax { position: 0 }));
+        assert_eq!(parse("sideways::p"), Err(XPathError::Syntax { position: 0 }));
+        assert_eq!(parse("foo()"), Err(XPathError::UnknownFunction { name: "foo".into() }));
+        assert_eq!(parse("not()"), Err(XPathError::ArgumentCount { function: "not".into() }));
+        assert_eq!(parse("//x:p"), Err(XPathError::UnknownPrefix { prefix: "x".into() }));
+        assert!(parse("/").is_ok());
+        assert!(parse("1.5.2").is_err());
+    }
+}
@@ -20,7 +20,7 @@ fn tag_with_newline() {
 
     let mut iter = document.select(&selector);
     let a = iter.next().unwrap();
-    assert_eq!(a.value().get_attribute("href"), Some("https://github.com/causal-agent/scraper"));
+    assert_eq!(a.as_data().unwrap().get_attribute("href"), Some("https://github.com/causal-agent/scraper"));
 }
 
 #[test]
@@ -1,7 +1,10 @@
 use crate::{ZhihuError, ZhihuResult};
-use htmler::{Html, Node, NodeKind, Selector};
+use htmler::{
+    visit::{Visitor, Walk},
+    Html, Node, Selector,
+};
 use std::{
-    fmt::{Display, Formatter, Write},
+    fmt::{Display, Formatter},
     io::Write as _,
     path::Path,
     str::FromStr,
@@ -90,77 +93,67 @@ impl ZhihuAnswer {
     fn extract_content(&mut self, html: &Html) -> ZhihuResult<()> {
         // div.RichContent-inner
         let selector = Selector::new("span.CopyrightRichText-richText");
-        let _: Option<_> = try {
-            let node = html.select(&selector).next()?;
-            for child in node.children() {
-                self.read_content_node(child).ok()?;
-            }
-        };
+        if let Some(node) = html.select(&selector).next() {
+            node.walk(&mut ContentWriter { content: &mut self.content });
+        }
         Ok(())
     }
-    fn read_content_node(&mut self, node: Node) -> ZhihuResult<()> {
-        match node.as_kind() {
-            NodeKind::Document => {
-                println!("document")
-            }
-            NodeKind::Fragment => {
-                println!("fragment")
-            }
-            NodeKind::Doctype(_) => {
-                println!("doctype")
-            }
-            NodeKind::Comment(_) => {
-                println!("comment")
+}
+
+/// Renders the rich text of an answer as markdown.
+struct ContentWriter<'s> {
+    content: &'s mut String,
+}
+
+impl<'a> Visitor<'a> for ContentWriter<'_> {
+    fn visit_element(&mut self, node: Node<'a>) -> Walk {
+        let e = node.as_data().unwrap();
+        match e.name() {
+            "p" => Walk::Continue,
+            "span" => {
+                // math mode
+                if e.has_class("ztext-math") {
+                    if let Some(s) = e.get_attribute("data-tex") {
+                        self.content.push_str(" $$");
+                        self.content.push_str(s);
+                        self.content.push_str("$$ ");
+                    }
+                    Walk::SkipChildren
+                }
+                // normal mode
+                else {
+                    Walk::Continue
+                }
             }
-            NodeKind::Text(t) => {
-                self.content.push_str(t.trim());
+            "br" => {
+                self.content.push('\n');
+                Walk::Continue
             }
-            NodeKind::Element(e) => {
-                match e.name() {
-                    "p" => {
-                        for child in node.children() {
-                            self.read_content_node(child)?;
-                        }
-                        self.content.push_str("\n\n");
-                    }
-                    "span" => {
-                        // math mode
-                        if e.has_class("ztext-math") {
-                            match e.get_attribute("data-tex") {
-                                Some(s) => {
-                                    self.content.push_str(" $$");
-                                    self.content.push_str(s);
-                                    self.content.push_str("$$ ");
-                                }
-                                None => {}
-                            }
-                        }
-                        // normal mode
-                        else {
-                            for child in node.children() {
-                                self.read_content_node(child)?;
-                            }
-                        }
+            "figure" => {
+                for child in node.descendants().filter(|e| e.has_class("img")) {
+                    let original = child.get_attribute("data-original");
+                    if !original.is_empty() {
+                        self.content.push_str("![](");
+                        self.content.push_str(original);
+                        self.content.push(')');
+                        break;
                     }
-                    "br" => {
-                        self.content.push_str("\n");
-                    }
-                    "figure" => {
-                        for child in node.descendants().filter(|e| e.has_class("img")) {
-                            let original = child.get_attribute("data-original");
-                            if !original.is_empty() {
-                                write!(self.content, "![]({})", original)?;
-                                break;
-                            }
-                        }
-                    }
-                    unknown => panic!("unknown element: {unknown}"),
                 }
+                Walk::SkipChildren
             }
-            NodeKind::ProcessingInstruction(_) => {
-                println!("processing instruction");
-            }
+            unknown => panic!("unknown element: {unknown}"),
         }
-        Ok(())
+    }
+
+    fn leave_element(&mut self, node: Node<'a>) -> Walk {
+        if node.is_a("p") {
+            self.content.push_str("\n\n");
+        }
+        Walk::Continue
+    }
+
+    fn visit_text(&mut self, _: Node<'a>, text: &'a str) -> Walk {
+        self.content.push_str(text.trim());
+        Walk::Continue
     }
 }